@@ -1,9 +1,9 @@
 use crate::grapheme_iter::GraphemeIter;
+use crate::util;
 use std::fmt::{self, Write as FmtWrite};
 use std::io::{self, Write};
 use std::iter::FromIterator;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
 
 /// A modification performed on a `Buffer`. These are used for the purpose of undo/redo.
 #[derive(Debug, Clone)]
@@ -55,12 +55,19 @@ impl Action {
 /// A buffer for text in the line editor.
 ///
 /// It keeps track of each action performed on it for use with undo/redo.
+///
+/// Positions taken and returned by its methods, like the editor's cursor, count grapheme
+/// clusters (what shows as one character on screen) unless the name says otherwise. Use
+/// `grapheme_to_char` and `char_to_grapheme` to convert to and from counts of `char`s.
 #[derive(Debug, Clone)]
 pub struct Buffer {
     data: String,
     actions: Vec<Action>,
     undone_actions: Vec<Action>,
+    // StartGroups less EndGroups in `actions`
+    open_groups: isize,
     register: Option<String>,
+    register_linewise: bool,
     curr_num_graphemes: usize,
     grapheme_indices: Vec<usize>,
 }
@@ -91,6 +98,12 @@ impl<'a> From<&'a str> for Buffer {
     }
 }
 
+impl AsRef<str> for Buffer {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
 impl fmt::Display for Buffer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let chars = self.data.chars();
@@ -109,7 +122,9 @@ impl FromIterator<char> for Buffer {
             data: str,
             actions: Vec::new(),
             undone_actions: Vec::new(),
+            open_groups: 0,
             register: None,
+            register_linewise: false,
             curr_num_graphemes: g_idxs.len(),
             grapheme_indices: g_idxs,
         }
@@ -128,7 +143,9 @@ impl Buffer {
             data: String::new(),
             actions: Vec::new(),
             undone_actions: Vec::new(),
+            open_groups: 0,
             register: None,
+            register_linewise: false,
             curr_num_graphemes: 0,
             grapheme_indices: Vec::new(),
         }
@@ -137,14 +154,22 @@ impl Buffer {
     pub fn clear_actions(&mut self) {
         self.actions.clear();
         self.undone_actions.clear();
+        self.open_groups = 0;
     }
 
     pub fn start_undo_group(&mut self) {
         self.actions.push(Action::StartGroup);
+        self.open_groups += 1;
     }
 
     pub fn end_undo_group(&mut self) {
         self.actions.push(Action::EndGroup);
+        self.open_groups -= 1;
+    }
+
+    /// Returns true if an undo group has been started and not yet ended.
+    pub fn in_undo_group(&self) -> bool {
+        self.open_groups > 0
     }
 
     pub fn undo(&mut self) -> Option<usize> {
@@ -162,8 +187,12 @@ impl Buffer {
                 EndGroup => {
                     group_nest += 1;
                     group_count = 0;
+                    self.open_groups += 1;
+                }
+                StartGroup => {
+                    group_nest -= 1;
+                    self.open_groups -= 1;
                 }
-                StartGroup => group_nest -= 1,
                 // count the actions in this group so we can ignore empty groups below
                 _ => group_count += 1,
             }
@@ -191,8 +220,12 @@ impl Buffer {
                 StartGroup => {
                     group_nest += 1;
                     group_count = 0;
+                    self.open_groups += 1;
+                }
+                EndGroup => {
+                    group_nest -= 1;
+                    self.open_groups -= 1;
                 }
-                EndGroup => group_nest -= 1,
                 // count the actions in this group so we can ignore empty groups below
                 _ => group_count += 1,
             }
@@ -241,14 +274,69 @@ impl Buffer {
         self.lines().count()
     }
 
+    /// The position the line holding `cursor` starts at, just after the previous newline.
+    pub fn line_start(&self, cursor: usize) -> usize {
+        let after_newline = self
+            .range_graphemes_until(cursor)
+            .rev()
+            .position(|s| s == "\n")
+            .unwrap_or(cursor);
+        cursor - after_newline
+    }
+
+    /// The position the line holding `cursor` ends at, just before the next newline.
+    pub fn line_end(&self, cursor: usize) -> usize {
+        let before_newline = self
+            .range_graphemes_from(cursor)
+            .position(|s| s == "\n")
+            .unwrap_or_else(|| self.num_graphemes() - cursor);
+        cursor + before_newline
+    }
+
+    /// Length in grapheme clusters, the unit of every position in the buffer.
     pub fn num_graphemes(&self) -> usize {
         self.curr_num_graphemes
     }
 
+    /// Length in `char`s (Unicode scalar values). A grapheme can hold several of them.
+    pub fn num_chars(&self) -> usize {
+        self.data.chars().count()
+    }
+
+    /// How many `char`s come before the grapheme at `idx`. Past the end gives `num_chars`.
+    pub fn grapheme_to_char(&self, idx: usize) -> usize {
+        match self.grapheme_indices.get(idx) {
+            Some(&byte) => self.data[..byte].chars().count(),
+            None => self.num_chars(),
+        }
+    }
+
+    /// The grapheme holding the `char` at `idx`, so every `char` of a grapheme maps to the same
+    /// position. Past the end gives `num_graphemes`.
+    pub fn char_to_grapheme(&self, idx: usize) -> usize {
+        match self.data.char_indices().nth(idx) {
+            Some((byte, _)) => {
+                self.grapheme_indices
+                    .partition_point(|&start| start <= byte)
+                    - 1
+            }
+            None => self.num_graphemes(),
+        }
+    }
+
+    /// The text of the buffer. It is stored as a `String`, so this is free and does not copy,
+    /// for highlighters and other code that scans the whole line on every key. Offsets into it
+    /// are bytes while every position in the buffer API is a grapheme, see `range` to go from
+    /// graphemes to text.
+    pub fn as_str(&self) -> &str {
+        &self.data
+    }
+
     pub fn lines(&self) -> impl Iterator<Item = &str> + '_ {
         self.data.split('\n')
     }
 
+    /// Length in bytes of the UTF-8 text.
     pub fn num_bytes(&self) -> usize {
         self.data.as_bytes().len()
     }
@@ -257,10 +345,12 @@ impl Buffer {
         GraphemeIter::new(&self.data, &self.grapheme_indices, 0, self.num_graphemes()).get(cursor)
     }
 
+    /// The grapheme just before position `cursor`, None at the start.
     pub fn grapheme_before(&self, cursor: usize) -> Option<&str> {
-        self.get_grapheme(cursor - 1)
+        cursor.checked_sub(1).and_then(|i| self.get_grapheme(i))
     }
 
+    /// The grapheme at position `cursor`, None at the end.
     pub fn grapheme_after(&self, cursor: usize) -> Option<&str> {
         self.get_grapheme(cursor)
     }
@@ -271,6 +361,32 @@ impl Buffer {
         self.remove_raw(start, end, false);
     }
 
+    /// Inserts `text` at `start` and returns the number of graphemes inserted. Does not register
+    /// as an action in the undo/redo buffer.
+    pub fn insert_str_unrecorded(&mut self, start: usize, text: &str) -> usize {
+        let orig_len = self.num_graphemes();
+        self.insert_raw(start, text);
+        self.num_graphemes() - orig_len
+    }
+
+    /// Replaces the graphemes from `start` to `end` with `text` as one undo step. Unlike
+    /// `remove` it leaves the register alone.
+    pub fn replace(&mut self, start: usize, end: usize, text: &str) {
+        let removed = self.range(start, end).to_owned();
+        self.remove_raw(start, end, false);
+        self.insert_raw(start, text);
+        self.push_action(Action::StartGroup);
+        self.push_action(Action::Remove {
+            start,
+            text: removed,
+        });
+        self.push_action(Action::Insert {
+            start,
+            text: text.to_owned(),
+        });
+        self.push_action(Action::EndGroup);
+    }
+
     /// Returns the number of graphemes removed.
     pub fn remove(&mut self, start: usize, end: usize) -> usize {
         let orig_len = self.num_graphemes();
@@ -314,6 +430,35 @@ impl Buffer {
         inserted
     }
 
+    /// Insert contents of register as whole lines below (`right`) or above the line holding
+    /// `idx`, returning where the first inserted line starts.
+    pub fn insert_register_lines_around_idx(
+        &mut self,
+        idx: usize,
+        count: usize,
+        right: bool,
+    ) -> Option<usize> {
+        let text = self.register.as_ref()?;
+        let lines = vec![text.as_str(); count.max(1)].join("\n");
+        let (start, text) = if right {
+            (self.line_end(idx), format!("\n{}", lines))
+        } else {
+            (self.line_start(idx), format!("{}\n", lines))
+        };
+        self.insert_action(Action::Insert { start, text });
+        Some(if right { start + 1 } else { start })
+    }
+
+    /// Marks the register as holding whole lines (from `yy` or `dd` in vi mode) so a paste puts
+    /// it on lines of its own instead of inline. Any later yank or delete clears it.
+    pub fn set_register_linewise(&mut self, linewise: bool) {
+        self.register_linewise = linewise;
+    }
+
+    pub fn is_register_linewise(&self) -> bool {
+        self.register_linewise
+    }
+
     pub fn insert_str(&mut self, start: usize, text: &str) -> usize {
         let orig_len = self.num_graphemes();
         let text = text.to_owned();
@@ -364,6 +509,7 @@ impl Buffer {
         )
     }
 
+    /// The text from grapheme `start` up to, not including, grapheme `end`.
     pub fn range(&self, start: usize, end: usize) -> &str {
         if start == 0 && end >= self.curr_num_graphemes {
             self.range_graphemes_all().slice()
@@ -388,14 +534,14 @@ impl Buffer {
         self.range_graphemes(0, until)
             .slice()
             .lines()
-            .map(|line| line.width())
+            .map(util::str_width)
     }
 
     pub fn line_widths(&self) -> impl Iterator<Item = usize> + '_ {
         self.range_graphemes_all()
             .slice()
             .lines()
-            .map(|line| line.width())
+            .map(util::str_width)
     }
 
     pub fn truncate(&mut self, num: usize) {
@@ -434,6 +580,7 @@ impl Buffer {
     pub fn yank(&mut self, start: usize, end: usize) {
         let slice = self.range_graphemes(start, end).collect::<String>();
         self.register = Some(slice);
+        self.register_linewise = false;
     }
 
     fn string_to_grapheme_indices(str: &str) -> Vec<usize> {
@@ -474,6 +621,7 @@ impl Buffer {
                 self.data.clear();
                 if save_action {
                     self.register = Some(str.to_owned());
+                    self.register_linewise = false;
                     self.push_action(Action::Remove { start, text: str });
                     logged_action = true;
                 }
@@ -491,6 +639,7 @@ impl Buffer {
                     if save_action {
                         let str = drain.collect::<String>();
                         self.register = Some(str.to_owned());
+                        self.register_linewise = false;
                         self.push_action(Action::Remove { start, text: str });
                         logged_action = true;
                     }
@@ -839,6 +988,20 @@ mod tests {
         assert_eq!(Buffer::from(orig + append), buf0);
     }
 
+    #[test]
+    fn test_line_start_end() {
+        let buf = Buffer::from("ab\ncd\n\nef".to_owned());
+        assert_eq!(buf.line_start(0), 0);
+        assert_eq!(buf.line_end(0), 2);
+        assert_eq!(buf.line_start(2), 0);
+        assert_eq!(buf.line_start(4), 3);
+        assert_eq!(buf.line_end(4), 5);
+        assert_eq!(buf.line_start(6), 6);
+        assert_eq!(buf.line_end(6), 6);
+        assert_eq!(buf.line_start(9), 7);
+        assert_eq!(buf.line_end(7), 9);
+    }
+
     #[test]
     fn test_first() {
         let s = "\u{938}\u{94d}\u{924}\u{947} hello string";
@@ -925,4 +1088,77 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_replace() {
+        let mut buf = Buffer::from("abcd");
+        buf.yank(0, 1);
+        buf.replace(1, 3, "xyz");
+        assert_eq!(buf.as_str(), "axyzd");
+        buf.undo();
+        assert_eq!(buf.as_str(), "abcd");
+        buf.redo();
+        assert_eq!(buf.as_str(), "axyzd");
+        // the register still holds the yank
+        buf.insert_register_around_idx(5, 1, false);
+        assert_eq!(buf.as_str(), "axyzda");
+    }
+
+    #[test]
+    fn test_in_undo_group() {
+        let mut buf = Buffer::new();
+        assert!(!buf.in_undo_group());
+        buf.start_undo_group();
+        buf.start_undo_group();
+        assert!(buf.in_undo_group());
+        buf.end_undo_group();
+        assert!(buf.in_undo_group());
+        buf.end_undo_group();
+        assert!(!buf.in_undo_group());
+        buf.start_undo_group();
+        buf.insert_str(0, "a");
+        buf.end_undo_group();
+        buf.undo();
+        assert!(!buf.in_undo_group());
+        buf.redo();
+        assert!(!buf.in_undo_group());
+        buf.start_undo_group();
+        buf.clear_actions();
+        assert!(!buf.in_undo_group());
+    }
+
+    #[test]
+    fn test_as_str() {
+        let mut buf = Buffer::from("let x = \u{928}\u{94d};");
+        assert_eq!(buf.as_str(), "let x = \u{928}\u{94d};");
+        buf.insert_str(0, "  ");
+        assert_eq!(buf.as_str(), buf.to_string());
+        let s: &str = buf.as_ref();
+        assert_eq!(s.find('x'), Some(6));
+        assert_eq!(buf.range(0, 7), &s[..7]);
+    }
+
+    #[test]
+    fn test_grapheme_char_conversions() {
+        // "e" + combining acute, a family emoji joined by ZWJs, then "x".
+        let buf = Buffer::from("e\u{301}👨\u{200d}👩\u{200d}👧x");
+        assert_eq!(3, buf.num_graphemes());
+        assert_eq!(8, buf.num_chars());
+        assert_eq!(
+            vec![0, 2, 7, 8],
+            (0..=3).map(|g| buf.grapheme_to_char(g)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0, 0, 1, 1, 1, 1, 1, 2, 3, 3],
+            (0..=9).map(|c| buf.char_to_grapheme(c)).collect::<Vec<_>>()
+        );
+        assert_eq!(Some("x"), buf.grapheme_before(3));
+        assert_eq!(Some("e\u{301}"), buf.grapheme_after(0));
+        assert_eq!(None, buf.grapheme_before(0));
+        assert_eq!(None, buf.grapheme_after(3));
+
+        let empty = Buffer::new();
+        assert_eq!(0, empty.grapheme_to_char(0));
+        assert_eq!(0, empty.char_to_grapheme(0));
+    }
 }
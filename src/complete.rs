@@ -4,6 +4,13 @@ use std::path::PathBuf;
 pub trait Completer {
     fn completions(&mut self, start: &str) -> Vec<String>;
     fn on_event(&mut self, _event: Event) {}
+
+    /// False if the last `completions` were provisional, for example the first results of a
+    /// slow lookup with more to come. The listed completions are then not cycled through, the
+    /// next completion asks for them again. True by default.
+    fn is_complete(&self) -> bool {
+        true
+    }
 }
 
 /// Completer with no completions
@@ -1,12 +1,94 @@
-use std::io;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time;
 
+use sl_console::event::{Key, KeyCode, KeyMod, MouseButton, MouseEvent};
 use sl_console::*;
 
 use super::*;
+use crate::cursor::ViMoveMode;
+use crate::keymap::literal_text;
+use crate::terminal::{
+    ALTERNATE_SCREEN_OFF, ALTERNATE_SCREEN_ON, BRACKETED_PASTE_OFF, BRACKETED_PASTE_ON,
+    MOUSE_REPORTING_OFF,
+};
 
 pub type ColorClosure = Box<dyn FnMut(&str) -> String>;
 
+/// What a [CursorColorClosure] is told about the text it colors besides the text itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ColorInfo {
+    /// Byte offset of the cursor in the text, None if the cursor is on another line.
+    pub cursor: Option<usize>,
+}
+
+/// Colors the line like a [ColorClosure] but also gets where the cursor is, to highlight the
+/// word under it for example. It is called with each line of the buffer (the part before an
+/// autosuggestion for the last one) and returns the line with color codes added, see
+/// [Context::set_cursor_color_closure].
+pub type CursorColorClosure = Box<dyn FnMut(&str, ColorInfo) -> String>;
+
+/// The outcome of [Context::read_line_result].
+#[derive(Debug)]
+pub enum ReadResult {
+    /// The line that was entered, with the newline removed.
+    Line(String),
+    /// Ctrl-d on an empty line.
+    Eof,
+    /// Ctrl-c.
+    Interrupted,
+    /// The read was cancelled through the token set with [Context::set_cancel_token].
+    Cancelled,
+    /// Any other error reading from or writing to the terminal.
+    Io(io::Error),
+}
+
+impl From<io::Result<String>> for ReadResult {
+    fn from(res: io::Result<String>) -> Self {
+        match res {
+            Ok(line) => ReadResult::Line(line),
+            Err(err) if Cancelled::is_cancelled(&err) => ReadResult::Cancelled,
+            Err(err) => match err.kind() {
+                io::ErrorKind::UnexpectedEof => ReadResult::Eof,
+                io::ErrorKind::Interrupted => ReadResult::Interrupted,
+                _ => ReadResult::Io(err),
+            },
+        }
+    }
+}
+
+/// The error inside the `io::Error` returned when a read is cancelled through the token set with
+/// [Context::set_cancel_token].
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Cancelled {
+    /// True if `err` is a cancelled read.
+    pub fn is_cancelled(err: &io::Error) -> bool {
+        matches!(err.get_ref(), Some(inner) if inner.is::<Cancelled>())
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "read cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// Gets every key read before the keymap does. Returning true means the key was handled and
+/// the keymap never sees it.
+pub type KeyInterceptor = Box<dyn for<'a> FnMut(Key, &mut Editor<'a>) -> io::Result<bool>>;
+
+/// Called with the editor each time a line starts being read, see [Context::set_on_read_start].
+pub type ReadStartCallback = Box<dyn for<'a> FnMut(&mut Editor<'a>)>;
+
 /// Primary interface to readline-like functionality
 pub struct Context {
     pub history: History,
@@ -14,6 +96,37 @@ pub struct Context {
     buf: String,
     handler: Box<dyn Completer>,
     keymap: Box<dyn KeyMap>,
+    ctrl_c_clears_line: bool,
+    echo_mode: EchoMode,
+    complete_key: Key,
+    accept_autosuggestion_keys: Vec<Key>,
+    tab_stop: usize,
+    newline_on_accept: bool,
+    tab_inserts_spaces: Option<usize>,
+    completion_max_display: Option<usize>,
+    completion_commit_on_enter: bool,
+    list_on_empty: bool,
+    complete_whole_word: bool,
+    insert_common_prefix: bool,
+    completion_sort: CompletionSort,
+    search_wrap: bool,
+    history_edit_prompt: Option<Prompt>,
+    word_delete_mode: ViMoveMode,
+    long_line_mode: LongLineMode,
+    history_up_mode: HistoryUpMode,
+    cancel_token: Option<Arc<AtomicBool>>,
+    show_autosuggestions: bool,
+    autosuggestion_min_chars: usize,
+    autosuggestion_provider: Option<AutosuggestionProvider>,
+    bell_style: BellStyle,
+    completion_postprocess: Option<CompletionPostprocess>,
+    cursor_color_closure: Option<CursorColorClosure>,
+    no_completion_callback: Option<NoCompletionCallback>,
+    mouse_enabled: bool,
+    use_alternate_screen: bool,
+    bracketed_paste: bool,
+    key_interceptor: Option<KeyInterceptor>,
+    on_read_start: Option<ReadStartCallback>,
 }
 
 impl Default for Context {
@@ -30,9 +143,48 @@ impl Context {
             buf: String::with_capacity(512),
             handler: Box::new(EmptyCompleter::new()),
             keymap: Box::new(keymap::Emacs::new()),
+            ctrl_c_clears_line: false,
+            echo_mode: EchoMode::Normal,
+            complete_key: Key::new(KeyCode::Char('\t')),
+            accept_autosuggestion_keys: vec![
+                Key::new_mod(KeyCode::Char('f'), KeyMod::Ctrl),
+                Key::new(KeyCode::Right),
+            ],
+            tab_stop: 8,
+            newline_on_accept: true,
+            tab_inserts_spaces: None,
+            completion_max_display: None,
+            completion_commit_on_enter: false,
+            list_on_empty: true,
+            complete_whole_word: false,
+            insert_common_prefix: true,
+            completion_sort: CompletionSort::Alphabetical,
+            search_wrap: true,
+            history_edit_prompt: None,
+            word_delete_mode: ViMoveMode::Whitespace,
+            long_line_mode: LongLineMode::Wrap,
+            history_up_mode: HistoryUpMode::PrefixFilter,
+            cancel_token: None,
+            show_autosuggestions: true,
+            autosuggestion_min_chars: 1,
+            autosuggestion_provider: None,
+            bell_style: BellStyle::None,
+            completion_postprocess: None,
+            cursor_color_closure: None,
+            no_completion_callback: None,
+            mouse_enabled: false,
+            use_alternate_screen: false,
+            bracketed_paste: false,
+            key_interceptor: None,
+            on_read_start: None,
         }
     }
 
+    /// Starts a [ContextBuilder] with the same defaults as `Context::new()`.
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+
     pub fn set_completer(&mut self, completer: Box<dyn Completer>) -> &mut Self {
         self.handler = completer;
         self
@@ -43,20 +195,393 @@ impl Context {
         self
     }
 
+    /// If true, ctrl-c clears the line being edited instead of making `read_line()` return an
+    /// `Interrupted` error. An in progress history search or completion is cancelled too.
+    pub fn set_ctrl_c_clears_line(&mut self, clears_line: bool) -> &mut Self {
+        self.ctrl_c_clears_line = clears_line;
+        self
+    }
+
+    /// Sets how the line is shown while it is typed, for example masked for passwords. Lines
+    /// read in the hidden or masked modes should not be pushed to history.
+    pub fn set_echo_mode(&mut self, echo_mode: EchoMode) -> &mut Self {
+        self.echo_mode = echo_mode;
+        self
+    }
+
+    /// Sets whether accepting a line writes a newline, see [Editor::set_newline_on_accept].
+    pub fn set_newline_on_accept(&mut self, newline_on_accept: bool) -> &mut Self {
+        self.newline_on_accept = newline_on_accept;
+        self
+    }
+
+    /// Sets the columns between tab stops used to draw tabs in the buffer, 8 by default.
+    pub fn set_tab_stop(&mut self, tab_stop: usize) -> &mut Self {
+        self.tab_stop = tab_stop;
+        self
+    }
+
+    /// Sets the key that completes, tab by default, see [Editor::set_complete_key]. Ctrl-space
+    /// is `Key::new_mod(KeyCode::Char(' '), KeyMod::Ctrl)`.
+    pub fn set_complete_key(&mut self, key: Key) -> &mut Self {
+        self.complete_key = key;
+        self
+    }
+
+    /// Sets the keys that accept the autosuggestion, ctrl-f and right by default, see
+    /// [Editor::set_accept_autosuggestion_keys].
+    pub fn set_accept_autosuggestion_keys(&mut self, keys: Vec<Key>) -> &mut Self {
+        self.accept_autosuggestion_keys = keys;
+        self
+    }
+
+    /// Lists at most `max` completions at once, see [Editor::set_completion_max_display].
+    pub fn set_completion_max_display(&mut self, max: Option<usize>) -> &mut Self {
+        self.completion_max_display = max;
+        self
+    }
+
+    /// Makes enter commit the highlighted completion instead of only closing the list, see
+    /// [Editor::set_completion_commit_on_enter].
+    pub fn set_completion_commit_on_enter(&mut self, commit: bool) -> &mut Self {
+        self.completion_commit_on_enter = commit;
+        self
+    }
+
+    /// If set, tab inserts this many spaces when the completer has nothing to offer. By default
+    /// tab only completes.
+    pub fn set_tab_inserts_spaces(&mut self, spaces: Option<usize>) -> &mut Self {
+        self.tab_inserts_spaces = spaces;
+        self
+    }
+
+    /// Sets whether tab on an empty word only lists the completions (the default) rather than
+    /// inserting their common prefix.
+    pub fn set_list_on_empty(&mut self, list_on_empty: bool) -> &mut Self {
+        self.list_on_empty = list_on_empty;
+        self
+    }
+
+    /// Sets whether a completion replaces the whole word the cursor is in rather than only the
+    /// part before the cursor (the default).
+    pub fn set_complete_whole_word(&mut self, whole_word: bool) -> &mut Self {
+        self.complete_whole_word = whole_word;
+        self
+    }
+
+    /// Sets whether completing first inserts the prefix the completions share (the default)
+    /// or lists them right away, see [Editor::set_insert_common_prefix].
+    pub fn set_insert_common_prefix(&mut self, insert: bool) -> &mut Self {
+        self.insert_common_prefix = insert;
+        self
+    }
+
+    /// Sets whether completions are sorted alphabetically (the default) or kept in the order
+    /// the completer returned them, see [Editor::set_completion_sort].
+    pub fn set_completion_sort(&mut self, sort: CompletionSort) -> &mut Self {
+        self.completion_sort = sort;
+        self
+    }
+
+    /// Sets whether history search wraps around at the first and last match (the default) or
+    /// stops there with a bell.
+    pub fn set_search_wrap(&mut self, wrap: bool) -> &mut Self {
+        self.search_wrap = wrap;
+        self
+    }
+
+    /// Sets a prompt shown while a history entry is edited, see
+    /// [Editor::set_history_edit_prompt].
+    pub fn set_history_edit_prompt(&mut self, prompt: Option<Prompt>) -> &mut Self {
+        self.history_edit_prompt = prompt;
+        self
+    }
+
+    /// Sets a function run on the candidates of every completer, see
+    /// [Editor::set_completion_postprocess]. It gets the word being completed and the candidates
+    /// after the editor has sorted and deduplicated them, so the order it returns is kept.
+    pub fn set_completion_postprocess(
+        &mut self,
+        postprocess: Option<CompletionPostprocess>,
+    ) -> &mut Self {
+        self.completion_postprocess = postprocess;
+        self
+    }
+
+    /// Colors lines with `closure`, which unlike the [ColorClosure] given to `read_line` is told
+    /// where the cursor is. It is used instead of that one when both are set. Like it, it is
+    /// run when typing pauses, in between the colors of the last run are reused.
+    pub fn set_cursor_color_closure(&mut self, closure: Option<CursorColorClosure>) -> &mut Self {
+        self.cursor_color_closure = closure;
+        self
+    }
+
+    /// Sets a function called with the word being completed when no completion is found, see
+    /// [Editor::set_no_completion_callback].
+    pub fn set_no_completion_callback(
+        &mut self,
+        callback: Option<NoCompletionCallback>,
+    ) -> &mut Self {
+        self.no_completion_callback = callback;
+        self
+    }
+
+    /// Sets whether ctrl-w deletes back to whitespace (the default) or also stops at
+    /// punctuation.
+    pub fn set_word_delete_mode(&mut self, mode: ViMoveMode) -> &mut Self {
+        self.word_delete_mode = mode;
+        self
+    }
+
+    /// Sets whether lines too long for the terminal wrap (the default) or scroll sideways on the
+    /// prompt row.
+    pub fn set_long_line_mode(&mut self, mode: LongLineMode) -> &mut Self {
+        self.long_line_mode = mode;
+        self
+    }
+
+    /// Sets whether up from a non-empty line goes through the history entries starting with it
+    /// (the default) or through all of them, see [Editor::set_history_up_mode].
+    pub fn set_history_up_mode(&mut self, mode: HistoryUpMode) -> &mut Self {
+        self.history_up_mode = mode;
+        self
+    }
+
+    /// Setting `token` to true from another thread makes a pending `read_line()` give up on the
+    /// line and return an `Interrupted` error for which [Cancelled::is_cancelled] is true
+    /// ([ReadResult::Cancelled] from `read_line_result()`). The terminal is left on a fresh line
+    /// with raw mode restored and the token is reset to false.
+    ///
+    /// Input is polled so the token is noticed within 200ms, while a token is set the read loop
+    /// wakes up that often even when no keys are pressed.
+    pub fn set_cancel_token(&mut self, token: Option<Arc<AtomicBool>>) -> &mut Self {
+        self.cancel_token = token;
+        self
+    }
+
+    /// Empties the history, and the history file as well if `clear_file` is true. The next
+    /// line read starts without history search, navigation or autosuggestions from it.
+    ///
+    /// ```no_run
+    /// use sl_liner::{Context, Prompt};
+    ///
+    /// let mut context = Context::new();
+    /// let line = context.read_line(Prompt::from("$ "), None).unwrap();
+    /// context.history.push(line).unwrap();
+    /// // a new session, up arrow finds nothing from the last one
+    /// context.clear_history(false).unwrap();
+    /// let line = context.read_line(Prompt::from("$ "), None).unwrap();
+    /// ```
+    pub fn clear_history(&mut self, clear_file: bool) -> io::Result<()> {
+        if clear_file {
+            self.history.clear_history_and_file()
+        } else {
+            self.history.clear_history();
+            Ok(())
+        }
+    }
+
+    /// Replaces the history with `history`, a prepared one for a test or a new session. The
+    /// history search and autosuggestions of the next line read use only the new history.
+    pub fn set_history(&mut self, history: History) -> &mut Self {
+        self.history = history;
+        self
+    }
+
+    /// Turns history off for a prompt that should leave no trace, or back on. See
+    /// [History::set_enabled].
+    pub fn set_history_enabled(&mut self, enabled: bool) -> &mut Self {
+        self.history.set_enabled(enabled);
+        self
+    }
+
+    /// Sets a function that gets first crack at every key read, for application wide shortcuts
+    /// (F5 to run, a custom ctrl-l) whatever the keymap. It can change the editor through the
+    /// `&mut Editor` it is given. If it returns true the key counts as handled: the keymap does
+    /// not see it and, since the keymap sends them, neither `BeforeKey` nor `AfterKey` events
+    /// are sent for it. If it returns false the key goes on to the keymap as usual, which sends
+    /// `BeforeKey` after the interceptor has run. An error ends `read_line` with that error.
+    /// Text pasted with bracketed paste on does not go through it.
+    pub fn set_key_interceptor(&mut self, interceptor: Option<KeyInterceptor>) -> &mut Self {
+        self.key_interceptor = interceptor;
+        self
+    }
+
+    /// Sets a function called each time `read_line` (or one of its variants) starts on a new
+    /// line, once the editor is set up and the prompt drawn and before the first key is read.
+    /// It runs after the keymap's `init`, so what it changes on the editor stays in place, and
+    /// is the spot to refresh completion data or reset state kept for each line.
+    pub fn set_on_read_start(&mut self, callback: Option<ReadStartCallback>) -> &mut Self {
+        self.on_read_start = callback;
+        self
+    }
+
     pub fn set_editor_rules(&mut self, rules: Box<dyn EditorRules>) -> &mut Self {
         self.rules = rules;
         self
     }
 
+    /// Sets how failed actions are signalled, see [Editor::set_bell_style]. The default is
+    /// `BellStyle::None`.
+    pub fn set_bell_style(&mut self, style: BellStyle) -> &mut Self {
+        self.bell_style = style;
+        self
+    }
+
+    /// Turns autosuggestions from history (or the editor's provider) on or off, they are on by
+    /// default.
+    pub fn set_show_autosuggestions(&mut self, show: bool) -> &mut Self {
+        self.show_autosuggestions = show;
+        self
+    }
+
+    /// Waits for `min_chars` graphemes before suggesting, see
+    /// [Editor::set_autosuggestion_min_chars].
+    pub fn set_autosuggestion_min_chars(&mut self, min_chars: usize) -> &mut Self {
+        self.autosuggestion_min_chars = min_chars;
+        self
+    }
+
+    /// Takes autosuggestions from `provider` instead of history, see
+    /// [Editor::set_autosuggestion_provider].
+    pub fn set_autosuggestion_provider(
+        &mut self,
+        provider: Option<AutosuggestionProvider>,
+    ) -> &mut Self {
+        self.autosuggestion_provider = provider;
+        self
+    }
+
+    /// Turns on mouse support: a left click on the line being edited moves the cursor there.
+    /// The terminal reports mouse events while a line is read, which takes over mouse selection
+    /// in most terminals. Off by default.
+    pub fn set_mouse_enabled(&mut self, enabled: bool) -> &mut Self {
+        self.mouse_enabled = enabled;
+        self
+    }
+
+    /// Reads lines on the terminal's alternate screen, starting at its top left, and switches
+    /// back to the main screen with the cursor where it was once the line is done. This keeps
+    /// a full screen program's display intact while it asks for a line. Off by default.
+    pub fn set_use_alternate_screen(&mut self, use_alternate_screen: bool) -> &mut Self {
+        self.use_alternate_screen = use_alternate_screen;
+        self
+    }
+
+    /// Turns on bracketed paste: the terminal marks pasted text, which is then inserted as it
+    /// is, newlines included, instead of being handled key by key. Pasting a multiline snippet
+    /// does not accept the line at its first newline nor go through the editor rules or auto
+    /// indent, only an enter typed after it does. Off by default.
+    pub fn set_bracketed_paste(&mut self, bracketed_paste: bool) -> &mut Self {
+        self.bracketed_paste = bracketed_paste;
+        self
+    }
+
+    /// The terminal's current columns and rows, 80 by 24 if they cannot be read. It is read
+    /// again on each call, so it follows resizes, for example from a color closure.
+    pub fn terminal_size(&self) -> (u16, u16) {
+        util::terminal_size().unwrap_or((80, 24))
+    }
+
     /// Creates an `Editor` and feeds it keypresses from stdin until the line is entered.
     /// The output is stdout.
     /// The returned line has the newline removed.
     /// Before returning, will revert all changes to the history buffers.
+    ///
+    /// When stdin is not a terminal, for example when a script pipes a file in, there is no
+    /// editing: the next line of input is read and returned as is, without writing the prompt
+    /// or any escape codes and without looking at the history. The end of the input is an
+    /// `UnexpectedEof` error, like ctrl-d on an empty line.
     pub fn read_line(&mut self, prompt: Prompt, f: Option<ColorClosure>) -> io::Result<String> {
         self.edit_line(prompt, f, Buffer::new())
     }
 
+    /// Same as `Context.read_line()` but the line starts out as `init` with the cursor at its
+    /// end, to edit an earlier line again.
+    ///
+    /// ```no_run
+    /// use sl_liner::{Context, Prompt};
+    ///
+    /// fn run(command: &str) -> Result<(), String> {
+    ///     Err(format!("{}: command not found", command))
+    /// }
+    ///
+    /// let mut context = Context::new();
+    /// let mut line = context.read_line(Prompt::from("$ "), None).unwrap();
+    /// while let Err(err) = run(&line) {
+    ///     // let the failed command be fixed instead of typed again
+    ///     eprintln!("{}", err);
+    ///     line = context
+    ///         .read_line_with_init(Prompt::from("$ "), &line, None)
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn read_line_with_init(
+        &mut self,
+        prompt: Prompt,
+        init: &str,
+        f: Option<ColorClosure>,
+    ) -> io::Result<String> {
+        self.edit_line(prompt, f, init)
+    }
+
+    /// Same as `Context.read_line()`, but reports Ctrl-d and Ctrl-c as [ReadResult::Eof] and
+    /// [ReadResult::Interrupted] instead of as error kinds.
+    ///
+    /// ```no_run
+    /// use sl_liner::{Context, Prompt, ReadResult};
+    ///
+    /// let mut context = Context::new();
+    /// loop {
+    ///     match context.read_line_result(Prompt::from("$ "), None) {
+    ///         ReadResult::Line(line) => println!("{}", line),
+    ///         ReadResult::Interrupted | ReadResult::Cancelled => continue,
+    ///         ReadResult::Eof => break,
+    ///         ReadResult::Io(err) => panic!("{}", err),
+    ///     }
+    /// }
+    /// ```
+    pub fn read_line_result(&mut self, prompt: Prompt, f: Option<ColorClosure>) -> ReadResult {
+        self.read_line(prompt, f).into()
+    }
+
+    /// Reads a single keypress from stdin with the terminal in raw mode, without a prompt or any
+    /// line editing. Ctrl-c returns an `Interrupted` error like `read_line()` does.
+    ///
+    /// ```no_run
+    /// use sl_liner::Context;
+    /// use sl_console::event::KeyCode;
+    ///
+    /// let mut context = Context::new();
+    /// print!("continue? [y/n] ");
+    /// let yes = matches!(context.read_key().map(|k| k.code), Ok(KeyCode::Char('y')));
+    /// ```
+    pub fn read_key(&mut self) -> io::Result<Key> {
+        con_init()?;
+        // Raw mode is restored when this is dropped.
+        let _conout = conout().lock().into_raw_mode()?;
+        let mut conin = conin();
+        loop {
+            match conin.get_event() {
+                Some(Ok(sl_console::event::Event::Key(key))) => {
+                    if key.code == KeyCode::Char('c') && key.mods == Some(KeyMod::Ctrl) {
+                        return Err(io::Error::new(io::ErrorKind::Interrupted, "ctrl-c"));
+                    }
+                    return Ok(key);
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Some(Err(err)) => {
+                    return Err(err);
+                }
+                None => {}
+            }
+        }
+    }
+
     /// Same as `Context.read_line()`, but passes the provided initial buffer to the editor.
+    /// When stdin is not a terminal the initial buffer is ignored and the next line of input is
+    /// returned.
     ///
     /// ```no_run
     /// use sl_liner::{Context, Completer, Prompt};
@@ -82,7 +607,50 @@ impl Context {
         f: Option<ColorClosure>,
         buffer: B,
     ) -> io::Result<String> {
+        if !io::stdin().is_terminal() {
+            return read_piped_line(io::stdin().lock());
+        }
         con_init()?;
+        let use_alternate_screen = self.use_alternate_screen;
+        let bracketed_paste = self.bracketed_paste;
+        let mut setup = String::new();
+        if use_alternate_screen {
+            setup.push_str(ALTERNATE_SCREEN_ON);
+        }
+        if bracketed_paste {
+            setup.push_str(BRACKETED_PASTE_ON);
+        }
+        if !setup.is_empty() {
+            let mut conout = conout().lock();
+            conout.write_all(setup.as_bytes())?;
+            conout.flush()?;
+        }
+        let res = self.edit_line_raw(prompt, f, buffer.into());
+        // whether the line was entered or not
+        let mut teardown = String::new();
+        if self.mouse_enabled {
+            teardown.push_str(MOUSE_REPORTING_OFF);
+        }
+        if bracketed_paste {
+            teardown.push_str(BRACKETED_PASTE_OFF);
+        }
+        if use_alternate_screen {
+            teardown.push_str(ALTERNATE_SCREEN_OFF);
+        }
+        if !teardown.is_empty() {
+            let mut conout = conout().lock();
+            conout.write_all(teardown.as_bytes())?;
+            conout.flush()?;
+        }
+        res
+    }
+
+    fn edit_line_raw(
+        &mut self,
+        prompt: Prompt,
+        f: Option<ColorClosure>,
+        buffer: Buffer,
+    ) -> io::Result<String> {
         let mut conout = conout().lock().into_raw_mode()?;
         let mut conin = conin();
         let mut ed = Editor::new_with_init_buffer(
@@ -94,23 +662,108 @@ impl Context {
             buffer,
             &*self.rules,
         )?;
+        ed.set_ctrl_c_clears_line(self.ctrl_c_clears_line);
+        ed.set_echo_mode(self.echo_mode);
+        ed.set_complete_key(self.complete_key);
+        ed.set_accept_autosuggestion_keys(self.accept_autosuggestion_keys.clone());
+        ed.set_tab_stop(self.tab_stop);
+        ed.set_newline_on_accept(self.newline_on_accept);
+        ed.set_tab_inserts_spaces(self.tab_inserts_spaces);
+        ed.set_completion_max_display(self.completion_max_display);
+        ed.set_completion_commit_on_enter(self.completion_commit_on_enter);
+        ed.set_list_on_empty(self.list_on_empty);
+        ed.set_complete_whole_word(self.complete_whole_word);
+        ed.set_insert_common_prefix(self.insert_common_prefix);
+        ed.set_completion_sort(self.completion_sort);
+        ed.set_search_wrap(self.search_wrap);
+        ed.set_history_edit_prompt(self.history_edit_prompt.clone());
+        ed.set_word_delete_mode(self.word_delete_mode);
+        ed.set_long_line_mode(self.long_line_mode);
+        ed.set_history_up_mode(self.history_up_mode);
+        ed.set_show_autosuggestions(self.show_autosuggestions);
+        ed.set_autosuggestion_min_chars(self.autosuggestion_min_chars);
+        ed.set_autosuggestion_provider(self.autosuggestion_provider.as_mut());
+        ed.set_bell_style(self.bell_style);
+        ed.set_completion_postprocess(self.completion_postprocess.as_mut());
+        ed.set_cursor_color_closure(self.cursor_color_closure.as_mut());
+        ed.set_no_completion_callback(self.no_completion_callback.as_mut());
+        ed.set_mouse_reporting(self.mouse_enabled);
         self.keymap.init(&mut ed);
+        if let Some(on_read_start) = &mut self.on_read_start {
+            on_read_start(&mut ed);
+            ed.flush()?;
+        }
         ed.use_closure(false);
         let mut do_color = false;
         let timeout = time::Duration::from_millis(200);
+        // A click waiting for the cursor position, to place it relative to the prompt.
+        let mut pending_click = None;
+        // Text pasted so far, between the terminal's paste start and end markers.
+        let mut paste: Option<String> = None;
         loop {
-            let c = if do_color {
+            if let Some(token) = &self.cancel_token {
+                if token.swap(false, Ordering::SeqCst) {
+                    ed.abandon_line()?;
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, Cancelled));
+                }
+            }
+            let c = if do_color || self.cancel_token.is_some() {
                 conin.get_event_timeout(timeout)
             } else {
                 conin.get_event()
             };
             match c {
+                Some(Ok(sl_console::event::Event::Key(key))) if paste.is_some() => {
+                    if let (Some(paste), Some(text)) = (&mut paste, literal_text(key)) {
+                        paste.push_str(&text);
+                    }
+                }
                 Some(Ok(sl_console::event::Event::Key(key))) => {
                     do_color = true;
-                    if self.keymap.handle_key(key, &mut ed, &mut *self.handler)? {
+                    let intercepted = match &mut self.key_interceptor {
+                        Some(interceptor) => interceptor(key, &mut ed)?,
+                        None => false,
+                    };
+                    if intercepted {
+                        ed.flush()?;
+                    } else if self.keymap.handle_key(key, &mut ed, &mut *self.handler)? {
                         break;
                     }
                 }
+                Some(Ok(sl_console::event::Event::Mouse(MouseEvent::Press(
+                    MouseButton::Left,
+                    col,
+                    row,
+                )))) if self.mouse_enabled => {
+                    pending_click = Some((col, row));
+                    ed.request_cursor_position()?;
+                }
+                Some(Ok(sl_console::event::Event::Unsupported(bytes))) => {
+                    if bytes == PASTE_START {
+                        paste = Some(String::new());
+                    } else if bytes == PASTE_END {
+                        if let Some(text) = paste.take() {
+                            do_color = true;
+                            ed.insert_pasted(&text)?;
+                        }
+                    } else if let (Some((col, row)), Some((cursor_row, _))) =
+                        (pending_click, parse_cursor_report(&bytes))
+                    {
+                        pending_click = None;
+                        // the terminal counts from 1, the editor from 0 and the prompt line
+                        let (_, cursor_offset) = ed.cursor_screen_position()?;
+                        let prompt_row = (cursor_row - 1).checked_sub(cursor_offset);
+                        if let Some(row) =
+                            prompt_row.and_then(|p| row.saturating_sub(1).checked_sub(p))
+                        {
+                            let col = col.saturating_sub(1);
+                            self.handler
+                                .on_event(Event::new(&mut ed, EventKind::MouseClick { col, row }));
+                            ed.move_cursor_to_screen_position(col, row)?;
+                            ed.flush()?;
+                        }
+                    }
+                }
                 Some(Ok(_)) => {}
                 Some(Err(err)) if err.kind() == io::ErrorKind::WouldBlock => {
                     if do_color {
@@ -129,3 +782,285 @@ impl Context {
         Ok(ed.into())
     }
 }
+
+/// Reads the next line of input that is not a terminal and strips its line ending. The end of
+/// the input is an `UnexpectedEof` error.
+pub(crate) fn read_piped_line<R: BufRead>(mut input: R) -> io::Result<String> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of input"));
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(line)
+}
+
+/// What a terminal with bracketed paste on sends before and after pasted text.
+const PASTE_START: &[u8] = b"\x1B[200~";
+const PASTE_END: &[u8] = b"\x1B[201~";
+
+/// Parses the terminal's answer to a cursor position request, `ESC [ row ; col R`, into the
+/// (row, col) it gives, both counted from 1.
+fn parse_cursor_report(bytes: &[u8]) -> Option<(u16, u16)> {
+    let report = std::str::from_utf8(bytes).ok()?;
+    let mut parts = report.strip_prefix("\x1B[")?.strip_suffix('R')?.split(';');
+    let row = parts.next()?.parse().ok()?;
+    let col = parts.next()?.parse().ok()?;
+    match (row, col, parts.next()) {
+        (1..=u16::MAX, 1..=u16::MAX, None) => Some((row, col)),
+        _ => None,
+    }
+}
+
+/// Collects [Context] options and applies them in one go, an alternative to calling the setters
+/// on a `Context` one at a time.
+///
+/// ```no_run
+/// use sl_liner::keymap::Vi;
+/// use sl_liner::{Context, FilenameCompleter, Prompt};
+///
+/// let mut vi = Vi::new();
+/// vi.set_normal_prompt_prefix(Some("[N] ".to_string()));
+/// vi.set_insert_prompt_prefix(Some("[I] ".to_string()));
+/// let mut context = Context::builder()
+///     .keymap(Box::new(vi))
+///     .history_file("/tmp/history.txt")
+///     // case insensitive file name completion
+///     .completer(Box::new(FilenameCompleter::with_case_sensitivity(
+///         None::<&str>,
+///         false,
+///     )))
+///     .tab_inserts_spaces(Some(4))
+///     .show_autosuggestions(false)
+///     .build()
+///     .unwrap();
+/// let line = context.read_line(Prompt::from("$ "), None);
+/// ```
+pub struct ContextBuilder {
+    context: Context,
+    history_file: Option<PathBuf>,
+    history_enabled: Option<bool>,
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        ContextBuilder {
+            context: Context::new(),
+            history_file: None,
+            history_enabled: None,
+        }
+    }
+
+    pub fn keymap(mut self, keymap: Box<dyn KeyMap>) -> Self {
+        self.context.set_keymap(keymap);
+        self
+    }
+
+    pub fn completer(mut self, completer: Box<dyn Completer>) -> Self {
+        self.context.set_completer(completer);
+        self
+    }
+
+    pub fn editor_rules(mut self, rules: Box<dyn EditorRules>) -> Self {
+        self.context.set_editor_rules(rules);
+        self
+    }
+
+    /// Use `history` instead of an empty history. A history file set with
+    /// [ContextBuilder::history_file] is loaded into it on `build()`.
+    pub fn history(mut self, history: History) -> Self {
+        self.context.history = history;
+        self
+    }
+
+    /// Turns history off or on, see [Context::set_history_enabled]. Applied on `build()`, so
+    /// it also covers a history given with [ContextBuilder::history].
+    pub fn history_enabled(mut self, enabled: bool) -> Self {
+        self.history_enabled = Some(enabled);
+        self
+    }
+
+    /// Load history from `path` and append new entries to it, see
+    /// [History::set_file_name_and_load_history].
+    pub fn history_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.history_file = Some(path.into());
+        self
+    }
+
+    pub fn newline_on_accept(mut self, newline_on_accept: bool) -> Self {
+        self.context.set_newline_on_accept(newline_on_accept);
+        self
+    }
+
+    pub fn tab_stop(mut self, tab_stop: usize) -> Self {
+        self.context.set_tab_stop(tab_stop);
+        self
+    }
+
+    pub fn complete_key(mut self, key: Key) -> Self {
+        self.context.set_complete_key(key);
+        self
+    }
+
+    pub fn accept_autosuggestion_keys(mut self, keys: Vec<Key>) -> Self {
+        self.context.set_accept_autosuggestion_keys(keys);
+        self
+    }
+
+    pub fn completion_max_display(mut self, max: Option<usize>) -> Self {
+        self.context.set_completion_max_display(max);
+        self
+    }
+
+    pub fn completion_commit_on_enter(mut self, commit: bool) -> Self {
+        self.context.set_completion_commit_on_enter(commit);
+        self
+    }
+
+    pub fn tab_inserts_spaces(mut self, spaces: Option<usize>) -> Self {
+        self.context.set_tab_inserts_spaces(spaces);
+        self
+    }
+
+    pub fn list_on_empty(mut self, list_on_empty: bool) -> Self {
+        self.context.set_list_on_empty(list_on_empty);
+        self
+    }
+
+    pub fn complete_whole_word(mut self, whole_word: bool) -> Self {
+        self.context.set_complete_whole_word(whole_word);
+        self
+    }
+
+    pub fn insert_common_prefix(mut self, insert: bool) -> Self {
+        self.context.set_insert_common_prefix(insert);
+        self
+    }
+
+    pub fn completion_sort(mut self, sort: CompletionSort) -> Self {
+        self.context.set_completion_sort(sort);
+        self
+    }
+
+    pub fn search_wrap(mut self, wrap: bool) -> Self {
+        self.context.set_search_wrap(wrap);
+        self
+    }
+
+    pub fn history_edit_prompt(mut self, prompt: Option<Prompt>) -> Self {
+        self.context.set_history_edit_prompt(prompt);
+        self
+    }
+
+    pub fn completion_postprocess(mut self, postprocess: CompletionPostprocess) -> Self {
+        self.context.set_completion_postprocess(Some(postprocess));
+        self
+    }
+
+    pub fn cursor_color_closure(mut self, closure: CursorColorClosure) -> Self {
+        self.context.set_cursor_color_closure(Some(closure));
+        self
+    }
+
+    pub fn no_completion_callback(mut self, callback: NoCompletionCallback) -> Self {
+        self.context.set_no_completion_callback(Some(callback));
+        self
+    }
+
+    pub fn mouse_enabled(mut self, enabled: bool) -> Self {
+        self.context.set_mouse_enabled(enabled);
+        self
+    }
+
+    pub fn use_alternate_screen(mut self, use_alternate_screen: bool) -> Self {
+        self.context.set_use_alternate_screen(use_alternate_screen);
+        self
+    }
+
+    pub fn bracketed_paste(mut self, bracketed_paste: bool) -> Self {
+        self.context.set_bracketed_paste(bracketed_paste);
+        self
+    }
+
+    pub fn key_interceptor(mut self, interceptor: KeyInterceptor) -> Self {
+        self.context.set_key_interceptor(Some(interceptor));
+        self
+    }
+
+    pub fn on_read_start(mut self, callback: ReadStartCallback) -> Self {
+        self.context.set_on_read_start(Some(callback));
+        self
+    }
+
+    pub fn show_autosuggestions(mut self, show: bool) -> Self {
+        self.context.set_show_autosuggestions(show);
+        self
+    }
+
+    pub fn autosuggestion_min_chars(mut self, min_chars: usize) -> Self {
+        self.context.set_autosuggestion_min_chars(min_chars);
+        self
+    }
+
+    pub fn autosuggestion_provider(mut self, provider: AutosuggestionProvider) -> Self {
+        self.context.set_autosuggestion_provider(Some(provider));
+        self
+    }
+
+    pub fn ctrl_c_clears_line(mut self, clears_line: bool) -> Self {
+        self.context.set_ctrl_c_clears_line(clears_line);
+        self
+    }
+
+    pub fn echo_mode(mut self, echo_mode: EchoMode) -> Self {
+        self.context.set_echo_mode(echo_mode);
+        self
+    }
+
+    pub fn word_delete_mode(mut self, mode: ViMoveMode) -> Self {
+        self.context.set_word_delete_mode(mode);
+        self
+    }
+
+    pub fn long_line_mode(mut self, mode: LongLineMode) -> Self {
+        self.context.set_long_line_mode(mode);
+        self
+    }
+
+    pub fn history_up_mode(mut self, mode: HistoryUpMode) -> Self {
+        self.context.set_history_up_mode(mode);
+        self
+    }
+
+    pub fn bell_style(mut self, style: BellStyle) -> Self {
+        self.context.set_bell_style(style);
+        self
+    }
+
+    pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> Self {
+        self.context.set_cancel_token(Some(token));
+        self
+    }
+
+    /// Returns the configured `Context`, failing only if the history file can not be loaded.
+    pub fn build(self) -> io::Result<Context> {
+        let mut context = self.context;
+        if let Some(path) = self.history_file {
+            context.history.set_file_name_and_load_history(path)?;
+        }
+        if let Some(enabled) = self.history_enabled {
+            context.set_history_enabled(enabled);
+        }
+        Ok(context)
+    }
+}
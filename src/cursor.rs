@@ -3,6 +3,86 @@ use std::cmp;
 
 use crate::{Buffer, EditorRules};
 
+pub trait ViKeywordRule {
+    /// All alphanumeric characters and _ are considered valid for keywords in vi by default.
+    fn is_vi_keyword(&self, str: &str) -> bool {
+        let mut ret = false;
+        if str == "_" {
+            ret = true
+        } else if !str.trim().is_empty() {
+            for c in str.chars() {
+                if c.is_alphanumeric() {
+                    ret = true;
+                } else {
+                    ret = false;
+                    break;
+                }
+            }
+        }
+        ret
+    }
+}
+
+pub struct DefaultViKeywordRule;
+
+impl ViKeywordRule for DefaultViKeywordRule {}
+
+impl Default for DefaultViKeywordRule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DefaultViKeywordRule {
+    pub fn new() -> Self {
+        DefaultViKeywordRule {}
+    }
+}
+
+/// Where word motions stop. `Keyword` stops wherever keyword characters meet other
+/// non-whitespace characters as well as at whitespace (vi's `w`), `Whitespace` only stops at
+/// whitespace (vi's `W`).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ViMoveMode {
+    Keyword,
+    Whitespace,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum ViMoveDir {
+    Left,
+    Right,
+}
+
+impl ViMoveDir {
+    pub fn advance(self, cursor: &mut usize, max: usize) -> bool {
+        self.move_cursor(cursor, max, self)
+    }
+
+    pub fn go_back(self, cursor: &mut usize, max: usize) -> bool {
+        match self {
+            ViMoveDir::Right => self.move_cursor(cursor, max, ViMoveDir::Left),
+            ViMoveDir::Left => self.move_cursor(cursor, max, ViMoveDir::Right),
+        }
+    }
+
+    fn move_cursor(self, cursor: &mut usize, max: usize, dir: ViMoveDir) -> bool {
+        if dir == ViMoveDir::Right && *cursor == max {
+            return false;
+        }
+
+        if dir == ViMoveDir::Left && *cursor == 0 {
+            return false;
+        }
+
+        match dir {
+            ViMoveDir::Right => *cursor += 1,
+            ViMoveDir::Left => *cursor -= 1,
+        };
+        true
+    }
+}
+
 /// Represents the position of the cursor relative to words in the buffer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CursorPosition {
@@ -131,6 +211,11 @@ impl<'a> Cursor<'a> {
         self.curr_grapheme += len;
     }
 
+    pub fn insert_str_after_cursor_silent(&mut self, buf: &mut Buffer, s: &str) {
+        let len = buf.insert_str_unrecorded(self.curr_grapheme, s);
+        self.curr_grapheme += len;
+    }
+
     pub fn insert_chars_after_cursor(&mut self, buf: &mut Buffer, cs: &[char]) {
         let len = buf.insert(self.curr_grapheme, cs.iter());
         self.curr_grapheme += len;
@@ -222,9 +307,9 @@ impl<'a> Cursor<'a> {
     }
 
     pub fn is_at_beginning_of_word_or_line(&self, buf: &Buffer) -> bool {
-        let num_chars = buf.num_graphemes();
+        let num_graphemes = buf.num_graphemes();
         let cursor_pos = self.curr_grapheme;
-        if num_chars > 0 && cursor_pos != 0 {
+        if num_graphemes > 0 && cursor_pos != 0 {
             let str = buf.grapheme_before(cursor_pos);
             if let Some(str) = str {
                 return str.trim().is_empty();
@@ -238,26 +323,219 @@ impl<'a> Cursor<'a> {
     }
 
     pub fn is_at_end_of_line(&self, buf: &Buffer) -> bool {
-        let num_chars = buf.num_graphemes();
+        let num_graphemes = buf.num_graphemes();
         if self.no_eol {
-            self.curr_grapheme == num_chars - 1
+            self.curr_grapheme + 1 >= num_graphemes
         } else {
-            self.curr_grapheme == num_chars
+            self.curr_grapheme == num_graphemes
         }
     }
 
     pub fn pre_display_adjustment(&mut self, buf: &Buffer) {
-        let buf_num_chars = buf.num_graphemes();
+        let buf_num_graphemes = buf.num_graphemes();
         // Don't let the cursor go over the end!
-        if buf_num_chars < self.curr_grapheme {
-            self.curr_grapheme = buf_num_chars;
+        if buf_num_graphemes < self.curr_grapheme {
+            self.curr_grapheme = buf_num_graphemes;
         }
 
         // Can't move past the last character in vi normal mode
-        if self.no_eol && self.curr_grapheme != 0 && self.curr_grapheme == buf_num_chars {
+        if self.no_eol && self.curr_grapheme != 0 && self.curr_grapheme == buf_num_graphemes {
             self.curr_grapheme -= 1;
         }
     }
+
+    /// The position `count` words after `pos` in `buf`, at the start of a word like vi's `w`
+    /// (`W` with `ViMoveMode::Whitespace`). `rule` decides which graphemes make up keywords, see
+    /// `DefaultViKeywordRule`. Stops at the end of the buffer.
+    pub fn next_word_start(
+        buf: &Buffer,
+        pos: usize,
+        count: usize,
+        mode: ViMoveMode,
+        rule: &dyn ViKeywordRule,
+    ) -> usize {
+        skip_words(buf, pos, count, mode, rule, ViMoveDir::Right, false)
+    }
+
+    /// The position `count` words before `pos`, at the start of a word like vi's `b`.
+    pub fn prev_word_start(
+        buf: &Buffer,
+        pos: usize,
+        count: usize,
+        mode: ViMoveMode,
+        rule: &dyn ViKeywordRule,
+    ) -> usize {
+        to_word_edge(buf, pos, count, mode, rule, ViMoveDir::Left)
+    }
+
+    /// The position `count` words after `pos`, on the last grapheme of a word like vi's `e`.
+    pub fn next_word_end(
+        buf: &Buffer,
+        pos: usize,
+        count: usize,
+        mode: ViMoveMode,
+        rule: &dyn ViKeywordRule,
+    ) -> usize {
+        to_word_edge(buf, pos, count, mode, rule, ViMoveDir::Right)
+    }
+
+    /// The position `count` words before `pos`, on the last grapheme of a word like vi's `ge`.
+    pub fn prev_word_end(
+        buf: &Buffer,
+        pos: usize,
+        count: usize,
+        mode: ViMoveMode,
+        rule: &dyn ViKeywordRule,
+    ) -> usize {
+        skip_words(buf, pos, count, mode, rule, ViMoveDir::Left, false)
+    }
+}
+
+// Moves `count` words from `pos` to where a word starts, or ends when going left. With
+// `ws_included_in_count` runs of whitespace count as words and the result is one before the
+// boundary, as the word text objects want.
+pub(crate) fn skip_words(
+    buf: &Buffer,
+    pos: usize,
+    count: usize,
+    move_mode: ViMoveMode,
+    rule: &dyn ViKeywordRule,
+    direction: ViMoveDir,
+    ws_included_in_count: bool,
+) -> usize {
+    #[derive(Clone, Copy)]
+    enum State {
+        Whitespace,
+        Keyword,
+        NonKeyword,
+    }
+
+    let mut cursor = pos;
+    'repeat: for _ in 0..count {
+        let mut state = match buf.grapheme_after(cursor) {
+            None => break,
+            Some(str) => match str {
+                str if str.trim().is_empty() => State::Whitespace,
+                str if rule.is_vi_keyword(str) => State::Keyword,
+                _ => State::NonKeyword,
+            },
+        };
+
+        while direction.advance(&mut cursor, buf.num_graphemes()) {
+            let str = match buf.grapheme_after(cursor) {
+                Some(str) => str,
+                _ => break 'repeat,
+            };
+
+            // if ws_included_in_count is true we want to make sure we treat
+            // any contiguous string of whitespace appropriately towards
+            // the overall count, this means that at (NonKeyWord and Keyword)
+            // to Whitespace boundaries we need to break so the count loop
+            // increments one more time. The default behavior just cycles
+            // through Whitespace.
+            match state {
+                State::Whitespace => match str {
+                    str if str.trim().is_empty() => {}
+                    _ => {
+                        break;
+                    }
+                },
+                State::Keyword => match str {
+                    str if str.trim().is_empty() => {
+                        if ws_included_in_count {
+                            break;
+                        } else {
+                            state = State::Whitespace
+                        }
+                    }
+                    str if move_mode == ViMoveMode::Keyword && !rule.is_vi_keyword(str) => break,
+                    _ => {}
+                },
+                State::NonKeyword => match str {
+                    str if str.trim().is_empty() => {
+                        if ws_included_in_count {
+                            break;
+                        } else {
+                            state = State::Whitespace
+                        }
+                    }
+                    str if move_mode == ViMoveMode::Keyword && rule.is_vi_keyword(str) => break,
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    // default positioning of cursor when moving in this manner ends one
+    // position to the left of the desired positioning in vi when moving
+    // and treating whitespace as words for the purposed of text objects.
+    if ws_included_in_count && count > 0 {
+        cursor -= 1;
+    }
+    cursor
+}
+
+// Moves `count` words from `pos` to where a word ends, or starts when going left.
+pub(crate) fn to_word_edge(
+    buf: &Buffer,
+    pos: usize,
+    count: usize,
+    move_mode: ViMoveMode,
+    rule: &dyn ViKeywordRule,
+    direction: ViMoveDir,
+) -> usize {
+    enum State {
+        Whitespace,
+        EndOnWord,
+        EndOnOther,
+        EndOnWhitespace,
+    }
+
+    let mut cursor = pos;
+    'repeat: for _ in 0..count {
+        let mut state = State::Whitespace;
+
+        while direction.advance(&mut cursor, buf.num_graphemes()) {
+            let str = match buf.grapheme_after(cursor) {
+                Some(c) => c,
+                _ => break 'repeat,
+            };
+
+            match state {
+                State::Whitespace => match str {
+                    // skip initial whitespace
+                    str if str.trim().is_empty() => {}
+                    // if we are in keyword mode and found a keyword, stop on word
+                    str if move_mode == ViMoveMode::Keyword && rule.is_vi_keyword(str) => {
+                        state = State::EndOnWord;
+                    }
+                    // not in keyword mode, stop on whitespace
+                    _ if move_mode == ViMoveMode::Whitespace => {
+                        state = State::EndOnWhitespace;
+                    }
+                    // in keyword mode, found non-whitespace non-keyword, stop on anything
+                    _ => {
+                        state = State::EndOnOther;
+                    }
+                },
+                State::EndOnWord if !rule.is_vi_keyword(str) => {
+                    direction.go_back(&mut cursor, buf.num_graphemes());
+                    break;
+                }
+                State::EndOnWhitespace if str.trim().is_empty() => {
+                    direction.go_back(&mut cursor, buf.num_graphemes());
+                    break;
+                }
+                State::EndOnOther if str.trim().is_empty() || rule.is_vi_keyword(str) => {
+                    direction.go_back(&mut cursor, buf.num_graphemes());
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    cursor
 }
 
 #[cfg(test)]
@@ -266,6 +544,36 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn word_motions() {
+        let rule = DefaultViKeywordRule::new();
+        let buf = Buffer::from("let x.y = \u{e9}t\u{e9};");
+        let next_start = |pos, mode| Cursor::next_word_start(&buf, pos, 1, mode, &rule);
+        assert_eq!(next_start(0, ViMoveMode::Keyword), 4);
+        assert_eq!(next_start(4, ViMoveMode::Keyword), 5);
+        assert_eq!(next_start(4, ViMoveMode::Whitespace), 8);
+        assert_eq!(
+            Cursor::next_word_start(&buf, 0, 3, ViMoveMode::Keyword, &rule),
+            6
+        );
+
+        let prev_start = |pos, mode| Cursor::prev_word_start(&buf, pos, 1, mode, &rule);
+        assert_eq!(prev_start(10, ViMoveMode::Keyword), 8);
+        assert_eq!(prev_start(8, ViMoveMode::Keyword), 6);
+        assert_eq!(prev_start(8, ViMoveMode::Whitespace), 4);
+
+        let next_end = |pos, mode| Cursor::next_word_end(&buf, pos, 1, mode, &rule);
+        assert_eq!(next_end(0, ViMoveMode::Keyword), 2);
+        assert_eq!(next_end(2, ViMoveMode::Keyword), 4);
+        assert_eq!(next_end(2, ViMoveMode::Whitespace), 6);
+        assert_eq!(next_end(8, ViMoveMode::Keyword), 12);
+
+        let prev_end = |pos, mode| Cursor::prev_word_end(&buf, pos, 1, mode, &rule);
+        assert_eq!(prev_end(10, ViMoveMode::Keyword), 8);
+        assert_eq!(prev_end(6, ViMoveMode::Keyword), 5);
+        assert_eq!(prev_end(6, ViMoveMode::Whitespace), 2);
+    }
+
     #[test]
     fn test_clamp_if_pos_is_past_move() {
         let rules = DefaultEditorRules::default();
@@ -291,6 +599,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_line_widths_zwj_and_combining() {
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        let family_buf = Buffer::from(family.to_owned());
+        assert_eq!(family_buf.line_widths().collect::<Vec<usize>>(), vec![2]);
+        let e_acute = "e\u{301}";
+        let e_acute_buf = Buffer::from(format!("{}{}\n{}", e_acute, family, e_acute));
+        assert_eq!(
+            e_acute_buf.line_widths().collect::<Vec<usize>>(),
+            vec![3, 1]
+        );
+        assert_eq!(
+            e_acute_buf.line_width_until(1).collect::<Vec<usize>>(),
+            vec![1]
+        );
+    }
+
     #[test]
     fn test_clear_exit_for_female_scientist() {
         let rules = DefaultEditorRules::default();
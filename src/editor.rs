@@ -1,14 +1,112 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::io;
 
+use sl_console::event::{Key, KeyCode, KeyMod};
 use sl_console::{self, color};
 
-use crate::context::ColorClosure;
+use crate::context::{ColorClosure, CursorColorClosure};
 use crate::cursor::CursorPosition;
+use crate::cursor::{DefaultViKeywordRule, ViKeywordRule, ViMoveMode};
 use crate::event::*;
 use crate::prompt::Prompt;
-use crate::{util, Completer, EditorRules, Terminal};
+use crate::{terminal, util, Completer, EditorRules, Terminal};
 use crate::{Buffer, Cursor};
 use crate::{History, Metrics};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Supplies autosuggestions from a custom source. Receives the current buffer and returns the
+/// suffix to display after it, or None for no suggestion.
+pub type AutosuggestionProvider = Box<dyn FnMut(&str) -> Option<String>>;
+
+/// Post-processes the candidates of every completer. Receives the word being completed and the
+/// candidates, already sorted (see [CompletionSort]) and deduplicated, and returns the candidates to use in that order.
+pub type CompletionPostprocess = Box<dyn FnMut(&str, Vec<String>) -> Vec<String>>;
+
+/// Called with the word being completed when no completer has anything for it.
+pub type NoCompletionCallback = Box<dyn FnMut(&str)>;
+
+/// How the buffer is shown while it is edited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EchoMode {
+    /// Show the buffer as typed.
+    Normal,
+    /// Show only the prompt.
+    Hidden,
+    /// Show this character in place of each grapheme, for passwords.
+    Masked(char),
+}
+
+/// How the editor signals that a key could not do anything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BellStyle {
+    /// Ring the terminal bell.
+    Audible,
+    /// Briefly flash the screen.
+    Visual,
+    /// Stay quiet.
+    None,
+}
+
+/// How a buffer line wider than the terminal is shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LongLineMode {
+    /// Continue the line on the next terminal row.
+    Wrap,
+    /// Keep the line on the prompt row and scroll it sideways to follow the cursor, marking
+    /// hidden text with `<` and `>`. Buffers with more than one line are always wrapped.
+    Scroll,
+}
+
+/// Which history entries up and down go through when the line is not empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryUpMode {
+    /// Every entry, in order, like bash.
+    Chronological,
+    /// Only the entries starting with the line typed so far, like fish.
+    PrefixFilter,
+}
+
+/// The order completion candidates are inserted and listed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionSort {
+    /// Sorted alphabetically.
+    Alphabetical,
+    /// The order the completer returned them in, for completers that rank by frequency or
+    /// recency. Only the later copies of a duplicate are dropped.
+    AsReturned,
+}
+
+// The last history search, kept after it ends so it can be repeated.
+struct HistorySearch {
+    pattern: String,
+    forward: bool,
+    // History index of the entry the search or its last repeat ended on.
+    loc: Option<usize>,
+}
+
+// The line as it was before a history search or a completion list started, put back by
+// Editor::cancel. The buffers keep their undo actions.
+struct CancelPoint {
+    history_loc: Option<usize>,
+    hist_buf_valid: bool,
+    new_buf: Buffer,
+    hist_buf: Buffer,
+    cursor: usize,
+}
+
+/// A snapshot of what is being edited, see [Editor::snapshot]. It has no terminal state, only
+/// what is needed to pick up the edit again, for example after a restart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EditorState {
+    /// The text of the line being edited.
+    pub buffer: String,
+    /// The cursor as a grapheme index into `buffer`.
+    pub cursor: usize,
+    /// The index of the history entry being edited, None for a new line.
+    pub history_location: Option<usize>,
+}
 
 /// The core line editor. Displays and provides editing for history and the new buffer.
 pub struct Editor<'a> {
@@ -35,20 +133,86 @@ pub struct Editor<'a> {
 
     // The next completion to suggest, or none
     show_completions_hint: Option<(Vec<String>, Option<usize>)>,
+    // The completer said the listed completions are not all there is yet.
+    completions_provisional: bool,
 
     // Show autosuggestions based on history
     show_autosuggestions: bool,
+    // Graphemes typed before an autosuggestion is looked for.
+    autosuggestion_min_chars: usize,
 
     reverse_search: bool,
     forward_search: bool,
+    // Direction of the last search step, shown in the prompt.
+    search_step_forward: bool,
+    last_history_search: Option<HistorySearch>,
+    cancel_point: Option<CancelPoint>,
+    // Whether the completer was last told a search started, to send it SearchEnded once it ends.
+    search_event_sent: bool,
     buffer_changed: bool,
 
     history_subset_index: Vec<usize>,
     history_subset_loc: Option<usize>,
 
     autosuggestion: Option<Buffer>,
+    // Ghost text set by the host and the line it was set for, dropped once the line changes.
+    inline_hint: Option<(String, String)>,
+
+    // If set, autosuggestions come from here instead of history.
+    autosuggestion_provider: Option<&'a mut AutosuggestionProvider>,
+    // Last buffer given to the provider and its answer, so it is only asked on change.
+    provided_suffix: Option<(String, Option<String>)>,
+
+    // Applied to the candidates of any completer.
+    completion_postprocess: Option<&'a mut CompletionPostprocess>,
+    no_completion_callback: Option<&'a mut NoCompletionCallback>,
+
+    // Ctrl-c clears the line instead of interrupting.
+    ctrl_c_clears_line: bool,
+
+    // Maximum number of graphemes the buffer may hold.
+    max_length: Option<usize>,
+    // Typed characters replace the one under the cursor instead of being inserted.
+    overwrite: bool,
+
+    echo_mode: EchoMode,
+
+    // Word boundaries used by ctrl-w.
+    word_delete_mode: ViMoveMode,
+
+    long_line_mode: LongLineMode,
+
+    history_up_mode: HistoryUpMode,
+
+    bell_style: BellStyle,
+
+    // The key that completes, tab by default.
+    complete_key: Key,
+    // Keys that take the autosuggestion into the line when the cursor is at its end.
+    accept_autosuggestion_keys: Vec<Key>,
+    // Tabs in the buffer are drawn up to the next multiple of this many columns.
+    tab_stop: usize,
+    // Tab inserts this many spaces when there is nothing to complete.
+    tab_inserts_spaces: Option<usize>,
+    list_on_empty: bool,
+    // Most completions listed at once, None for all of them.
+    completion_max_display: Option<usize>,
+    // Enter takes the highlighted completion instead of just closing the list.
+    completion_commit_on_enter: bool,
+    complete_whole_word: bool,
+    // Tab first fills in the prefix all completions share before listing them.
+    insert_common_prefix: bool,
+    completion_sort: CompletionSort,
+    // Searching past the last match goes around to the first one.
+    search_wrap: bool,
+    // Drawn instead of the prompt while a history entry is being edited.
+    history_edit_prompt: Option<Prompt>,
 
     history_fresh: bool,
+    // The last newline went on to a new line instead of finishing the read.
+    newline_continued: bool,
+    // Accepting the line moves the terminal cursor to the next line.
+    newline_on_accept: bool,
 }
 
 macro_rules! cur_buf_mut {
@@ -109,15 +273,51 @@ impl<'a> Editor<'a> {
             cur_history_loc: None,
             history,
             show_completions_hint: None,
+            completions_provisional: false,
             show_autosuggestions: true,
+            autosuggestion_min_chars: 1,
             term,
             reverse_search: false,
             forward_search: false,
+            search_step_forward: false,
+            last_history_search: None,
+            cancel_point: None,
+            search_event_sent: false,
             buffer_changed: false,
             history_subset_index: vec![],
             history_subset_loc: None,
             autosuggestion: None,
+            inline_hint: None,
+            autosuggestion_provider: None,
+            provided_suffix: None,
+            completion_postprocess: None,
+            no_completion_callback: None,
+            ctrl_c_clears_line: false,
+            max_length: None,
+            overwrite: false,
+            echo_mode: EchoMode::Normal,
+            word_delete_mode: ViMoveMode::Whitespace,
+            long_line_mode: LongLineMode::Wrap,
+            history_up_mode: HistoryUpMode::PrefixFilter,
+            bell_style: BellStyle::None,
+            complete_key: Key::new(KeyCode::Char('\t')),
+            accept_autosuggestion_keys: vec![
+                Key::new_mod(KeyCode::Char('f'), KeyMod::Ctrl),
+                Key::new(KeyCode::Right),
+            ],
+            tab_stop: 8,
+            tab_inserts_spaces: None,
+            list_on_empty: true,
+            completion_max_display: None,
+            completion_commit_on_enter: false,
+            complete_whole_word: false,
+            insert_common_prefix: true,
+            completion_sort: CompletionSort::Alphabetical,
+            search_wrap: true,
+            history_edit_prompt: None,
             history_fresh: false,
+            newline_continued: false,
+            newline_on_accept: true,
         };
 
         if !ed.new_buf.is_empty() {
@@ -137,11 +337,345 @@ impl<'a> Editor<'a> {
         self.term.use_closure(use_closure)
     }
 
+    /// Colors lines with `closure`, which is also told where the cursor is, instead of the
+    /// [ColorClosure]. See [Context::set_cursor_color_closure].
+    pub fn set_cursor_color_closure(&mut self, closure: Option<&'a mut CursorColorClosure>) {
+        self.term.set_cursor_closure(closure);
+    }
+
+    /// Sets how failed actions, like moving past the end of the line, completing with nothing to
+    /// complete or typing past the max length, are signalled. The default is `BellStyle::None`.
+    pub fn set_bell_style(&mut self, style: BellStyle) {
+        self.bell_style = style;
+    }
+
+    pub fn bell_style(&self) -> BellStyle {
+        self.bell_style
+    }
+
+    /// Rings the bell in the style set with [Editor::set_bell_style].
+    pub fn ring_bell(&mut self) -> io::Result<()> {
+        let style = self.bell_style;
+        self.term.bell(style)
+    }
+
+    /// Turns autosuggestions on (the default) or off.
+    pub fn set_show_autosuggestions(&mut self, show: bool) {
+        self.show_autosuggestions = show;
+        if !show {
+            self.autosuggestion = None;
+        }
+    }
+
+    /// Only looks for an autosuggestion once the line has at least `min_chars` graphemes, 1 by
+    /// default. Searching history is not affected.
+    pub fn set_autosuggestion_min_chars(&mut self, min_chars: usize) {
+        self.autosuggestion_min_chars = min_chars;
+    }
+
+    pub fn autosuggestion_min_chars(&self) -> usize {
+        self.autosuggestion_min_chars
+    }
+
+    /// Use `provider` instead of history to compute autosuggestions, None goes back to history.
+    /// See [Context::set_autosuggestion_provider].
+    pub fn set_autosuggestion_provider(
+        &mut self,
+        provider: Option<&'a mut AutosuggestionProvider>,
+    ) {
+        self.autosuggestion_provider = provider;
+        self.provided_suffix = None;
+    }
+
+    /// Sets the key that completes, tab by default. When it is another key, tab is handled by
+    /// the keymap like any other key, emacs and vi insert mode insert a literal tab for example.
+    pub fn set_complete_key(&mut self, key: Key) {
+        self.complete_key = key;
+    }
+
+    pub fn complete_key(&self) -> Key {
+        self.complete_key
+    }
+
+    /// Sets the keys that accept the autosuggestion while one is shown and the cursor is at the
+    /// end of the line, ctrl-f and right by default. Add `Key::new(KeyCode::End)` to accept it
+    /// with end as well. Otherwise these keys do what the keymap has them do.
+    pub fn set_accept_autosuggestion_keys(&mut self, keys: Vec<Key>) {
+        self.accept_autosuggestion_keys = keys;
+    }
+
+    pub fn accept_autosuggestion_keys(&self) -> &[Key] {
+        &self.accept_autosuggestion_keys
+    }
+
+    /// If true, ctrl-c clears the line and keeps editing instead of returning an `Interrupted`
+    /// error. Any search or completion in progress is cancelled as well.
+    pub fn set_ctrl_c_clears_line(&mut self, clears_line: bool) {
+        self.ctrl_c_clears_line = clears_line;
+    }
+
+    pub fn ctrl_c_clears_line(&self) -> bool {
+        self.ctrl_c_clears_line
+    }
+
+    /// Sets where [Editor::kill_word_before_cursor] (ctrl-w) stops. The default is
+    /// `ViMoveMode::Whitespace`, `ViMoveMode::Keyword` also stops at punctuation.
+    pub fn set_word_delete_mode(&mut self, mode: ViMoveMode) {
+        self.word_delete_mode = mode;
+    }
+
+    pub fn word_delete_mode(&self) -> ViMoveMode {
+        self.word_delete_mode
+    }
+
+    /// If set, tab inserts this many spaces when the completer has nothing for the word before
+    /// the cursor. By default tab only completes.
+    pub fn set_tab_inserts_spaces(&mut self, spaces: Option<usize>) {
+        self.tab_inserts_spaces = spaces;
+    }
+
+    pub fn tab_inserts_spaces(&self) -> Option<usize> {
+        self.tab_inserts_spaces
+    }
+
+    /// Lists at most `max` completions at once followed by how many more there are, None (the
+    /// default) lists them all. Cycling through the completions still goes through all of
+    /// them, the list shows the ones around the current completion.
+    pub fn set_completion_max_display(&mut self, max: Option<usize>) {
+        self.completion_max_display = max;
+    }
+
+    pub fn completion_max_display(&self) -> Option<usize> {
+        self.completion_max_display
+    }
+
+    /// When true, enter while completions are listed commits the highlighted one, or the first
+    /// one if none is highlighted yet, and closes the list without submitting the line. When
+    /// false (the default) enter only closes the list.
+    pub fn set_completion_commit_on_enter(&mut self, commit: bool) {
+        self.completion_commit_on_enter = commit;
+    }
+
+    pub fn completion_commit_on_enter(&self) -> bool {
+        self.completion_commit_on_enter
+    }
+
+    /// Sets the columns between tab stops, 8 by default. A tab in the buffer is drawn as the
+    /// spaces up to the next tab stop, counting from the start of its line.
+    pub fn set_tab_stop(&mut self, tab_stop: usize) {
+        self.tab_stop = tab_stop.max(1);
+    }
+
+    pub fn tab_stop(&self) -> usize {
+        self.tab_stop
+    }
+
+    /// If set (the default), completing an empty word with several candidates only lists them,
+    /// nothing is inserted even if they share a prefix. Otherwise the common prefix is inserted
+    /// as for any other word.
+    pub fn set_list_on_empty(&mut self, list_on_empty: bool) {
+        self.list_on_empty = list_on_empty;
+    }
+
+    pub fn list_on_empty(&self) -> bool {
+        self.list_on_empty
+    }
+
+    /// If set, a completion replaces the whole word the cursor is in, including the part after
+    /// the cursor. By default only the part before the cursor is replaced.
+    pub fn set_complete_whole_word(&mut self, whole_word: bool) {
+        self.complete_whole_word = whole_word;
+    }
+
+    pub fn complete_whole_word(&self) -> bool {
+        self.complete_whole_word
+    }
+
+    /// If set (the default), completing with several candidates first inserts the longest prefix
+    /// they share, and only lists them once there is nothing more to insert. If not set, they
+    /// are listed straight away and the line is left alone until one is picked.
+    pub fn set_insert_common_prefix(&mut self, insert: bool) {
+        self.insert_common_prefix = insert;
+    }
+
+    pub fn insert_common_prefix(&self) -> bool {
+        self.insert_common_prefix
+    }
+
+    /// Sets the order completions are used in, alphabetical by default. Either way duplicates
+    /// are removed.
+    pub fn set_completion_sort(&mut self, sort: CompletionSort) {
+        self.completion_sort = sort;
+    }
+
+    pub fn completion_sort(&self) -> CompletionSort {
+        self.completion_sort
+    }
+
+    /// If set (the default), searching on from the last match wraps around to the other end of
+    /// the matches. Otherwise the search stays on the last match and rings the bell.
+    pub fn set_search_wrap(&mut self, wrap: bool) {
+        self.search_wrap = wrap;
+    }
+
+    pub fn search_wrap(&self) -> bool {
+        self.search_wrap
+    }
+
+    /// Sets a prompt drawn instead of the usual one while the line is a history entry (up arrow
+    /// for example), `(hist) $ ` say, until it is back on the new line. The prefix and suffix
+    /// set with [Editor::set_prompt_prefix] and [Editor::set_prompt_suffix] are kept. None (the
+    /// default) keeps the one prompt.
+    pub fn set_history_edit_prompt(&mut self, prompt: Option<Prompt>) {
+        self.history_edit_prompt = prompt;
+    }
+
+    pub fn history_edit_prompt(&self) -> Option<&Prompt> {
+        self.history_edit_prompt.as_ref()
+    }
+
+    /// Runs `postprocess` on the candidates of every completion, after they are sorted and
+    /// deduplicated and before any are inserted or listed. It can reorder, filter or cap them.
+    pub fn set_completion_postprocess(
+        &mut self,
+        postprocess: Option<&'a mut CompletionPostprocess>,
+    ) {
+        self.completion_postprocess = postprocess;
+    }
+
+    /// Runs `callback` with the word being completed when a completion finds nothing, to give
+    /// some feedback beyond the bell.
+    pub fn set_no_completion_callback(&mut self, callback: Option<&'a mut NoCompletionCallback>) {
+        self.no_completion_callback = callback;
+    }
+
+    /// True if the last `handle_newline` continued on a new line rather than accepting the
+    /// buffer, for example to draw a continuation prompt. Starts out false for each read since
+    /// every read uses a new `Editor`.
+    pub fn last_newline_continued(&self) -> bool {
+        self.newline_continued
+    }
+
+    /// If set (the default), accepting the line writes a newline so the terminal cursor ends up
+    /// below it. When embedding the editor in a program that does its own layout turn it off,
+    /// the cursor is then left at the end of the accepted line and moving it on is up to the
+    /// host. The accepted line is returned either way.
+    pub fn set_newline_on_accept(&mut self, newline_on_accept: bool) {
+        self.newline_on_accept = newline_on_accept;
+    }
+
+    pub fn newline_on_accept(&self) -> bool {
+        self.newline_on_accept
+    }
+
+    /// Sets how the buffer is shown. In the hidden and masked modes there are no
+    /// autosuggestions and history search and navigation are disabled, so history is not shown
+    /// alongside the secret. The buffer can still be edited normally.
+    pub fn set_echo_mode(&mut self, echo_mode: EchoMode) {
+        self.echo_mode = echo_mode;
+        if echo_mode != EchoMode::Normal {
+            self.clear_search();
+            self.autosuggestion = None;
+        }
+    }
+
+    /// Sets whether lines too long for the terminal wrap (the default) or scroll sideways.
+    pub fn set_long_line_mode(&mut self, mode: LongLineMode) {
+        self.long_line_mode = mode;
+    }
+
+    pub fn long_line_mode(&self) -> LongLineMode {
+        self.long_line_mode
+    }
+
+    /// Sets whether moving up from a non-empty line only goes through the history entries that
+    /// start with it (the default) or through all of them.
+    pub fn set_history_up_mode(&mut self, mode: HistoryUpMode) {
+        self.history_up_mode = mode;
+    }
+
+    pub fn history_up_mode(&self) -> HistoryUpMode {
+        self.history_up_mode
+    }
+
+    /// Width left after the prompt if the shown line should be scrolled rather than wrapped.
+    fn scroll_view_width(&self, prompt: &str, line: &Buffer) -> io::Result<Option<usize>> {
+        if self.long_line_mode == LongLineMode::Wrap || line.num_lines() > 1 {
+            return Ok(None);
+        }
+        let view_width =
+            util::terminal_width()?.saturating_sub(util::last_prompt_line_width(prompt));
+        // Room for both markers and the cursor.
+        Ok(if view_width >= 3 {
+            Some(view_width)
+        } else {
+            None
+        })
+    }
+
+    /// What to draw in place of the buffer and cursor, None if the buffer is drawn as is.
+    fn drawn_buffer(&self) -> Option<(Buffer, Cursor<'a>)> {
+        self.concealed().or_else(|| self.shown_buffer())
+    }
+
+    /// The buffer and cursor with tabs expanded and other control characters (inserted with
+    /// ctrl-v or pasted) drawn in caret notation or as <U+XXXX>, None if there are none.
+    fn shown_buffer(&self) -> Option<(Buffer, Cursor<'a>)> {
+        let mut cursor = Cursor::new_with_divider(self.editor_rules);
+        let buf = cur_buf!(self);
+        let shown = Buffer::from(util::shown_text(buf.as_str(), self.tab_stop)?);
+        let before_cursor =
+            util::shown_text(buf.range(0, self.cursor.curr_grapheme()), self.tab_stop)
+                .map(|s| Buffer::from(s).num_graphemes())
+                .unwrap_or_else(|| self.cursor.curr_grapheme());
+        cursor.move_cursor_to(&shown, before_cursor);
+        Some((shown, cursor))
+    }
+
+    /// What to draw in place of the buffer and cursor when the echo mode hides the buffer.
+    fn concealed(&self) -> Option<(Buffer, Cursor<'a>)> {
+        let mut cursor = Cursor::new_with_divider(self.editor_rules);
+        match self.echo_mode {
+            EchoMode::Normal => None,
+            EchoMode::Hidden => Some((Buffer::new(), cursor)),
+            EchoMode::Masked(mask) => {
+                let masked: String = cur_buf!(self)
+                    .range_graphemes_all()
+                    .map(|g| if g == "\n" { '\n' } else { mask })
+                    .collect();
+                let buf = Buffer::from(masked);
+                cursor.move_cursor_to(&buf, self.cursor.curr_grapheme());
+                Some((buf, cursor))
+            }
+        }
+    }
+
     fn is_search(&self) -> bool {
         self.reverse_search || self.forward_search
     }
 
+    /// `SearchStarted` or `SearchEnded` if a history search started or ended since this was last
+    /// called, so each one is sent once.
+    pub(crate) fn take_search_event(&mut self) -> Option<EventKind> {
+        if self.is_search() == self.search_event_sent {
+            return None;
+        }
+        self.search_event_sent = self.is_search();
+        if self.search_event_sent {
+            Some(EventKind::SearchStarted)
+        } else {
+            Some(EventKind::SearchEnded)
+        }
+    }
+
     fn clear_search(&mut self) {
+        if self.is_search() {
+            let loc = self.search_history_loc();
+            if let Some(search) = self.last_history_search.as_mut() {
+                search.forward = self.forward_search;
+                search.loc = loc;
+            }
+        }
         self.reverse_search = false;
         self.forward_search = false;
         self.history_subset_loc = None;
@@ -157,6 +691,51 @@ impl<'a> Editor<'a> {
         self.cursor.get_words_and_cursor_position(cur_buf!(self))
     }
 
+    /// The word the cursor is in or touching, as its start and end grapheme and its text. The
+    /// cursor touches a word when it is on the first character of the word or just after the
+    /// last one. None if the cursor is in whitespace between words.
+    pub fn word_under_cursor(&self) -> Option<(usize, usize, &str)> {
+        let (words, pos) = self.get_words_and_cursor_position();
+        let (start, end) = match pos {
+            CursorPosition::InWord(i)
+            | CursorPosition::OnWordLeftEdge(i)
+            | CursorPosition::OnWordRightEdge(i) => words[i],
+            CursorPosition::InSpace(_, _) => return None,
+        };
+        Some((
+            start,
+            end,
+            cur_buf!(self).range_graphemes(start, end).slice(),
+        ))
+    }
+
+    /// Captures the line being edited, the cursor and the history location so that
+    /// [Editor::restore] can bring them back, in this or a later editor.
+    pub fn snapshot(&self) -> EditorState {
+        EditorState {
+            buffer: cur_buf!(self).to_string(),
+            cursor: self.cursor.curr_grapheme(),
+            history_location: self.cur_history_loc,
+        }
+    }
+
+    /// Goes back to a state taken with [Editor::snapshot]. A history location past the end of
+    /// the history is dropped and the text becomes the new line, and the cursor is kept within
+    /// the text. Undo history is not part of the state and starts over.
+    pub fn restore(&mut self, state: EditorState) -> io::Result<()> {
+        self.clear_search();
+        self.show_completions_hint = None;
+        match state.history_location {
+            Some(i) if i < self.history.len() => {
+                self.cur_history_loc = Some(i);
+                self.hist_buf_valid = true;
+            }
+            _ => self.cur_history_loc = None,
+        }
+        *cur_buf_mut!(self) = Buffer::from(state.buffer);
+        self.move_cursor_to(state.cursor)
+    }
+
     pub fn history(&mut self) -> &mut History {
         self.history
     }
@@ -165,27 +744,57 @@ impl<'a> Editor<'a> {
         self.cursor.curr_grapheme()
     }
 
+    /// The terminal's current columns and rows, 80 by 24 if they cannot be read.
+    pub fn terminal_size(&self) -> (u16, u16) {
+        util::terminal_size().unwrap_or((80, 24))
+    }
+
+    /// Redraws the line without any search, completions or autosuggestion and moves to the next
+    /// terminal line, for when the line is given up on rather than entered.
+    pub(crate) fn abandon_line(&mut self) -> io::Result<()> {
+        self.clear_search();
+        self.show_completions_hint = None;
+        self.cursor.move_cursor_to_end_of_line(cur_buf!(self));
+        self.display_term_with_autosuggest(false)?;
+        self.term.write_newline()
+    }
+
     // XXX: Returning a bool to indicate doneness is a bit awkward, maybe change it
     pub fn handle_newline(&mut self) -> io::Result<bool> {
         self.history_fresh = false;
+        self.newline_continued = false;
         if self.is_search() {
             self.accept_autosuggestion()?;
         }
         self.clear_search();
-        if self.show_completions_hint.is_some() {
-            self.show_completions_hint = None;
+        if let Some((completions, highlighted)) = self.show_completions_hint.take() {
+            if self.completion_commit_on_enter {
+                // a highlighted completion is already in the buffer
+                if highlighted.is_none() {
+                    self.replace_completed_word(&completions[0])?;
+                }
+                self.display_term()?;
+            }
             return Ok(false);
         }
 
         let buf = cur_buf_mut!(self);
-        if self.editor_rules.evaluate_on_newline(buf) {
+        let should_evaluate = self.editor_rules.evaluate_on_newline(buf);
+        self.newline_continued = !should_evaluate;
+        if should_evaluate {
             self.cursor.move_cursor_to_end_of_line(cur_buf!(self));
             self.display_term_with_autosuggest(false)?;
-            self.term.write_newline()?;
+            if self.newline_on_accept {
+                self.term.write_newline()?;
+            }
             self.show_completions_hint = None;
             Ok(true)
         } else {
+            let indent = self.editor_rules.indent_for_newline(buf);
             buf.push('\n');
+            if !indent.is_empty() {
+                buf.insert_str(buf.num_graphemes(), &indent);
+            }
             self.cursor.move_cursor_to_end_of_line(buf);
             self.display_term()?;
             Ok(false)
@@ -200,13 +809,41 @@ impl<'a> Editor<'a> {
     fn freshen_history(&mut self) {
         if !self.history_fresh && self.history.load_history(false).is_ok() {
             self.history_fresh = true;
+            self.history_reloaded();
+        }
+    }
+
+    /// Reads the history file again to pick up what other processes added to it since it was
+    /// last loaded, and redraws with an autosuggestion taken from the history as it is now.
+    /// Fails if the history has no file.
+    pub fn reload_history(&mut self) -> io::Result<()> {
+        self.history.load_history(false)?;
+        self.history_fresh = true;
+        self.history_reloaded();
+        self.display_term()
+    }
+
+    // After the history is reloaded its entries may have moved or changed, so nothing taken
+    // from the old ones can be trusted.
+    fn history_reloaded(&mut self) {
+        if matches!(self.cur_history_loc, Some(i) if i >= self.history.len()) {
+            self.cur_history_loc = None;
+            self.hist_buf_valid = false;
         }
+        self.autosuggestion = self.current_autosuggestion();
     }
 
     /// Refresh incremental search, either when started or when the buffer changes.
     fn refresh_search(&mut self, forward: bool) {
         let search_history_loc = self.search_history_loc();
-        self.history_subset_index = self.history.search_index(&self.new_buf.to_string());
+        if !self.new_buf.is_empty() {
+            self.last_history_search = Some(HistorySearch {
+                pattern: self.new_buf.to_string(),
+                forward,
+                loc: None,
+            });
+        }
+        self.history_subset_index = self.history.search_index(self.new_buf.as_str());
         if !self.history_subset_index.is_empty() {
             self.history_subset_loc = if forward {
                 Some(0)
@@ -231,6 +868,7 @@ impl<'a> Editor<'a> {
 
         self.reverse_search = !forward;
         self.forward_search = forward;
+        self.search_step_forward = forward;
         self.cur_history_loc = None;
         self.hist_buf_valid = false;
         self.buffer_changed = false;
@@ -241,30 +879,127 @@ impl<'a> Editor<'a> {
     /// forward (top to bottom) other wise reverse (bottom to top).  It is valid to continue a
     /// search with forward changed (i.e. reverse search direction for one result).
     pub fn search(&mut self, forward: bool) -> io::Result<()> {
+        if self.echo_mode != EchoMode::Normal || !self.history.is_enabled() {
+            return Ok(());
+        }
         if !self.is_search() {
+            self.set_cancel_point();
             self.freshen_history();
             self.refresh_search(forward);
         } else if !self.history_subset_index.is_empty() {
-            self.history_subset_loc = if let Some(p) = self.history_subset_loc {
-                if forward {
-                    if p < self.history_subset_index.len() - 1 {
-                        Some(p + 1)
-                    } else {
-                        Some(0)
-                    }
-                } else if p > 0 {
-                    Some(p - 1)
-                } else {
-                    Some(self.history_subset_index.len() - 1)
+            self.search_step_forward = forward;
+            let last = self.history_subset_index.len() - 1;
+            self.history_subset_loc = match self.history_subset_loc {
+                Some(p) if forward && p < last => Some(p + 1),
+                Some(p) if !forward && p > 0 => Some(p - 1),
+                Some(p) if !self.search_wrap => {
+                    self.ring_bell()?;
+                    Some(p)
                 }
-            } else {
-                None
+                Some(_) if forward => Some(0),
+                Some(_) => Some(last),
+                None => None,
             };
         }
         self.display_term()?;
         Ok(())
     }
 
+    /// The pattern and direction (true for forward) of the last history search in this read, if
+    /// it has not been forgotten since.
+    pub fn last_history_search(&self) -> Option<(&str, bool)> {
+        self.last_history_search
+            .as_ref()
+            .map(|search| (search.pattern.as_str(), search.forward))
+    }
+
+    /// Forgets the last history search, for when another kind of search replaces it.
+    pub fn forget_history_search(&mut self) {
+        self.last_history_search = None;
+    }
+
+    /// Moves to the next history entry containing the pattern of the last history search, newer
+    /// ones if `forward`, without starting a new search. Returns false, changing nothing, if
+    /// there is no such entry.
+    pub fn search_history_again(&mut self, forward: bool) -> io::Result<bool> {
+        if self.is_search() {
+            self.search(forward)?;
+            return Ok(true);
+        }
+        let (pattern, from) = match &self.last_history_search {
+            Some(_) if !self.history.is_enabled() => return Ok(false),
+            Some(search) => (search.pattern.clone(), self.cur_history_loc.or(search.loc)),
+            None => return Ok(false),
+        };
+        let matches = self.history.search_index(&pattern);
+        let found = match (from, forward) {
+            (Some(from), true) => matches.into_iter().find(|i| *i > from),
+            (None, true) => None,
+            (from, false) => {
+                let end = from.unwrap_or_else(|| self.history.len());
+                matches.into_iter().rev().find(|i| *i < end)
+            }
+        };
+        match found {
+            Some(i) => {
+                self.show_history_match(i)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Moves to the closest history entry containing `pattern`, newer ones if `forward`, and
+    /// makes it the last history search so [Editor::search_history_again] repeats it. If there
+    /// is none that way it goes around to the other end of history, unless search wrapping is
+    /// off. Returns false, changing nothing, if no other entry contains `pattern`.
+    pub fn search_history_for(&mut self, pattern: &str, forward: bool) -> io::Result<bool> {
+        if pattern.is_empty() || !self.history.is_enabled() {
+            return Ok(false);
+        }
+        if self.is_search() {
+            self.accept_autosuggestion()?;
+            self.clear_search();
+        }
+        self.last_history_search = Some(HistorySearch {
+            pattern: pattern.to_string(),
+            forward,
+            loc: None,
+        });
+        if self.search_history_again(forward)? {
+            return Ok(true);
+        }
+        if !self.search_wrap {
+            return Ok(false);
+        }
+        let matches = self.history.search_index(pattern);
+        let wrapped = if forward {
+            matches.first()
+        } else {
+            matches.last()
+        };
+        match wrapped.copied() {
+            Some(i) if Some(i) != self.cur_history_loc => {
+                self.show_history_match(i)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // Shows history entry `i`, found by repeating a history search.
+    fn show_history_match(&mut self, i: usize) -> io::Result<()> {
+        if let Some(search) = self.last_history_search.as_mut() {
+            search.loc = Some(i);
+        }
+        self.history_subset_loc = None;
+        self.history_subset_index.clear();
+        self.cur_history_loc = Some(i);
+        self.hist_buf_valid = false;
+        cur_buf_mut!(self);
+        self.move_cursor_to_end_of_line()
+    }
+
     pub fn flush(&mut self) -> io::Result<()> {
         self.term.flush()
     }
@@ -283,8 +1018,20 @@ impl<'a> Editor<'a> {
 
     /// Inserts characters from internal register to the right or the left of the cursor, moving the
     /// cursor to the last character inserted.
+    ///
+    /// A line-wise register (see `Buffer::set_register_linewise`) goes on new lines below or above
+    /// the cursor's line instead, with the cursor moved to the start of the first one.
     pub fn paste(&mut self, right: bool, count: usize) -> io::Result<()> {
-        self.cursor.insert_around(cur_buf_mut!(self), right, count);
+        if cur_buf!(self).is_register_linewise() {
+            let cursor = self.cursor();
+            if let Some(start) =
+                cur_buf_mut!(self).insert_register_lines_around_idx(cursor, count, right)
+            {
+                self.cursor.move_cursor_to(cur_buf!(self), start);
+            }
+        } else {
+            self.cursor.insert_around(cur_buf_mut!(self), right, count);
+        }
         self.display_term()
     }
 
@@ -302,25 +1049,104 @@ impl<'a> Editor<'a> {
         self.show_completions_hint = None;
     }
 
+    fn set_cancel_point(&mut self) {
+        self.cancel_point = Some(CancelPoint {
+            history_loc: self.cur_history_loc,
+            hist_buf_valid: self.hist_buf_valid,
+            new_buf: self.new_buf.clone(),
+            hist_buf: self.hist_buf.clone(),
+            cursor: self.cursor.curr_grapheme(),
+        });
+    }
+
+    /// Cancels a history search or the completions being listed, putting the line back the way
+    /// it was before the search or the completing started, and redraws. Returns false if
+    /// neither was going on.
+    pub fn cancel(&mut self) -> io::Result<bool> {
+        let pending = self.is_search() || self.show_completions_hint.is_some();
+        let point = self.cancel_point.take();
+        if !pending {
+            return Ok(false);
+        }
+        self.clear_search();
+        self.show_completions_hint = None;
+        if let Some(point) = point {
+            self.cur_history_loc = point.history_loc;
+            self.hist_buf_valid = point.hist_buf_valid;
+            self.new_buf = point.new_buf;
+            self.hist_buf = point.hist_buf;
+            self.buffer_changed = true;
+            self.cursor.move_cursor_to(cur_buf!(self), point.cursor);
+        }
+        self.display_term()?;
+        Ok(true)
+    }
+
+    /// The completions being listed or cycled through and the index of the one in the buffer,
+    /// None if there are none. A host can use this to draw the list itself.
+    pub fn completion_state(&self) -> Option<(&[String], Option<usize>)> {
+        self.show_completions_hint
+            .as_ref()
+            .map(|(completions, i)| (&completions[..], *i))
+    }
+
     pub fn complete(&mut self, handler: &mut dyn Completer) -> io::Result<()> {
-        handler.on_event(Event::new(self, EventKind::BeforeComplete));
+        if !self.complete_inner(handler)? {
+            self.ring_bell()?;
+        }
+        Ok(())
+    }
 
-        if let Some((completions, i_in)) = self.show_completions_hint.take() {
-            let i = i_in.map_or(0, |i| (i + 1) % completions.len());
+    /// Does what the complete key (tab) does, for a host that offers completion some other way,
+    /// a button for example, rather than by feeding a key. Repeated calls cycle through the
+    /// completions just like pressing tab again, until the line is edited or
+    /// [Editor::skip_completions_hint] is called. The output is flushed.
+    pub fn trigger_completion(&mut self, handler: &mut dyn Completer) -> io::Result<()> {
+        self.complete_or_insert_spaces(handler)?;
+        self.flush()
+    }
 
-            match i_in {
-                Some(x) if cur_buf!(self) == &Buffer::from(&completions[x][..]) => {
-                    self.cursor.reset(cur_buf_mut!(self));
+    /// Completes like [Editor::complete], but when there is nothing to complete inserts the
+    /// spaces set with [Editor::set_tab_inserts_spaces] instead. This is what tab does.
+    pub fn complete_or_insert_spaces(&mut self, handler: &mut dyn Completer) -> io::Result<()> {
+        match self.tab_inserts_spaces {
+            Some(spaces) => {
+                if !self.complete_inner(handler)? {
+                    self.insert_str_after_cursor(&" ".repeat(spaces))?;
                 }
-                _ => self.delete_word_before_cursor(false)?,
+                Ok(())
             }
-            self.insert_str_after_cursor(&completions[i])?;
-
+            None => self.complete(handler),
+        }
+    }
+
+    // Returns false if the completer had nothing to offer.
+    fn complete_inner(&mut self, handler: &mut dyn Completer) -> io::Result<bool> {
+        handler.on_event(Event::new(self, EventKind::BeforeComplete));
+
+        if self.show_completions_hint.is_none() {
+            self.set_cancel_point();
+        }
+        if self.completions_provisional && self.show_completions_hint.is_some() {
+            // ask again rather than cycle through a list that may have grown
+            self.show_completions_hint = None;
+        }
+        if let Some((completions, i_in)) = self.show_completions_hint.take() {
+            let i = i_in.map_or(0, |i| (i + 1) % completions.len());
+
+            match i_in {
+                Some(x) if cur_buf!(self) == &Buffer::from(&completions[x][..]) => {
+                    self.cursor.reset(cur_buf_mut!(self));
+                    self.insert_str_after_cursor(&completions[i])?;
+                }
+                _ => self.replace_completed_word(&completions[i])?,
+            }
+
             self.show_completions_hint = Some((completions, Some(i)));
         }
         if self.show_completions_hint.is_some() {
             self.display_term()?;
-            return Ok(());
+            return Ok(true);
         }
 
         let (word, completions) = {
@@ -333,19 +1159,37 @@ impl<'a> Editor<'a> {
             };
 
             let mut completions = handler.completions(word);
-            completions.sort();
-            completions.dedup();
+            self.completions_provisional = !handler.is_complete();
+            match self.completion_sort {
+                CompletionSort::Alphabetical => {
+                    completions.sort();
+                    completions.dedup();
+                }
+                CompletionSort::AsReturned => {
+                    let mut seen = HashSet::new();
+                    completions.retain(|c| seen.insert(c.clone()));
+                }
+            }
+            if let Some(postprocess) = self.completion_postprocess.as_mut() {
+                completions = postprocess(word, completions);
+            }
             (word, completions)
         };
 
         if completions.is_empty() {
-            // Do nothing.
+            if let Some(callback) = self.no_completion_callback.as_mut() {
+                callback(word);
+            }
             self.show_completions_hint = None;
-            Ok(())
+            Ok(false)
         } else if completions.len() == 1 {
             self.show_completions_hint = None;
-            self.delete_word_before_cursor(false)?;
-            self.insert_str_after_cursor(completions[0].as_ref())
+            self.replace_completed_word(completions[0].as_ref())?;
+            Ok(true)
+        } else if (word.is_empty() && self.list_on_empty) || !self.insert_common_prefix {
+            self.show_completions_hint = Some((completions, None));
+            self.display_term()?;
+            Ok(true)
         } else {
             let common_prefix = util::find_longest_common_prefix(
                 &completions
@@ -358,15 +1202,45 @@ impl<'a> Editor<'a> {
                 let s = p.iter().cloned().collect::<String>();
 
                 if s.len() > word.len() && s.starts_with(word) {
-                    self.delete_word_before_cursor(false)?;
-                    return self.insert_str_after_cursor(s.as_ref());
+                    self.replace_completed_word(s.as_ref())?;
+                    return Ok(true);
                 }
             }
 
             self.show_completions_hint = Some((completions, None));
             self.display_term()?;
 
-            Ok(())
+            Ok(true)
+        }
+    }
+
+    // Swaps the word being completed for `completion` as a single undo step, splitting it out of
+    // any group already open (such as a vi insert) so undo goes back to the text before it.
+    fn replace_completed_word(&mut self, completion: &str) -> io::Result<()> {
+        let in_group = cur_buf!(self).in_undo_group();
+        if in_group {
+            cur_buf_mut!(self).end_undo_group();
+        }
+        cur_buf_mut!(self).start_undo_group();
+        self.delete_completed_word()?;
+        self.insert_str_after_cursor(completion)?;
+        cur_buf_mut!(self).end_undo_group();
+        if in_group {
+            cur_buf_mut!(self).start_undo_group();
+        }
+        Ok(())
+    }
+
+    // Deletes the word being completed, see `set_complete_whole_word`.
+    fn delete_completed_word(&mut self) -> io::Result<()> {
+        if self.complete_whole_word {
+            if let Some((start, end)) = self.get_word_before_cursor(false) {
+                self.cursor.move_cursor_to(cur_buf!(self), end);
+                self.cursor.delete_until_cursor(cur_buf_mut!(self), start);
+            }
+            self.display_term()
+        } else {
+            self.delete_word_before_cursor(false)
         }
     }
 
@@ -408,6 +1282,76 @@ impl<'a> Editor<'a> {
         self.display_term()
     }
 
+    /// Deletes back to the start of the whitespace separated word before the cursor, skipping
+    /// any whitespace directly before the cursor first.
+    pub fn delete_word_before_cursor_ws(&mut self) -> io::Result<()> {
+        self.delete_word_before_cursor_in_mode(ViMoveMode::Whitespace)
+    }
+
+    /// Deletes the word before the cursor using the boundaries set with
+    /// [Editor::set_word_delete_mode].
+    pub fn kill_word_before_cursor(&mut self) -> io::Result<()> {
+        self.delete_word_before_cursor_in_mode(self.word_delete_mode)
+    }
+
+    fn delete_word_before_cursor_in_mode(&mut self, mode: ViMoveMode) -> io::Result<()> {
+        let rule = DefaultViKeywordRule::new();
+        let buf = cur_buf!(self);
+        let before = |i: usize| if i > 0 { buf.grapheme_before(i) } else { None };
+        let mut start = self.cursor();
+        while matches!(before(start), Some(g) if g.trim().is_empty()) {
+            start -= 1;
+        }
+        let is_keyword = matches!(before(start), Some(g) if rule.is_vi_keyword(g));
+        while let Some(g) = before(start) {
+            if g.trim().is_empty()
+                || (mode == ViMoveMode::Keyword && rule.is_vi_keyword(g) != is_keyword)
+            {
+                break;
+            }
+            start -= 1;
+        }
+        self.cursor.delete_until_cursor(cur_buf_mut!(self), start);
+        self.display_term()
+    }
+
+    /// Replaces the text from grapheme `start` up to the cursor with `text`, leaving the cursor
+    /// after it. The replacement is undone as a single step.
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) -> io::Result<()> {
+        let buf = cur_buf_mut!(self);
+        buf.start_undo_group();
+        self.cursor.delete_until_cursor(buf, start);
+        self.cursor.insert_str_after_cursor(buf, text);
+        buf.end_undo_group();
+        self.display_term()
+    }
+
+    /// Replaces the contents of the current buffer with `text` and moves the cursor to the end.
+    /// The replacement is undone as a single step and leaves the register alone. Cancels any
+    /// search or completion in progress.
+    pub fn set_buffer(&mut self, text: &str) -> io::Result<()> {
+        self.clear_search();
+        self.show_completions_hint = None;
+        let buf = cur_buf_mut!(self);
+        let len = buf.num_graphemes();
+        buf.replace(0, len, text);
+        self.move_cursor_to_end_of_line()
+    }
+
+    /// Empties the line being edited, cancelling any search or completion in progress. On a
+    /// history entry it reverts the changes made to the entry instead and moves the cursor to
+    /// the start, the line typed before moving into history is kept.
+    pub fn clear_buffer(&mut self) -> io::Result<()> {
+        if self.cur_history_loc.is_none() {
+            return self.set_buffer("");
+        }
+        self.clear_search();
+        self.show_completions_hint = None;
+        self.hist_buf_valid = false;
+        cur_buf_mut!(self);
+        self.move_cursor_to(0)
+    }
+
     /// Clears the screen then prints the prompt and current buffer.
     pub fn clear(&mut self) -> io::Result<()> {
         self.term.clear()?;
@@ -415,14 +1359,36 @@ impl<'a> Editor<'a> {
         self.display_term()
     }
 
+    /// Draws the prompt and line again from scratch, for after something else wrote to the
+    /// terminal (a logging thread for example) and left the line garbled. Unlike a normal
+    /// redraw it does not move up to where the prompt was drawn, it starts over on the row the
+    /// terminal cursor is on, clearing it and everything below, so the other output should end
+    /// with a newline. Wrapping and the cursor column are worked out again from the current
+    /// terminal width. Only the last line of a multi line prompt is drawn again.
+    ///
+    /// The editor does nothing to keep other writers off the terminal, so the caller has to
+    /// make sure nothing else writes to it while the editor is drawing, for example by sending
+    /// the other output to the thread reading the line and calling this after writing it.
+    pub fn redraw(&mut self) -> io::Result<()> {
+        self.term.forget_cursor_line();
+        self.display_term()
+    }
+
     /// Move up (backwards) in history.
     pub fn move_up(&mut self) -> io::Result<()> {
-        if self.is_search() {
+        if self.echo_mode != EchoMode::Normal || !self.history.is_enabled() {
+            Ok(())
+        } else if self.is_search() {
             self.search(false)
         } else {
             self.hist_buf_valid = false;
             self.freshen_history();
-            if self.new_buf.num_graphemes() > 0 {
+            // only browse entries starting with the draft if we started from the draft, after
+            // jumping into history (M-< or a search) just step through it
+            if self.history_up_mode == HistoryUpMode::PrefixFilter
+                && self.new_buf.num_graphemes() > 0
+                && (self.history_subset_loc.is_some() || self.cur_history_loc.is_none())
+            {
                 match self.history_subset_loc {
                     Some(i) if i > 0 => {
                         self.history_subset_loc = Some(i - 1);
@@ -430,7 +1396,7 @@ impl<'a> Editor<'a> {
                     }
                     None => {
                         self.history_subset_index =
-                            self.history.get_history_subset(&self.new_buf.to_string());
+                            self.history.get_history_subset(self.new_buf.as_str());
                         if !self.history_subset_index.is_empty() {
                             self.history_subset_loc = Some(self.history_subset_index.len() - 1);
                             self.cur_history_loc = Some(
@@ -457,21 +1423,21 @@ impl<'a> Editor<'a> {
 
     /// Move down (forwards) in history, or to the new buffer if we reach the end of history.
     pub fn move_down(&mut self) -> io::Result<()> {
-        if self.is_search() {
+        if self.echo_mode != EchoMode::Normal || !self.history.is_enabled() {
+            Ok(())
+        } else if self.is_search() {
             self.search(true)
         } else {
             self.hist_buf_valid = false;
-            if self.new_buf.num_graphemes() > 0 {
-                if let Some(i) = self.history_subset_loc {
-                    if i < self.history_subset_index.len() - 1 {
-                        self.history_subset_loc = Some(i + 1);
-                        self.cur_history_loc = Some(self.history_subset_index[i + 1]);
-                    } else {
-                        self.cur_history_loc = None;
-                        self.history_subset_loc = None;
-                        self.history_subset_index.clear();
-                        self.history_fresh = false;
-                    }
+            if let Some(i) = self.history_subset_loc {
+                if i < self.history_subset_index.len() - 1 {
+                    self.history_subset_loc = Some(i + 1);
+                    self.cur_history_loc = Some(self.history_subset_index[i + 1]);
+                } else {
+                    self.cur_history_loc = None;
+                    self.history_subset_loc = None;
+                    self.history_subset_index.clear();
+                    self.history_fresh = false;
                 }
             } else {
                 match self.cur_history_loc.take() {
@@ -488,7 +1454,9 @@ impl<'a> Editor<'a> {
     /// Moves to the start of history (ie. the earliest history entry).
     pub fn move_to_start_of_history(&mut self) -> io::Result<()> {
         self.hist_buf_valid = false;
-        if self.history.is_empty() {
+        self.history_subset_loc = None;
+        self.history_subset_index.clear();
+        if self.history.is_empty() || !self.history.is_enabled() {
             self.cur_history_loc = None;
             self.hist_buf_valid = false;
             self.display_term()
@@ -503,6 +1471,8 @@ impl<'a> Editor<'a> {
     /// Moves to the end of history (ie. the new buffer).
     pub fn move_to_end_of_history(&mut self) -> io::Result<()> {
         self.hist_buf_valid = false;
+        self.history_subset_loc = None;
+        self.history_subset_index.clear();
         if self.cur_history_loc.is_some() {
             self.cur_history_loc = None;
             self.hist_buf_valid = false;
@@ -539,35 +1509,123 @@ impl<'a> Editor<'a> {
     }
 
     /// Inserts a string directly after the cursor, moving the cursor to the right.
-    ///
-    /// Note: it is more efficient to call `insert_chars_after_cursor()` directly.
     pub fn insert_str_after_cursor(&mut self, s: &str) -> io::Result<()> {
+        let s = self.limit_insert(s)?;
         self.cursor.insert_str_after_cursor(cur_buf_mut!(self), s);
         self.display_term()
     }
 
-    /// Inserts a character directly after the cursor, moving the cursor to the right.
+    /// Inserts a string directly after the cursor like `insert_str_after_cursor`, but does not
+    /// register as an action in the undo/redo buffer. For keymaps building an edit out of
+    /// several steps, the steps that should not be undone one by one. Undo replays the
+    /// recorded actions by position, so the caller has to keep it consistent, by wrapping the
+    /// whole edit in `Buffer::start_undo_group` and `Buffer::end_undo_group` and recording its
+    /// net effect for example.
+    pub fn insert_str_silent(&mut self, s: &str) -> io::Result<()> {
+        let s = self.limit_insert(s)?;
+        self.cursor
+            .insert_str_after_cursor_silent(cur_buf_mut!(self), s);
+        self.display_term()
+    }
+
+    /// Inserts text pasted into the terminal after the cursor exactly as it was pasted, as one
+    /// undo step. Its newlines go in as they are, not through the editor rules, and nothing is
+    /// indented, so only an enter typed outside of the paste accepts the line. `\r\n` and `\r`
+    /// line endings become `\n`.
+    pub fn insert_pasted(&mut self, text: &str) -> io::Result<()> {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.insert_str_after_cursor(&text)
+    }
+
+    /// Appends a string to the end of the buffer. The cursor stays where it is, even when it is
+    /// at the end of the buffer.
+    pub fn append_str(&mut self, s: &str) -> io::Result<()> {
+        let s = self.limit_insert(s)?;
+        let cursor = self.cursor();
+        let buf = cur_buf_mut!(self);
+        buf.insert_str(buf.num_graphemes(), s);
+        self.cursor.move_cursor_to(cur_buf!(self), cursor);
+        self.display_term()
+    }
+
+    /// Inserts a string at the start of the buffer. The cursor moves right with the text it was
+    /// on.
+    pub fn prepend_str(&mut self, s: &str) -> io::Result<()> {
+        let s = self.limit_insert(s)?;
+        let cursor = self.cursor();
+        let inserted = cur_buf_mut!(self).insert_str(0, s);
+        self.cursor
+            .move_cursor_to(cur_buf!(self), cursor + inserted);
+        self.display_term()
+    }
+
+    /// Inserts a character directly after the cursor, moving the cursor to the right. In
+    /// overwrite mode it replaces the character under the cursor instead, unless the cursor is
+    /// at the end of a line.
     pub fn insert_after_cursor(&mut self, c: char) -> io::Result<()> {
+        let cursor = self.cursor();
+        if self.overwrite && !matches!(cur_buf!(self).grapheme_after(cursor), None | Some("\n")) {
+            cur_buf_mut!(self).replace(cursor, cursor + 1, c.encode_utf8(&mut [0; 4]));
+            self.cursor.move_cursor_to(cur_buf!(self), cursor + 1);
+            return self.display_term();
+        }
+        if self.limit_insert(c.encode_utf8(&mut [0; 4]))?.is_empty() {
+            return Ok(());
+        }
         self.cursor.insert_char_after_cursor(cur_buf_mut!(self), c);
         self.display_term()
     }
 
     /// Inserts characters directly after the cursor, moving the cursor to the right.
     pub fn insert_chars_after_cursor(&mut self, cs: &[char]) -> io::Result<()> {
-        self.cursor
-            .insert_chars_after_cursor(cur_buf_mut!(self), cs);
-        self.display_term()
+        let s: String = cs.iter().collect();
+        self.insert_str_after_cursor(&s)
+    }
+
+    /// Turns overwrite mode on or off, it is off by default. While it is on typed characters
+    /// replace the one under the cursor, like the insert key toggles in a terminal. Only
+    /// `insert_after_cursor` is affected, pasted or completed text is still inserted.
+    pub fn set_overwrite(&mut self, overwrite: bool) {
+        self.overwrite = overwrite;
+    }
+
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    /// Limit the buffer to at most `max_length` graphemes, None for no limit. Inserts that would
+    /// go past the limit are cut short and ring the bell.
+    pub fn set_max_length(&mut self, max_length: Option<usize>) {
+        self.max_length = max_length;
+    }
+
+    /// Returns the part of `s` that fits under the max length, ringing the bell if any of it
+    /// does not.
+    fn limit_insert<'s>(&mut self, s: &'s str) -> io::Result<&'s str> {
+        let max_length = match self.max_length {
+            Some(max_length) => max_length,
+            None => return Ok(s),
+        };
+        let room = max_length.saturating_sub(cur_buf!(self).num_graphemes());
+        match s.grapheme_indices(true).nth(room) {
+            Some((end, _)) => {
+                self.ring_bell()?;
+                Ok(&s[..end])
+            }
+            None => Ok(s),
+        }
     }
 
     /// Deletes the character directly before the cursor, moving the cursor to the left.
-    /// If the cursor is at the start of the line, nothing happens.
+    /// If the cursor is at the start of the line, nothing happens. A character is a whole
+    /// grapheme cluster, so a letter goes together with any combining marks on it.
     pub fn delete_before_cursor(&mut self) -> io::Result<()> {
         self.cursor.delete_before_cursor(cur_buf_mut!(self));
         self.display_term()
     }
 
-    /// Deletes the character directly after the cursor. The cursor does not move.
-    /// If the cursor is at the end of the line, nothing happens.
+    /// Deletes the character (grapheme cluster) directly after the cursor. The cursor does not
+    /// move. If the cursor is at the end of the line, nothing happens.
     pub fn delete_after_cursor(&mut self) -> io::Result<()> {
         self.cursor.delete_after_cursor(cur_buf_mut!(self));
         self.display_term()
@@ -579,6 +1637,14 @@ impl<'a> Editor<'a> {
         self.display_term()
     }
 
+    /// Deletes back to the start of the line the cursor is on, which is only the start of the
+    /// buffer for single line buffers.
+    pub fn delete_line_before_cursor(&mut self) -> io::Result<()> {
+        let start = cur_buf!(self).line_start(self.cursor());
+        self.cursor.delete_until_cursor(cur_buf_mut!(self), start);
+        self.display_term()
+    }
+
     /// Yanks every character after the cursor until the end of the line.
     pub fn yank_all_after_cursor(&mut self) -> io::Result<()> {
         self.cursor.yank_all_after_cursor(cur_buf_mut!(self));
@@ -597,6 +1663,16 @@ impl<'a> Editor<'a> {
         self.display_term()
     }
 
+    /// Returns every character from the cursor until the given position, like `yank_until` but
+    /// without putting them in the buffer's register, so a paste still gets what was last
+    /// yanked or deleted.
+    pub fn yank_until_silent(&self, position: usize) -> String {
+        let cursor = self.cursor();
+        cur_buf!(self)
+            .range_graphemes(cursor.min(position), cursor.max(position))
+            .collect()
+    }
+
     /// Deletes every character from the cursor until the given position. Does not register as an
     /// action in the undo/redo buffer or in the buffer's register.
     pub fn delete_until_silent(&mut self, position: usize) -> io::Result<()> {
@@ -657,6 +1733,20 @@ impl<'a> Editor<'a> {
         self.display_term()
     }
 
+    /// Moves the cursor to the start of the line it is on. Unlike `move_cursor_to_start_of_line`
+    /// this stops after the previous newline in a multi-line buffer.
+    pub fn move_cursor_to_start_of_current_line(&mut self) -> io::Result<()> {
+        let start = cur_buf!(self).line_start(self.cursor());
+        self.move_cursor_to(start)
+    }
+
+    /// Moves the cursor to the end of the line it is on. Unlike `move_cursor_to_end_of_line`
+    /// this stops before the next newline in a multi-line buffer.
+    pub fn move_cursor_to_end_of_current_line(&mut self) -> io::Result<()> {
+        let end = cur_buf!(self).line_end(self.cursor());
+        self.move_cursor_to(end)
+    }
+
     pub fn curr_char(&self) -> Option<&str> {
         let buf = cur_buf!(self);
         buf.grapheme_after(self.cursor.curr_grapheme())
@@ -704,23 +1794,57 @@ impl<'a> Editor<'a> {
         self.move_cursor_to_end_of_line()
     }
 
+    /// Accept the autosuggestion up to the end of its next word, leaving the rest of it displayed.
+    /// Does nothing if no autosuggestion is showing or while searching history.
+    pub fn accept_autosuggestion_word(&mut self) -> io::Result<()> {
+        if !self.show_autosuggestions || self.is_search() {
+            return Ok(());
+        }
+        if let Some(autosuggestion) = self.autosuggestion.clone() {
+            let start = cur_buf!(self).num_graphemes();
+            let end = self
+                .editor_rules
+                .divide_words(&autosuggestion)
+                .into_iter()
+                .map(|(_, end)| end)
+                .find(|end| *end > start)
+                .unwrap_or_else(|| autosuggestion.num_graphemes());
+            let word = autosuggestion.range(start, end).to_owned();
+            self.move_cursor_to_end_of_line()?;
+            self.insert_str_after_cursor(&word)?;
+        }
+        Ok(())
+    }
+
     /// Returns current auto suggestion, for history search this is the current match if not
     /// searching the first history entry to start with current text (reverse order).
     /// Return None if nothing found.
     fn current_autosuggestion(&mut self) -> Option<Buffer> {
-        // If we are editing a previous history item no autosuggestion.
-        if self.hist_buf_valid || self.new_buf.num_graphemes() == 0 {
+        // If we are editing a previous history item or hiding the buffer no autosuggestion.
+        if self.hist_buf_valid
+            || self.new_buf.num_graphemes() == 0
+            || self.echo_mode != EchoMode::Normal
+        {
+            return None;
+        }
+        if !self.is_search() && self.new_buf.num_graphemes() < self.autosuggestion_min_chars {
             return None;
         }
+        if !self.is_search() && self.show_autosuggestions && self.autosuggestion_provider.is_some()
+        {
+            return self.provided_autosuggestion();
+        }
         let context_history = &self.history;
-        let autosuggestion = if self.is_search() {
+        let autosuggestion = if !context_history.is_enabled() {
+            None
+        } else if self.is_search() {
             self.search_history_loc().map(|i| &context_history[i])
         } else if self.show_autosuggestions {
             self.cur_history_loc
                 .map(|i| &context_history[i])
                 .or_else(|| {
                     context_history
-                        .get_newest_match(Some(context_history.len()), &self.new_buf.to_string())
+                        .get_newest_match(Some(context_history.len()), self.new_buf.as_str())
                         .map(|i| &context_history[i])
                 })
         } else {
@@ -729,10 +1853,70 @@ impl<'a> Editor<'a> {
         autosuggestion.map(|hist| hist.into())
     }
 
+    /// Asks the autosuggestion provider to complete the new buffer, reusing the last answer if
+    /// the buffer has not changed since.
+    fn provided_autosuggestion(&mut self) -> Option<Buffer> {
+        let line = self.new_buf.to_string();
+        let suffix = match &self.provided_suffix {
+            Some((query, suffix)) if *query == line => suffix.clone(),
+            _ => {
+                let suffix = match self.autosuggestion_provider.as_mut() {
+                    Some(provider) => provider(&line),
+                    None => None,
+                };
+                self.provided_suffix = Some((line.clone(), suffix.clone()));
+                suffix
+            }
+        };
+        match suffix {
+            Some(suffix) if !suffix.is_empty() => Some(Buffer::from(line + &suffix)),
+            _ => None,
+        }
+    }
+
     pub fn is_currently_showing_autosuggestion(&self) -> bool {
         self.autosuggestion.is_some()
     }
 
+    /// Shows `hint` dimmed after the line, like an autosuggestion but only when there is no
+    /// autosuggestion, for a default value or a type hint for example. It is never taken into
+    /// the line by the keys that accept an autosuggestion, only by
+    /// [Editor::accept_inline_hint]. The hint goes away as soon as the line changes unless it
+    /// is set again, and is shown from the next redraw.
+    pub fn set_inline_hint(&mut self, hint: Option<String>) {
+        self.inline_hint = hint.map(|hint| (hint, cur_buf!(self).to_string()));
+    }
+
+    /// The inline hint, None if there is none or the line has changed since it was set.
+    pub fn inline_hint(&self) -> Option<&str> {
+        match &self.inline_hint {
+            Some((hint, line)) if *line == cur_buf!(self).to_string() => Some(hint),
+            _ => None,
+        }
+    }
+
+    /// Appends the inline hint to the line. Returns false if there was no hint to accept.
+    pub fn accept_inline_hint(&mut self) -> io::Result<bool> {
+        match self.inline_hint().map(String::from) {
+            Some(hint) => {
+                self.inline_hint = None;
+                self.move_cursor_to_end_of_line()?;
+                self.insert_str_after_cursor(&hint)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // The line followed by the inline hint, drawn like an autosuggestion when there is none.
+    fn inline_hint_line(&self) -> Option<Buffer> {
+        if self.autosuggestion.is_some() || self.is_search() || self.echo_mode != EchoMode::Normal {
+            return None;
+        }
+        self.inline_hint()
+            .map(|hint| Buffer::from(cur_buf!(self).to_string() + hint))
+    }
+
     /// Override the prompt for incremental search if needed.
     fn get_prompt(&self) -> String {
         if self.is_search() {
@@ -745,11 +1929,19 @@ impl<'a> Editor<'a> {
                     color::Green.fg_str(),
                 )
             };
+            let (failed, direction) = match (hplace, self.search_step_forward) {
+                (0, true) => ("failed ", "i-search"),
+                (0, false) => ("failed ", "reverse-i-search"),
+                (_, true) => ("", "i-search"),
+                (_, false) => ("", "reverse-i-search"),
+            };
             let prefix = self.prompt.prefix();
             let suffix = self.prompt.suffix();
             format!(
-                "{}(search)'{}{}{}` ({}/{}):{} ",
+                "{}({}{})`{}{}{}' ({}/{}):{} ",
                 &prefix,
+                failed,
+                direction,
                 color,
                 self.current_buffer(),
                 color::Reset.fg_str(),
@@ -757,39 +1949,269 @@ impl<'a> Editor<'a> {
                 self.history_subset_index.len(),
                 &suffix
             )
+        } else if let (Some(_), Some(history_prompt)) =
+            (self.cur_history_loc, &self.history_edit_prompt)
+        {
+            format!(
+                "{}{}{}",
+                self.prompt.prefix(),
+                history_prompt.prompt,
+                self.prompt.suffix()
+            )
         } else {
             self.prompt.to_string()
         }
     }
 
+    /// Returns the terminal cursor position as (column, row), both 0-indexed. The row is relative
+    /// to the line the prompt (the last line of a multiline prompt) is drawn on, so add the
+    /// prompt's screen row to get absolute coordinates. Accounts for wrapped and multiline
+    /// buffers.
+    pub fn cursor_screen_position(&self) -> io::Result<(u16, u16)> {
+        let prompt = self.get_prompt();
+        let drawn = self.drawn_buffer();
+        let (buf, cursor) = match &drawn {
+            Some((buf, cursor)) => (buf, cursor),
+            None => (cur_buf!(self), &self.cursor),
+        };
+        let hint_line = self.inline_hint_line();
+        let suggestion = self.autosuggestion.as_ref().or(hint_line.as_ref());
+        let line = suggestion.unwrap_or(buf);
+        if let Some(view_width) = self.scroll_view_width(&prompt, line)? {
+            let line = line.to_string();
+            let cursor_col = terminal::line_width_to_cursor(&line, cursor.curr_grapheme());
+            let offset = terminal::scroll_offset(
+                self.term.scroll_offset(),
+                cursor_col,
+                util::str_width(&line),
+                view_width,
+            );
+            let col = util::last_prompt_line_width(&prompt) + cursor_col - offset;
+            return Ok((col as u16, 0));
+        }
+        let metrics = Metrics::new(&prompt, buf, cursor, suggestion)?;
+        let (col, row) = metrics.cursor_position();
+        Ok((col as u16, row as u16))
+    }
+
+    /// Moves the cursor to the character drawn at `(col, row)`, or after the last character on
+    /// that row if `col` is past it, the reverse of [Editor::cursor_screen_position]. Returns
+    /// false and leaves the cursor alone if nothing of the buffer is drawn there, like on the
+    /// prompt or below the buffer.
+    pub fn move_cursor_to_screen_position(&mut self, col: u16, row: u16) -> io::Result<bool> {
+        if self.is_search() {
+            return Ok(false);
+        }
+        let mut target = None;
+        for (pos, place) in self.cursor_screen_positions()?.into_iter().enumerate() {
+            let (pos_col, pos_row) = match place {
+                Some(place) => place,
+                None => continue,
+            };
+            if pos_row > row as usize {
+                break;
+            }
+            if pos_row == row as usize && pos_col <= col as usize {
+                target = Some(pos);
+            }
+        }
+        match target {
+            Some(pos) => {
+                self.move_cursor_to(pos)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Where `cursor_screen_position` would put the cursor for each position in the buffer, from
+    // 0 to the end, worked out in one pass. None for positions scrolled out of view.
+    fn cursor_screen_positions(&self) -> io::Result<Vec<Option<(usize, usize)>>> {
+        let prompt = self.get_prompt();
+        let prompt_width = util::last_prompt_line_width(&prompt);
+        let buf = cur_buf!(self);
+        let hidden = Buffer::new();
+        let shown = match self.echo_mode {
+            EchoMode::Hidden => &hidden,
+            _ => buf,
+        };
+        let hint_line = self.inline_hint_line();
+        let suggestion = self.autosuggestion.as_ref().or(hint_line.as_ref());
+        let view_width = self.scroll_view_width(&prompt, suggestion.unwrap_or(shown))?;
+        // a scrolled line has no newlines to wrap after
+        let width = match view_width {
+            Some(view_width) => view_width,
+            None => util::terminal_width()?,
+        };
+
+        // Same sums as `Metrics`: each line starts on a new row after the prompt's width.
+        let mut totals = Vec::with_capacity(buf.num_graphemes() + 1);
+        let mut base = 0;
+        let mut line_col = 0;
+        totals.push(prompt_width);
+        for g in buf.range_graphemes_all() {
+            match self.echo_mode {
+                EchoMode::Hidden => {}
+                _ if g.ends_with('\n') => {
+                    base = (base + prompt_width + line_col).div_ceil(width) * width;
+                    line_col = 0;
+                }
+                EchoMode::Normal => line_col += util::shown_width(g, line_col, self.tab_stop),
+                EchoMode::Masked(mask) => {
+                    line_col += util::str_width(mask.encode_utf8(&mut [0; 4]))
+                }
+            }
+            totals.push(base + prompt_width + line_col);
+        }
+
+        let view_width = match view_width {
+            Some(view_width) => view_width,
+            None => {
+                return Ok(totals
+                    .into_iter()
+                    .map(|total| Some((total % width, total / width)))
+                    .collect())
+            }
+        };
+        // a single line, scrolled the way it is drawn now
+        let line_width = match suggestion {
+            Some(suggestion) => util::str_width(suggestion.as_str()),
+            None => totals[totals.len() - 1] - prompt_width,
+        };
+        let offset = terminal::scroll_offset(
+            self.term.scroll_offset(),
+            totals[self.cursor.curr_grapheme()] - prompt_width,
+            line_width,
+            view_width,
+        );
+        Ok(totals
+            .into_iter()
+            .map(|total| {
+                let col = (total - prompt_width).checked_sub(offset)?;
+                if col > view_width {
+                    None
+                } else {
+                    Some((prompt_width + col, 0))
+                }
+            })
+            .collect())
+    }
+
+    /// Turns SGR mouse reporting on or off while the line is edited, see
+    /// [Context::set_mouse_enabled]. Off by default.
+    pub fn set_mouse_reporting(&mut self, mouse_reporting: bool) {
+        self.term.set_mouse_reporting(mouse_reporting);
+    }
+
+    /// Asks the terminal for the cursor position, it answers with a `ESC [ row ; col R` sequence
+    /// on the input.
+    pub(crate) fn request_cursor_position(&mut self) -> io::Result<()> {
+        self.term.request_cursor_position()
+    }
+
     pub fn set_no_eol(&mut self, no_eol: bool) {
         self.cursor.set_no_eol(no_eol);
     }
 
     fn display_term_with_autosuggest(&mut self, show_autosuggest: bool) -> io::Result<()> {
         let prompt = self.get_prompt();
+        let drawn = self.drawn_buffer();
         let buf = cur_buf!(self);
         let is_search = self.is_search();
 
-        let metrics = Metrics::new(&prompt, buf, &self.cursor, self.autosuggestion.as_ref())?;
+        let (shown_buf, shown_cursor) = match &drawn {
+            Some((buf, cursor)) => (buf, cursor),
+            None => (buf, &self.cursor),
+        };
+        let hint_line = self.inline_hint_line();
+        let shown_suggestion =
+            self.autosuggestion
+                .as_ref()
+                .or(hint_line.as_ref())
+                .map(
+                    |suggestion| match util::shown_text(suggestion.as_str(), self.tab_stop) {
+                        Some(shown) => Cow::Owned(Buffer::from(shown)),
+                        None => Cow::Borrowed(suggestion),
+                    },
+                );
+        // A history entry found by a search is cut to fit the terminal rather than wrapped.
+        let shown_suggestion = match shown_suggestion {
+            Some(entry) if is_search && self.scroll_view_width(&prompt, &entry)?.is_none() => {
+                let max_cols = util::terminal_width()?
+                    .saturating_sub(util::last_prompt_line_width(&prompt) + 1);
+                let lines: Vec<Cow<str>> = entry
+                    .lines()
+                    .map(|line| util::truncate_with_ellipsis(line, max_cols))
+                    .collect();
+                if lines.iter().any(|line| matches!(line, Cow::Owned(_))) {
+                    Some(Cow::Owned(Buffer::from(lines.join("\n"))))
+                } else {
+                    Some(entry)
+                }
+            }
+            shown_suggestion => shown_suggestion,
+        };
+        let line = match shown_suggestion.as_deref() {
+            Some(suggestion) if show_autosuggest => suggestion,
+            _ => shown_buf,
+        };
+        // The part of the history entry found by the search, shown in reverse video.
+        let highlight = match shown_suggestion.as_deref() {
+            Some(entry) if is_search && show_autosuggest && !shown_buf.is_empty() => {
+                let query = shown_buf.to_string();
+                entry
+                    .to_string()
+                    .find(&query)
+                    .map(|start| (start, start + query.len()))
+            }
+            _ => None,
+        };
+        let scroll_view_width = self.scroll_view_width(&prompt, line)?;
+        let metrics = Metrics::new(
+            &prompt,
+            shown_buf,
+            shown_cursor,
+            shown_suggestion.as_deref(),
+        )?;
+        let shown_cursor = shown_cursor.curr_grapheme();
         self.cursor.pre_display_adjustment(buf);
         self.term.clear_after_cursor()?;
-        let completion_lines = self
-            .term
-            .maybe_write_completions(self.show_completions_hint.as_ref())?;
+        let completion_lines = self.term.maybe_write_completions(
+            self.show_completions_hint.as_ref(),
+            self.completion_max_display,
+        )?;
 
         // Write the prompt
         self.term.write_prompt(&prompt)?;
+        self.term.set_highlight(highlight);
 
-        self.term.show_lines(
-            buf,
-            self.autosuggestion.as_ref(),
-            show_autosuggest,
-            metrics,
-            is_search,
-        )?;
+        match scroll_view_width {
+            Some(view_width) => {
+                let col = self.term.show_line_scrolled(
+                    shown_buf,
+                    shown_suggestion.as_deref(),
+                    show_autosuggest,
+                    shown_cursor,
+                    view_width,
+                    is_search,
+                )?;
+                let col = util::last_prompt_line_width(&prompt) + col;
+                self.term.display_at_column(col, completion_lines)?;
+            }
+            None => {
+                self.term.reset_scroll();
+                self.term.show_lines(
+                    shown_buf,
+                    shown_suggestion.as_deref(),
+                    show_autosuggest,
+                    shown_buf.range(0, shown_cursor).len(),
+                    metrics,
+                    is_search,
+                )?;
 
-        self.term.display(metrics, completion_lines)?;
+                self.term.display(metrics, completion_lines)?;
+            }
+        }
 
         Ok(())
     }
@@ -802,6 +2224,9 @@ impl<'a> Editor<'a> {
             self.refresh_search(forward);
         }
         self.autosuggestion = self.current_autosuggestion();
+        if self.inline_hint.is_some() && self.inline_hint().is_none() {
+            self.inline_hint = None;
+        }
 
         self.display_term_with_autosuggest(true)
     }
@@ -849,8 +2274,14 @@ impl<'a> From<Editor<'a>> for String {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
     use crate::prompt::Prompt;
-    use crate::{DefaultEditorRules, History};
+    use crate::{
+        indent_of_last_line, BasicCompleter, DefaultEditorRules, DefaultWordDivideRule, History,
+        NewlineRule,
+    };
 
     use super::*;
 
@@ -903,6 +2334,71 @@ mod tests {
         assert!(done.unwrap());
     }
 
+    #[test]
+    fn last_newline_continued() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("hel\\").unwrap();
+        assert!(!ed.last_newline_continued());
+        assert!(!ed.handle_newline().unwrap());
+        assert!(ed.last_newline_continued());
+
+        // closing a completion list neither continues nor accepts the line
+        let mut completer = BasicCompleter::new(vec!["hello", "help"]);
+        ed.clear_buffer().unwrap();
+        ed.insert_str_after_cursor("hel").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert!(!ed.handle_newline().unwrap());
+        assert!(!ed.last_newline_continued());
+
+        assert!(ed.handle_newline().unwrap());
+        assert!(!ed.last_newline_continued());
+    }
+
+    #[test]
+    fn newline_keeps_indent() {
+        struct IndentRule;
+        impl NewlineRule for IndentRule {
+            fn indent_for_newline(&self, buf: &Buffer) -> String {
+                indent_of_last_line(buf)
+            }
+        }
+
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::custom(DefaultWordDivideRule {}, IndentRule {});
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("(let\n    (a 1) \\").unwrap();
+        assert!(!ed.handle_newline().unwrap());
+        assert_eq!(ed.current_buffer().to_string(), "(let\n    (a 1) \\\n    ");
+        assert_eq!(ed.cursor(), 21);
+
+        // accepting the line adds nothing
+        ed.insert_str_after_cursor("a)").unwrap();
+        assert!(ed.handle_newline().unwrap());
+        assert_eq!(String::from(ed), "(let\n    (a 1) \\\n    a)");
+    }
+
     #[test]
     fn move_cursor_left() {
         let mut out = Vec::new();
@@ -1091,4 +2587,1643 @@ mod tests {
             String::from(ed)
         );
     }
+
+    #[test]
+    fn autosuggestion_provider() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("food").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut provider: AutosuggestionProvider = Box::new(|line: &str| {
+            if line == "foo" {
+                Some("bar".to_string())
+            } else {
+                None
+            }
+        });
+        ed.set_autosuggestion_provider(Some(&mut provider));
+
+        ed.insert_str_after_cursor("foo").unwrap();
+        assert!(ed.is_currently_showing_autosuggestion());
+        ed.accept_autosuggestion().unwrap();
+        assert!(!ed.is_currently_showing_autosuggestion());
+        assert_eq!(String::from(ed), "foobar");
+    }
+
+    #[test]
+    fn cursor_screen_position() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6, 0));
+        ed.insert_str_after_cursor("abc").unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (9, 0));
+
+        // wraps at the terminal width of 80 used by tests
+        ed.insert_str_after_cursor(&"x".repeat(80)).unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (9, 1));
+
+        // continuation lines are indented by the prompt width
+        ed.insert_str_after_cursor("\\\nab").unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (8, 2));
+    }
+
+    #[test]
+    fn set_and_clear_buffer() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("old").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("abc").unwrap();
+        ed.set_buffer("hello world").unwrap();
+        assert_eq!(ed.cursor(), 11);
+        assert_eq!(ed.current_buffer().to_string(), "hello world");
+        ed.undo();
+        assert_eq!(ed.current_buffer().to_string(), "abc");
+
+        ed.clear_buffer().unwrap();
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(ed.current_buffer().to_string(), "");
+
+        // on a history entry clearing reverts it and the draft is still there below it
+        ed.insert_str_after_cursor("ol").unwrap();
+        ed.move_up().unwrap();
+        ed.insert_str_after_cursor("er").unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "older");
+        ed.clear_buffer().unwrap();
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(ed.current_history_location(), Some(0));
+        assert_eq!(ed.current_buffer().to_string(), "old");
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_history_location(), None);
+        assert_eq!(ed.current_buffer().to_string(), "ol");
+    }
+
+    #[test]
+    fn set_buffer_keeps_register() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("abc").unwrap();
+        ed.current_buffer_mut().yank(0, 2);
+        ed.set_buffer("xyz").unwrap();
+        ed.clear_buffer().unwrap();
+        ed.paste(false, 1).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "ab");
+    }
+
+    #[test]
+    fn history_keeps_draft() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git add").unwrap();
+        history.push("ls").unwrap();
+        history.push("git commit").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("git").unwrap();
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git commit");
+        ed.insert_str_after_cursor(" -m").unwrap();
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git add");
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git commit");
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_history_location(), None);
+        assert_eq!(ed.current_buffer().to_string(), "git");
+        assert_eq!(ed.cursor(), 3);
+
+        // jumping into history directly still lets down come back to the draft
+        ed.move_to_start_of_history().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git add");
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "ls");
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git add");
+        ed.move_down().unwrap();
+        ed.move_down().unwrap();
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_history_location(), None);
+        assert_eq!(ed.current_buffer().to_string(), "git");
+    }
+
+    #[test]
+    fn complete_empty_word_lists() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = BasicCompleter::new(vec!["make build", "make check", "make test"]);
+        ed.insert_str_after_cursor("run ").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "run ");
+        assert!(ed.show_completions_hint.is_some());
+        // tab again cycles through the listed candidates
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "run make build");
+
+        ed.skip_completions_hint();
+        ed.clear_buffer().unwrap();
+        ed.set_list_on_empty(false);
+        ed.insert_str_after_cursor("run ").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "run make ");
+        assert!(ed.show_completions_hint.is_none());
+    }
+
+    #[test]
+    fn complete_whole_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = BasicCompleter::new(vec!["fooXbarbaz"]);
+        ed.insert_str_after_cursor("run fooXbar").unwrap();
+        ed.move_cursor_to(8).unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "run fooXbarbazbar");
+
+        ed.clear_buffer().unwrap();
+        ed.set_complete_whole_word(true);
+        ed.insert_str_after_cursor("run fooXbar").unwrap();
+        ed.move_cursor_to(8).unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "run fooXbarbaz");
+        assert_eq!(ed.cursor(), 14);
+    }
+
+    #[test]
+    fn completion_postprocess() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        // directories first, at most two
+        let mut postprocess: CompletionPostprocess = Box::new(|word, completions| {
+            assert_eq!(word, "s");
+            let (mut dirs, files): (Vec<String>, Vec<String>) =
+                completions.into_iter().partition(|c| c.ends_with('/'));
+            dirs.extend(files);
+            dirs.truncate(2);
+            dirs
+        });
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_completion_postprocess(Some(&mut postprocess));
+        let mut completer = BasicCompleter::new(vec!["setup.py", "src/", "scripts/", "sum.txt"]);
+        ed.insert_str_after_cursor("s").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(
+            ed.show_completions_hint,
+            Some((vec!["scripts/".to_string(), "src/".to_string()], None))
+        );
+    }
+
+    #[test]
+    fn no_completion_callback() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let attempted = Rc::new(RefCell::new(Vec::new()));
+        let words = attempted.clone();
+        let mut callback: NoCompletionCallback =
+            Box::new(move |word| words.borrow_mut().push(word.to_string()));
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_no_completion_callback(Some(&mut callback));
+        let mut completer = BasicCompleter::new(vec!["hello"]);
+        ed.insert_str_after_cursor("xy").unwrap();
+        ed.complete(&mut completer).unwrap();
+        ed.insert_str_after_cursor(" he").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "xy hello");
+        assert_eq!(*attempted.borrow(), vec!["xy".to_string()]);
+    }
+
+    #[test]
+    fn search_wraps_at_ends() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git add").unwrap();
+        history.push("ls").unwrap();
+        history.push("git commit").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_bell_style(BellStyle::Audible);
+        ed.insert_str_after_cursor("git").unwrap();
+        ed.search(false).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(2));
+        ed.search(true).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(0));
+        ed.search(false).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(2));
+        drop(ed);
+        assert!(!out.contains(&b'\x07'));
+    }
+
+    #[test]
+    fn search_stops_at_ends() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git add").unwrap();
+        history.push("ls").unwrap();
+        history.push("git commit").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_bell_style(BellStyle::Audible);
+        ed.set_search_wrap(false);
+        ed.insert_str_after_cursor("git").unwrap();
+        ed.search(false).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(2));
+        ed.search(true).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(2));
+        ed.search(false).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(0));
+        ed.search(false).unwrap();
+        assert_eq!(ed.search_history_loc(), Some(0));
+        drop(ed);
+        assert_eq!(out.iter().filter(|b| **b == b'\x07').count(), 2);
+    }
+
+    #[test]
+    fn search_highlights_match() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git commit").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.search(false).unwrap();
+        ed.insert_str_after_cursor("com").unwrap();
+        ed.flush().unwrap();
+        drop(ed);
+        let shown = String::from_utf8_lossy(&out).to_string();
+        assert!(shown.contains("git \x1b[7mcom\x1b[27mmit"));
+
+        // no match, nothing highlighted
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git commit").unwrap();
+        let mut buf = String::with_capacity(512);
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.search(false).unwrap();
+        ed.insert_str_after_cursor("xyz").unwrap();
+        ed.flush().unwrap();
+        drop(ed);
+        assert!(!String::from_utf8_lossy(&out).contains("\x1b[7m"));
+    }
+
+    #[test]
+    fn search_prompt() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git commit").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.search(false).unwrap();
+        ed.insert_str_after_cursor("git").unwrap();
+        let prompt = ed.get_prompt();
+        assert!(prompt.starts_with("(reverse-i-search)`"));
+        assert!(prompt.contains("git"));
+        assert!(prompt.ends_with("' (1/1): "));
+
+        ed.search(true).unwrap();
+        assert!(ed.get_prompt().starts_with("(i-search)`"));
+
+        // switching direction only lasts for one result
+        ed.insert_str_after_cursor("x").unwrap();
+        assert!(ed.get_prompt().starts_with("(failed reverse-i-search)`"));
+
+        ed.accept_autosuggestion().unwrap();
+        assert_eq!(ed.get_prompt(), "prompt");
+    }
+
+    #[test]
+    fn history_disabled() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git commit").unwrap();
+        history.set_enabled(false);
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("git").unwrap();
+        assert!(!ed.is_currently_showing_autosuggestion());
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_history_location(), None);
+        ed.move_to_start_of_history().unwrap();
+        assert_eq!(ed.current_history_location(), None);
+        ed.search(false).unwrap();
+        assert!(!ed.is_search());
+        assert_eq!(String::from(ed), "git");
+    }
+
+    #[test]
+    fn no_newline_on_accept() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            assert!(ed.newline_on_accept());
+            ed.set_newline_on_accept(false);
+            ed.insert_str_after_cursor("done").unwrap();
+            assert!(ed.handle_newline().unwrap());
+            assert_eq!(ed.current_buffer().to_string(), "done");
+        }
+        assert!(!out.ends_with(b"\r\n"));
+
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.insert_str_after_cursor("done").unwrap();
+            assert!(ed.handle_newline().unwrap());
+        }
+        assert!(out.ends_with(b"\r\n"));
+    }
+
+    #[test]
+    fn completion_max_display() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.set_completion_max_display(Some(2));
+            let mut completer = BasicCompleter::new(vec!["cab", "cad", "caf", "cag", "cah"]);
+            ed.insert_str_after_cursor("ca").unwrap();
+            ed.complete(&mut completer).unwrap();
+            assert_eq!(ed.show_completions_hint.as_ref().unwrap().0.len(), 5);
+            for _ in 0..4 {
+                ed.complete(&mut completer).unwrap();
+            }
+            assert_eq!(ed.current_buffer().to_string(), "cag");
+            ed.complete(&mut completer).unwrap();
+            assert_eq!(ed.current_buffer().to_string(), "cah");
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("… (3 more)"));
+        assert!(out.contains("… (4 more)"));
+        assert!(out.contains("cah"));
+    }
+
+    #[test]
+    fn completion_state() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = BasicCompleter::new(vec!["tab", "tag"]);
+        assert_eq!(ed.completion_state(), None);
+        ed.insert_str_after_cursor("ta").unwrap();
+        ed.complete(&mut completer).unwrap();
+        let expected = ["tab".to_string(), "tag".to_string()];
+        assert_eq!(ed.completion_state(), Some((&expected[..], None)));
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.completion_state(), Some((&expected[..], Some(0))));
+        ed.skip_completions_hint();
+        assert_eq!(ed.completion_state(), None);
+    }
+
+    #[test]
+    fn delete_whole_grapheme_cluster() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("cafe\u{301}").unwrap();
+        assert_eq!(ed.cursor(), 4);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6 + 4, 0));
+        ed.delete_before_cursor().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "caf");
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6 + 3, 0));
+
+        ed.insert_str_after_cursor("e\u{301}!").unwrap();
+        ed.move_cursor_to(3).unwrap();
+        ed.delete_after_cursor().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "caf!");
+        assert_eq!(ed.cursor(), 3);
+    }
+
+    #[test]
+    fn move_cursor_to_screen_position() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("hello\nworld").unwrap();
+
+        assert!(ed.move_cursor_to_screen_position(6 + 3, 0).unwrap());
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6 + 3, 0));
+        // past the end of a line goes to its end
+        assert!(ed.move_cursor_to_screen_position(40, 0).unwrap());
+        assert_eq!(ed.cursor(), 5);
+        assert!(ed.move_cursor_to_screen_position(6 + 1, 1).unwrap());
+        assert_eq!(ed.cursor(), 7);
+        assert!(ed.move_cursor_to_screen_position(6, 1).unwrap());
+        assert_eq!(ed.cursor(), 6);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6, 1));
+        ed.move_cursor_to(7).unwrap();
+
+        // the prompt and below the buffer are not part of the input
+        assert!(!ed.move_cursor_to_screen_position(2, 0).unwrap());
+        assert!(!ed.move_cursor_to_screen_position(6, 2).unwrap());
+        assert_eq!(ed.cursor(), 7);
+    }
+
+    #[test]
+    fn cursor_screen_positions_match_cursor_screen_position() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let long = "x".repeat(100);
+        ed.insert_str_after_cursor(&format!("a\tb\x01c\n{}\n\u{85}\u{4e2d}", long))
+            .unwrap();
+        let positions = ed.cursor_screen_positions().unwrap();
+        assert_eq!(positions.len(), ed.current_buffer().num_graphemes() + 1);
+        for (pos, place) in positions.into_iter().enumerate() {
+            ed.move_cursor_to(pos).unwrap();
+            let (col, row) = ed.cursor_screen_position().unwrap();
+            assert_eq!(place, Some((col as usize, row as usize)), "at {}", pos);
+        }
+
+        // a scrolled line is clicked on where it is drawn now
+        ed.set_long_line_mode(LongLineMode::Scroll);
+        ed.set_buffer(&long.repeat(2)).unwrap();
+        let (col, _) = ed.cursor_screen_position().unwrap();
+        let offset = 200 + 6 - col as usize;
+        assert!(offset > 0);
+        assert!(ed.move_cursor_to_screen_position(6 + 5, 0).unwrap());
+        assert_eq!(ed.cursor(), offset + 5);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6 + 5, 0));
+    }
+
+    #[test]
+    fn word_under_cursor() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("grep  fooé bar").unwrap();
+        assert_eq!(ed.word_under_cursor(), Some((11, 14, "bar")));
+        ed.move_cursor_to(8).unwrap();
+        assert_eq!(ed.word_under_cursor(), Some((6, 10, "fooé")));
+        ed.move_cursor_to(6).unwrap();
+        assert_eq!(ed.word_under_cursor(), Some((6, 10, "fooé")));
+        ed.move_cursor_to(5).unwrap();
+        assert_eq!(ed.word_under_cursor(), None);
+        ed.move_cursor_to(0).unwrap();
+        assert_eq!(ed.word_under_cursor(), Some((0, 4, "grep")));
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut history = History::new();
+        history.push("first").unwrap();
+        history.push("second").unwrap();
+        let state = {
+            let mut out = Vec::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.move_up().unwrap();
+            ed.insert_str_after_cursor(" edit").unwrap();
+            ed.move_cursor_to(2).unwrap();
+            ed.snapshot()
+        };
+        assert_eq!(
+            state,
+            EditorState {
+                buffer: "second edit".to_string(),
+                cursor: 2,
+                history_location: Some(1),
+            }
+        );
+
+        let mut out = Vec::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.restore(state.clone()).unwrap();
+        assert_eq!(ed.snapshot(), state);
+        assert_eq!(ed.current_buffer().to_string(), "second edit");
+        assert_eq!(ed.cursor(), 2);
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "");
+
+        // a history location that is gone leaves the text as a new line
+        ed.restore(EditorState {
+            buffer: "gone".to_string(),
+            cursor: 10,
+            history_location: Some(7),
+        })
+        .unwrap();
+        assert_eq!(ed.current_history_location(), None);
+        assert_eq!(ed.current_buffer().to_string(), "gone");
+        assert_eq!(ed.cursor(), 4);
+    }
+
+    #[test]
+    fn history_up_mode() {
+        let mut history = History::new();
+        for entry in &["git status", "ls", "git push", "make"] {
+            history.push(*entry).unwrap();
+        }
+        let mut out = Vec::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(ed.history_up_mode(), HistoryUpMode::PrefixFilter);
+        ed.insert_str_after_cursor("git").unwrap();
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git push");
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git status");
+        ed.move_down().unwrap();
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git");
+
+        ed.set_history_up_mode(HistoryUpMode::Chronological);
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "make");
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git push");
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "ls");
+        ed.move_down().unwrap();
+        ed.move_down().unwrap();
+        ed.move_down().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "git");
+    }
+
+    #[test]
+    fn completion_commit_on_enter() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = BasicCompleter::new(vec!["hello", "help", "helm"]);
+        ed.insert_str_after_cursor("hel").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert!(ed.completion_state().is_some());
+        // by default enter only closes the list
+        assert!(!ed.handle_newline().unwrap());
+        assert!(ed.completion_state().is_none());
+        assert_eq!(ed.current_buffer().to_string(), "hel");
+
+        ed.set_completion_commit_on_enter(true);
+        // nothing highlighted, the first completion is taken
+        ed.complete(&mut completer).unwrap();
+        assert!(!ed.handle_newline().unwrap());
+        assert!(ed.completion_state().is_none());
+        assert_eq!(ed.current_buffer().to_string(), "hello");
+
+        ed.clear_buffer().unwrap();
+        ed.insert_str_after_cursor("hel").unwrap();
+        ed.complete(&mut completer).unwrap();
+        ed.complete(&mut completer).unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.completion_state().unwrap().1, Some(1));
+        assert!(!ed.handle_newline().unwrap());
+        assert_eq!(ed.current_buffer().to_string(), "helm");
+        assert_eq!(ed.cursor(), 4);
+        // the next enter submits the line
+        assert!(ed.handle_newline().unwrap());
+    }
+
+    #[test]
+    fn autosuggestion_min_chars() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("cargo build").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_autosuggestion_min_chars(3);
+        ed.insert_str_after_cursor("ca").unwrap();
+        assert!(!ed.is_currently_showing_autosuggestion());
+        ed.insert_str_after_cursor("r").unwrap();
+        assert!(ed.is_currently_showing_autosuggestion());
+        ed.delete_before_cursor().unwrap();
+        assert!(!ed.is_currently_showing_autosuggestion());
+    }
+
+    #[test]
+    fn terminal_size() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(ed.terminal_size(), (80, 24));
+    }
+
+    #[test]
+    fn insert_common_prefix() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = BasicCompleter::new(vec!["foobar", "foobaz"]);
+        ed.insert_str_after_cursor("f").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "fooba");
+        assert!(ed.completion_state().is_none());
+
+        ed.clear_buffer().unwrap();
+        ed.set_insert_common_prefix(false);
+        ed.insert_str_after_cursor("f").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "f");
+        assert_eq!(ed.completion_state().unwrap().1, None);
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "foobar");
+    }
+
+    #[test]
+    fn inline_hint() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("cargo build").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("port=").unwrap();
+        ed.set_inline_hint(Some("8080".to_string()));
+        ed.display_term().unwrap();
+        assert_eq!(ed.inline_hint(), Some("8080"));
+        assert!(!ed.is_currently_showing_autosuggestion());
+        // not taken by the keys that accept an autosuggestion
+        ed.accept_autosuggestion().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "port=");
+        assert!(ed.accept_inline_hint().unwrap());
+        assert_eq!(ed.current_buffer().to_string(), "port=8080");
+        assert_eq!(ed.inline_hint(), None);
+
+        ed.set_inline_hint(Some(" (optional)".to_string()));
+        ed.insert_after_cursor('0').unwrap();
+        assert_eq!(ed.inline_hint(), None);
+        assert!(!ed.accept_inline_hint().unwrap());
+
+        // an autosuggestion is shown instead of the hint
+        ed.clear_buffer().unwrap();
+        ed.insert_str_after_cursor("car").unwrap();
+        ed.set_inline_hint(Some("go".to_string()));
+        ed.display_term().unwrap();
+        assert!(ed.inline_hint_line().is_none());
+        ed.set_show_autosuggestions(false);
+        ed.display_term().unwrap();
+        assert_eq!(
+            ed.inline_hint_line().map(|line| line.to_string()),
+            Some("cargo".to_string())
+        );
+    }
+
+    #[test]
+    fn search_match_cut_to_terminal_width() {
+        let long = format!("echo {}", "日本語".repeat(20));
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            history.push(long.as_str()).unwrap();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.insert_str_after_cursor("echo").unwrap();
+            ed.search(false).unwrap();
+        }
+        let out = String::from_utf8(out).unwrap();
+        let last_line = util::remove_codes(out.rsplit('\r').next().unwrap()).into_owned();
+        assert!(last_line.ends_with('…'));
+        assert!(util::str_width(&last_line) < 80);
+    }
+
+    #[test]
+    fn provisional_completions_asked_again() {
+        struct SlowCompleter {
+            calls: usize,
+        }
+        impl Completer for SlowCompleter {
+            fn completions(&mut self, _start: &str) -> Vec<String> {
+                self.calls += 1;
+                let mut found = vec!["ab1".to_string(), "ab2".to_string()];
+                if self.calls > 1 {
+                    found.push("ab3".to_string());
+                }
+                found
+            }
+            fn is_complete(&self) -> bool {
+                self.calls > 1
+            }
+        }
+
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = SlowCompleter { calls: 0 };
+        ed.insert_str_after_cursor("ab").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.completion_state().unwrap().0.len(), 2);
+        // the first list was provisional, tab asks again instead of cycling
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(completer.calls, 2);
+        assert_eq!(
+            ed.completion_state().unwrap(),
+            (
+                &["ab1".to_string(), "ab2".to_string(), "ab3".to_string()][..],
+                None
+            )
+        );
+        assert_eq!(ed.current_buffer().to_string(), "ab");
+        // now it is complete and tab cycles
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(completer.calls, 2);
+        assert_eq!(ed.current_buffer().to_string(), "ab1");
+    }
+
+    #[test]
+    fn completion_sort_as_returned() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer =
+            BasicCompleter::new(vec!["gitk", "git", "gist", "git", "gitk", "giraffe"]);
+        ed.set_insert_common_prefix(false);
+        ed.insert_str_after_cursor("gi").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(
+            ed.completion_state().unwrap().0,
+            &["giraffe", "gist", "git", "gitk"]
+        );
+
+        ed.clear_buffer().unwrap();
+        ed.set_completion_sort(CompletionSort::AsReturned);
+        ed.insert_str_after_cursor("gi").unwrap();
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(
+            ed.completion_state().unwrap().0,
+            &["gitk", "git", "gist", "giraffe"]
+        );
+        ed.complete(&mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "gitk");
+    }
+
+    #[test]
+    fn redraw_starts_on_the_cursor_row() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.insert_str_after_cursor("one\ntwo").unwrap();
+            ed.redraw().unwrap();
+            assert_eq!(ed.current_buffer().to_string(), "one\ntwo");
+            assert_eq!(ed.cursor(), 7);
+            ed.display_term().unwrap();
+        }
+        // only the normal redraw at the end moves up from the second line to the prompt
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("\x1B[1A").count(), 1);
+    }
+
+    #[test]
+    fn silent_insert_and_yank() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_silent("hello world").unwrap();
+        assert_eq!(ed.cursor(), 11);
+        assert_eq!(ed.undo(), None);
+
+        ed.yank_until(6).unwrap();
+        ed.move_cursor_to(0).unwrap();
+        assert_eq!(ed.yank_until_silent(5), "hello");
+        assert_eq!(ed.cursor(), 0);
+        // the register still holds what yank_until put there
+        ed.paste(false, 1).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "worldhello world");
+    }
+
+    #[test]
+    fn pasted_text_is_inserted_verbatim() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let snippet = "fn main() {\n    let s = \"(\";\n  echo a \\\n\tdone\n";
+        ed.insert_pasted(snippet).unwrap();
+        assert_eq!(ed.current_buffer().as_str(), snippet);
+        assert_eq!(ed.cursor(), ed.current_buffer().num_graphemes());
+        // the whole paste is one undo step
+        ed.undo();
+        assert_eq!(ed.current_buffer().as_str(), "");
+
+        ed.insert_pasted("one\r\ntwo\rthree").unwrap();
+        assert_eq!(ed.current_buffer().as_str(), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn trigger_completion_cycles_like_tab() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut completer = BasicCompleter::new(vec!["make build", "make check"]);
+        ed.set_insert_common_prefix(false);
+        ed.insert_str_after_cursor("ma").unwrap();
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            ed.trigger_completion(&mut completer).unwrap();
+            seen.push(ed.current_buffer().to_string());
+        }
+        // the first call lists the completions, the next ones cycle through them
+        assert_eq!(seen, vec!["ma", "make build", "make check", "make build"]);
+    }
+
+    #[test]
+    fn history_edit_prompt_shown_on_history_entries() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("ls -l").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("$ "),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_history_edit_prompt(Some(Prompt::from("(hist) $ ")));
+        ed.set_prompt_prefix("[I] ");
+        assert_eq!(ed.get_prompt(), "[I] $ ");
+        ed.move_up().unwrap();
+        assert_eq!(ed.current_history_location(), Some(0));
+        assert_eq!(ed.get_prompt(), "[I] (hist) $ ");
+        assert_eq!(ed.cursor_screen_position().unwrap(), (18, 0));
+        ed.move_down().unwrap();
+        assert_eq!(ed.get_prompt(), "[I] $ ");
+    }
+
+    #[test]
+    fn backspace_at_line_start_joins_lines() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("> "),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("echo one\ntwo").unwrap();
+        ed.move_cursor_to(9).unwrap();
+        // continuation lines are drawn lined up with the first one
+        assert_eq!(ed.cursor_screen_position().unwrap(), (2, 1));
+        ed.delete_before_cursor().unwrap();
+        assert_eq!(ed.current_buffer().as_str(), "echo onetwo");
+        assert_eq!(ed.cursor(), 8);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (10, 0));
+        ed.undo();
+        assert_eq!(ed.current_buffer().as_str(), "echo one\ntwo");
+    }
+
+    #[test]
+    fn cursor_color_closure_gets_cursor() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let seen_in_closure = seen.clone();
+        let mut closure: CursorColorClosure = Box::new(move |line, info| {
+            seen_in_closure
+                .borrow_mut()
+                .push((line.to_string(), info.cursor));
+            format!("<{}>", line)
+        });
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("> "),
+                Some(Box::new(|line| line.to_uppercase())),
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.set_cursor_color_closure(Some(&mut closure));
+            ed.insert_str_after_cursor("écho one\ntwo").unwrap();
+            seen.borrow_mut().clear();
+            ed.move_cursor_to(2).unwrap();
+            assert_eq!(
+                *seen.borrow(),
+                vec![("écho one".to_string(), Some(3)), ("two".to_string(), None)]
+            );
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("<two>"));
+        assert!(!out.contains("TWO"));
+    }
+
+    #[test]
+    fn overwrite_replaces_under_cursor() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("cat a\nb").unwrap();
+        ed.move_cursor_to(0).unwrap();
+        ed.set_overwrite(true);
+        for c in "dog xyz".chars() {
+            ed.insert_after_cursor(c).unwrap();
+        }
+        // the newline is not overwritten, the rest is inserted before it
+        assert_eq!(ed.current_buffer().as_str(), "dog xyz\nb");
+        assert_eq!(ed.cursor(), 7);
+        ed.undo();
+        assert_eq!(ed.current_buffer().as_str(), "dog xy\nb");
+
+        ed.set_overwrite(false);
+        ed.move_cursor_to(0).unwrap();
+        ed.insert_after_cursor('a').unwrap();
+        assert_eq!(ed.current_buffer().as_str(), "adog xy\nb");
+    }
+
+    #[test]
+    fn control_chars_shown_in_caret_notation() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.insert_str_after_cursor("a\x01b\x7f").unwrap();
+            ed.move_cursor_to(2).unwrap();
+            ed.flush().unwrap();
+            let (shown, cursor) = ed.shown_buffer().unwrap();
+            assert_eq!(shown.to_string(), "a^Ab^?");
+            assert_eq!(cursor.curr_grapheme(), 3);
+            assert_eq!(ed.current_buffer().to_string(), "a\x01b\x7f");
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("a^Ab^?"));
+        assert!(!out.contains('\x01'));
+    }
+
+    #[test]
+    fn cursor_after_control_char_uses_shown_width() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("> "),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("a\x01").unwrap();
+        // "> a^A"
+        assert_eq!(ed.cursor_screen_position().unwrap(), (5, 0));
+        ed.insert_str_after_cursor("\u{85}").unwrap();
+        assert_eq!(ed.shown_buffer().unwrap().0.to_string(), "a^A<U+0085>");
+        assert_eq!(ed.cursor_screen_position().unwrap(), (13, 0));
+    }
+
+    #[test]
+    fn tabs_expanded_to_tab_stops() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            ed.insert_str_after_cursor("a\tb").unwrap();
+            ed.move_cursor_to(2).unwrap();
+            assert_eq!(ed.cursor_screen_position().unwrap(), (6 + 8, 0));
+            ed.move_cursor_to(3).unwrap();
+            assert_eq!(ed.cursor_screen_position().unwrap(), (6 + 9, 0));
+
+            ed.set_tab_stop(4);
+            ed.insert_str_after_cursor("\n\tc\x01\td").unwrap();
+            let (shown, _) = ed.shown_buffer().unwrap();
+            assert_eq!(shown.to_string(), "a   b\n    c^A d");
+            ed.flush().unwrap();
+        }
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("a       b"));
+        assert!(!out.contains('\t'));
+    }
+
+    #[test]
+    fn append_and_prepend() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("let x").unwrap();
+        ed.move_cursor_to(4).unwrap();
+        ed.append_str(" = 1").unwrap();
+        assert_eq!(ed.cursor(), 4);
+        ed.prepend_str(">> ").unwrap();
+        assert_eq!(ed.cursor(), 7);
+        assert_eq!(ed.current_buffer().to_string(), ">> let x = 1");
+        ed.insert_after_cursor('y').unwrap();
+        assert_eq!(ed.current_buffer().to_string(), ">> let yx = 1");
+
+        ed.move_cursor_to_end_of_line().unwrap();
+        ed.append_str(" \\").unwrap();
+        assert_eq!(ed.cursor(), 13);
+
+        ed.set_max_length(Some(16));
+        ed.append_str("abcdef").unwrap();
+        assert_eq!(ed.current_buffer().to_string(), ">> let yx = 1 \\a");
+
+        ed.undo();
+        ed.undo();
+        ed.undo();
+        assert_eq!(ed.current_buffer().to_string(), ">> let x = 1");
+        ed.undo();
+        assert_eq!(ed.current_buffer().to_string(), "let x = 1");
+    }
+
+    #[test]
+    fn delete_word_before_cursor_ws() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.insert_str_after_cursor("foo-bar baz").unwrap();
+        ed.delete_word_before_cursor_ws().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "foo-bar ");
+        ed.delete_word_before_cursor_ws().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "");
+        assert_eq!(ed.cursor(), 0);
+    }
+
+    #[test]
+    fn kill_word_before_cursor_keyword() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_word_delete_mode(ViMoveMode::Keyword);
+        ed.insert_str_after_cursor("foo-bar baz").unwrap();
+        ed.kill_word_before_cursor().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "foo-bar ");
+        ed.kill_word_before_cursor().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "foo-");
+        ed.kill_word_before_cursor().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "foo");
+        ed.move_cursor_to(1).unwrap();
+        ed.kill_word_before_cursor().unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "oo");
+        assert_eq!(ed.cursor(), 0);
+    }
+
+    #[test]
+    fn long_line_scroll() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let line = "0123456789".repeat(10);
+        ed.insert_str_after_cursor(&line).unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (26, 1));
+
+        ed.set_long_line_mode(LongLineMode::Scroll);
+        ed.display_term().unwrap();
+        // 74 columns after the prompt, the cursor stays one short of the last one
+        assert_eq!(ed.cursor_screen_position().unwrap(), (78, 0));
+        ed.move_cursor_to(50).unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (78 - 50, 0));
+        ed.move_cursor_to(0).unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6, 0));
+        assert_eq!(ed.term.scroll_offset(), 0);
+
+        ed.set_buffer("short").unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (11, 0));
+    }
+
+    #[test]
+    fn max_length() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_max_length(Some(5));
+        ed.insert_str_after_cursor("abc").unwrap();
+        ed.insert_after_cursor('d').unwrap();
+        ed.insert_chars_after_cursor(&['e', 'f']).unwrap();
+        assert_eq!(ed.current_buffer().num_graphemes(), 5);
+        ed.insert_after_cursor('g').unwrap();
+        ed.move_cursor_to_start_of_line().unwrap();
+        ed.insert_str_after_cursor("xyz").unwrap();
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), "abcde");
+    }
+
+    #[test]
+    fn echo_mode_masked() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("secret123").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_echo_mode(EchoMode::Masked('*'));
+        ed.insert_str_after_cursor("secret").unwrap();
+        assert!(!ed.is_currently_showing_autosuggestion());
+        ed.delete_before_cursor().unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (11, 0));
+        ed.move_up().unwrap();
+        assert_eq!(String::from(ed), "secre");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("*****"));
+        assert!(!out.contains("sec"));
+    }
+
+    #[test]
+    fn echo_mode_hidden() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        ed.set_echo_mode(EchoMode::Hidden);
+        ed.insert_str_after_cursor("secret").unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6, 0));
+        assert_eq!(String::from(ed), "secret");
+        let out = String::from_utf8(out).unwrap();
+        assert!(!out.contains("sec"));
+        assert!(!out.contains('*'));
+    }
+
+    #[test]
+    fn cursor_position_wide_graphemes() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        // man, zwj, woman, zwj, girl: one grapheme two columns wide
+        ed.insert_str_after_cursor("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}")
+            .unwrap();
+        assert_eq!(ed.cursor(), 1);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (8, 0));
+        // e followed by a combining acute accent
+        ed.insert_str_after_cursor("e\u{301}").unwrap();
+        assert_eq!(ed.cursor(), 2);
+        assert_eq!(ed.cursor_screen_position().unwrap(), (9, 0));
+        ed.move_cursor_left(1).unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (8, 0));
+        ed.move_cursor_left(1).unwrap();
+        assert_eq!(ed.cursor_screen_position().unwrap(), (6, 0));
+    }
 }
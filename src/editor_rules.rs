@@ -9,6 +9,13 @@ pub trait NewlineRule {
     fn evaluate_on_newline(&self, buf: &Buffer) -> bool {
         last_non_ws_char_was_not_backslash(buf)
     }
+
+    /// Text inserted after the newline when the Editor continues to a new line instead of
+    /// evaluating, `buf` is the buffer before the newline is added. Default is no indent, see
+    /// [indent_of_last_line] to keep the indent of the previous line.
+    fn indent_for_newline(&self, _buf: &Buffer) -> String {
+        String::new()
+    }
 }
 
 /// Default NewlineRule implementation.
@@ -29,6 +36,15 @@ pub fn last_non_ws_char_was_not_backslash(buf: &Buffer) -> bool {
     !found_backslash
 }
 
+/// Leading whitespace of the last line in the buffer, an auto-indent for
+/// [NewlineRule::indent_for_newline].
+pub fn indent_of_last_line(buf: &Buffer) -> String {
+    let line_start = buf.line_start(buf.num_graphemes());
+    buf.range_graphemes(line_start, buf.num_graphemes())
+        .take_while(|g| *g == " " || *g == "\t")
+        .collect()
+}
+
 /// When the Editor is trying to place the term cursor it needs to know how to divide the words to
 /// determine its [CursorPosition] state.
 pub trait WordDivideRule {
@@ -139,6 +155,10 @@ where
     fn evaluate_on_newline(&self, buf: &Buffer) -> bool {
         self.newline_rule.evaluate_on_newline(buf)
     }
+
+    fn indent_for_newline(&self, buf: &Buffer) -> String {
+        self.newline_rule.indent_for_newline(buf)
+    }
 }
 
 impl<T, U> WordDivideRule for DefaultEditorRules<T, U>
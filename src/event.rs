@@ -21,4 +21,12 @@ pub enum EventKind {
     AfterKey(Key),
     /// Sent in `Editor.complete()`, before processing the completion.
     BeforeComplete,
+    /// Sent for a left click when mouse support is on, before the cursor is moved to it. The
+    /// position is 0-indexed and relative to the prompt line like
+    /// `Editor.cursor_screen_position()`.
+    MouseClick { col: u16, row: u16 },
+    /// Sent after the key that started an incremental history search.
+    SearchStarted,
+    /// Sent after the key that ended a history search, by accepting or leaving it.
+    SearchEnded,
 }
@@ -9,6 +9,10 @@ use std::{
 
 const DEFAULT_MAX_SIZE: usize = 1000;
 
+/// Runs on each entry before it goes in the history. Returns the entry to store, changed or
+/// not, or None to leave it out.
+pub type HistoryFilter = Box<dyn Fn(&str) -> Option<String>>;
+
 #[derive(Clone, Debug)]
 struct HistoryItem {
     context: Option<Vec<String>>,
@@ -61,6 +65,10 @@ pub struct History {
     max_contexts: usize,
     /// The current context to use for history searches.
     search_context: Option<String>,
+    /// When false nothing is recorded or loaded and the editor offers no history.
+    enabled: bool,
+    /// Changes or drops entries before they are pushed.
+    filter: Option<HistoryFilter>,
 }
 
 impl Default for History {
@@ -82,6 +90,8 @@ impl History {
             local_share: 0,
             max_contexts: 5,
             search_context: None,
+            enabled: true,
+            filter: None,
         }
     }
 
@@ -93,14 +103,48 @@ impl History {
         self.search_context = context;
     }
 
+    /// Turns history on (the default) or off. While off `push` and loading do nothing and the
+    /// editor offers no history navigation, search or history autosuggestions, for prompts that
+    /// should leave no trace. Items already in the history are kept for when it is turned back on.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets a filter run on every entry pushed, before it is compared with the last entry or
+    /// written to the history file. It can redact secrets (`export TOKEN=...`) or return None to
+    /// keep an entry out of the history altogether. Entries loaded from the file are not
+    /// filtered.
+    pub fn set_filter(&mut self, filter: Option<HistoryFilter>) {
+        self.filter = filter;
+    }
+
     /// Clears out the history.
     pub fn clear_history(&mut self) {
         self.buffers.clear();
     }
 
+    /// Clears out the history and empties the history file, if one is set.
+    pub fn clear_history_and_file(&mut self) -> io::Result<()> {
+        self.clear_history();
+        self.throwaways = 0;
+        self.local_share = 0;
+        if let Some(file_name) = &self.file_name {
+            File::create(file_name)?;
+            self.file_size = 0;
+        }
+        Ok(())
+    }
+
     /// Loads the history file from the saved path and appends it to the end of the history if append
     /// is true otherwise replace history.
     pub fn load_history(&mut self, append: bool) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
         if let Some(path) = self.file_name.clone() {
             let file_size = self.file_size;
             self.load_history_file_test(&path, file_size, append)
@@ -117,6 +161,9 @@ impl History {
 
     /// Loads the history file from path and appends it to the end of the history if append is true.
     pub fn load_history_file<P: AsRef<Path>>(&mut self, path: P, append: bool) -> io::Result<u64> {
+        if !self.enabled {
+            return Ok(0);
+        }
         self.load_history_file_test(path, 0, append)
     }
 
@@ -166,23 +213,47 @@ impl History {
                     self.add_buffer(buf, &mut dups);
                 }
             }
-            self.buffers.retain(|buf| {
-                if let Some(mut dup) = dups.get_mut(&buf.buffer) {
-                    if dup.0 > 1 {
-                        dup.0 -= 1;
-                        false
-                    } else {
-                        true
-                    }
-                } else {
-                    true
-                }
-            });
+            self.remove_loaded_duplicates(&mut dups);
             self.truncate();
         }
         Ok(new_length)
     }
 
+    /// Loads history written by [History::save_to_writer] (or a history file) from `reader`,
+    /// appending it to the end of the history if append is true otherwise replacing history.
+    pub fn load_from_reader<R: BufRead>(&mut self, reader: R, append: bool) -> io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let mut dups: HashMap<String, (usize, Option<Vec<String>>)> = HashMap::new();
+        if !append {
+            self.clear_history();
+        }
+        self.load_buffers_from(reader, &mut dups)?;
+        self.remove_loaded_duplicates(&mut dups);
+        self.truncate();
+        Ok(())
+    }
+
+    /// Keeps only the last copy of each item counted in `dups`.
+    fn remove_loaded_duplicates(
+        &mut self,
+        dups: &mut HashMap<String, (usize, Option<Vec<String>>)>,
+    ) {
+        self.buffers.retain(|buf| {
+            if let Some(mut dup) = dups.get_mut(&buf.buffer) {
+                if dup.0 > 1 {
+                    dup.0 -= 1;
+                    false
+                } else {
+                    true
+                }
+            } else {
+                true
+            }
+        });
+    }
+
     fn add_buffer(
         &mut self,
         mut buf: HistoryItem,
@@ -207,7 +278,14 @@ impl History {
             let status = format!("File not found {:?}", path);
             return Err(io::Error::new(io::ErrorKind::Other, status));
         };
-        let reader = BufReader::new(file);
+        self.load_buffers_from(BufReader::new(file), dups)
+    }
+
+    fn load_buffers_from<R: BufRead>(
+        &mut self,
+        reader: R,
+        dups: &mut HashMap<String, (usize, Option<Vec<String>>)>,
+    ) -> io::Result<()> {
         let mut context: Option<Vec<String>> = None;
         for line in reader.lines() {
             match line {
@@ -269,11 +347,35 @@ impl History {
         self.buffers.is_empty()
     }
 
+    /// Iterates over the history items from oldest to newest. Duplicates are removed as items
+    /// are pushed so each entry appears once.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator + '_ {
+        self.buffers.iter().map(|item| item.buffer.as_str())
+    }
+
+    /// The history item at `index` (0 is the oldest), or None if out of range.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.buffers.get(index).map(|item| item.buffer.as_str())
+    }
+
+    fn filtered(&self, item: String) -> Option<String> {
+        match &self.filter {
+            Some(filter) => filter(&item),
+            None => Some(item),
+        }
+    }
+
     /// Adds a "throwaway" history item.  Any of these will be removed once push
     /// is called.  Intended to allow "error" or other bad items to stick around
     /// long enough for the user to correct without cluttering history long term.
     pub fn push_throwaway(&mut self, new_item: impl Into<String>) -> io::Result<()> {
-        let new_item: String = new_item.into();
+        if !self.enabled {
+            return Ok(());
+        }
+        let new_item = match self.filtered(new_item.into()) {
+            Some(item) => item,
+            None => return Ok(()),
+        };
         // buffers[0] is the oldest entry
         // the new entry goes to the end
         if self.buffers.back().map(|b| b.buffer.to_string()) == Some(new_item.to_string()) {
@@ -292,7 +394,9 @@ impl History {
     /// size has been met. If writing to the disk is enabled, this function will be used for
     /// logging history to the designated history file.
     pub fn push(&mut self, new_item: impl Into<String>) -> io::Result<()> {
-        let new_item: String = new_item.into();
+        if !self.enabled {
+            return Ok(());
+        }
         // buffers[0] is the oldest entry
         // the new entry goes to the end
 
@@ -301,6 +405,10 @@ impl History {
             self.buffers.pop_back();
             self.throwaways -= 1;
         }
+        let new_item = match self.filtered(new_item.into()) {
+            Some(item) => item,
+            None => return Ok(()),
+        };
         self.local_share += 1;
         let mut same_last_context = true;
         if let Some(context) = &self.search_context {
@@ -342,7 +450,9 @@ impl History {
                 // Leave file size alone, if it is not right trigger a reload later.
                 let mut file = BufWriter::new(inner_file);
                 // Save the filesize after each append so we do not reload when we do not need to.
-                self.file_size += History::write_item(&mut file, &context, &item_str) as u64;
+                if let Ok(written) = History::write_item(&mut file, &context, &item_str) {
+                    self.file_size += written as u64;
+                }
             }
         }
         self.buffers.push_back(HistoryItem {
@@ -481,43 +591,45 @@ impl History {
         }
     }
 
-    fn write_item(file: &mut dyn Write, context: &Option<Vec<String>>, item: &str) -> usize {
+    fn write_item(
+        file: &mut dyn Write,
+        context: &Option<Vec<String>>,
+        item: &str,
+    ) -> io::Result<usize> {
         let mut ret = 0;
         if let Some(context) = context {
-            let _ = file.write_all(b"#<ctx>");
+            file.write_all(b"#<ctx>")?;
             ret += 6;
             let mut first = true;
             for ctx in context {
                 if !first {
-                    let _ = file.write_all(b":");
+                    file.write_all(b":")?;
                     ret += 1;
                 }
-                let _ = file.write_all(ctx.as_bytes());
+                file.write_all(ctx.as_bytes())?;
                 ret += ctx.as_bytes().len();
                 first = false;
             }
-            let _ = file.write_all(b"\n");
+            file.write_all(b"\n")?;
             ret += 1;
         }
-        let _ = file.write_all(item.as_bytes());
-        let _ = file.write_all(b"\n");
+        file.write_all(item.as_bytes())?;
+        file.write_all(b"\n")?;
         ret += item.as_bytes().len() + 1;
-        ret
+        Ok(ret)
     }
 
-    fn write_buffers<P: AsRef<Path>>(buffers: &VecDeque<HistoryItem>, path: P) -> io::Result<()> {
-        let mut file = BufWriter::new(File::create(&path)?);
-
-        // Write the commands to the history file.
-        for command in buffers.iter().cloned() {
-            let _ = History::write_item(&mut file, &command.context, &command.buffer);
+    /// Writes the history to `writer` in the history file format, oldest item first.
+    pub fn save_to_writer<W: Write>(&mut self, mut writer: W) -> io::Result<()> {
+        self.truncate();
+        for command in self.buffers.iter() {
+            History::write_item(&mut writer, &command.context, &command.buffer)?;
         }
-        Ok(())
+        writer.flush()
     }
 
     pub fn commit_to_file_path<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        self.truncate();
-        History::write_buffers(&self.buffers, path)
+        self.save_to_writer(BufWriter::new(File::create(&path)?))
     }
 
     pub fn commit_to_file(&mut self) -> io::Result<()> {
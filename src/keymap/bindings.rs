@@ -0,0 +1,301 @@
+//! User defined key bindings that a [KeyMap](crate::KeyMap) consults before its built in handling.
+use sl_console::event::Key;
+use std::io;
+use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Completer, Editor};
+
+/// What a bound key does.
+#[derive(Debug, Clone)]
+pub enum KeyAction {
+    /// Handle the key as if this other key had been pressed instead.
+    Key(Key),
+    /// Insert a literal string after the cursor.
+    InsertStr(String),
+    /// Complete the word before the cursor (Tab by default).
+    Complete,
+    /// Incremental history search backwards (Ctrl-r by default).
+    SearchBackward,
+    /// Incremental history search forwards (Ctrl-s by default).
+    SearchForward,
+    /// Accept the autosuggestion if one is showing (Ctrl-f by default).
+    AcceptAutosuggestion,
+    /// Finish the line as if enter had been pressed.
+    AcceptLine,
+    /// Swallow the key and do nothing.
+    Ignore,
+}
+
+impl KeyAction {
+    /// Performs the action, returning true if the line is done. [KeyAction::Key] is a remap and
+    /// is handled by the caller, it does nothing here.
+    pub(crate) fn perform<'a>(
+        &self,
+        editor: &mut Editor<'a>,
+        handler: &mut dyn Completer,
+    ) -> io::Result<bool> {
+        match self {
+            KeyAction::Key(_) | KeyAction::Ignore => {}
+            KeyAction::InsertStr(s) => editor.insert_str_after_cursor(s)?,
+            KeyAction::Complete => editor.complete(handler)?,
+            KeyAction::SearchBackward => editor.search(false)?,
+            KeyAction::SearchForward => editor.search(true)?,
+            KeyAction::AcceptAutosuggestion => {
+                if editor.is_currently_showing_autosuggestion() {
+                    editor.accept_autosuggestion()?;
+                }
+            }
+            KeyAction::AcceptLine => return editor.handle_newline(),
+        }
+        Ok(false)
+    }
+}
+
+/// A table of keys bound to [KeyAction]s.
+///
+/// ```
+/// use sl_liner::keymap::{Emacs, KeyAction};
+/// use sl_liner::KeyMap;
+/// use sl_console::event::{Key, KeyCode, KeyMod};
+///
+/// let mut keymap = Emacs::new();
+/// // Complete with ctrl-n instead of moving down in history.
+/// keymap.bind(Key::new_mod(KeyCode::Char('n'), KeyMod::Ctrl), KeyAction::Complete);
+/// // Make ctrl-h delete forward.
+/// keymap.bind(
+///     Key::new_mod(KeyCode::Char('h'), KeyMod::Ctrl),
+///     KeyAction::Key(Key::new(KeyCode::Delete)),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KeyBindings {
+    bindings: Vec<(Key, KeyAction)>,
+    sequences: Vec<KeySequence>,
+    // Most recently typed characters that are a prefix of some sequence.
+    pending: Vec<char>,
+    pending_ms: u128,
+    abbreviations: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+struct KeySequence {
+    keys: Vec<char>,
+    action: KeyAction,
+    timeout_ms: u32,
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn same_key(a: &Key, b: &Key) -> bool {
+    a.code == b.code && a.mods == b.mods
+}
+
+pub(crate) fn now_ms() -> u128 {
+    if let Ok(duration) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        duration.as_millis()
+    } else {
+        0
+    }
+}
+
+impl KeyBindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds `key` to `action`, replacing any previous binding for `key`.
+    pub fn bind(&mut self, key: Key, action: KeyAction) {
+        match self.bindings.iter_mut().find(|(k, _)| same_key(k, &key)) {
+            Some((_, a)) => *a = action,
+            None => self.bindings.push((key, action)),
+        }
+    }
+
+    /// Removes the binding for `key`, returning it if there was one.
+    pub fn unbind(&mut self, key: Key) -> Option<KeyAction> {
+        let idx = self.bindings.iter().position(|(k, _)| same_key(k, &key))?;
+        Some(self.bindings.remove(idx).1)
+    }
+
+    /// Returns the action bound to `key`.
+    pub fn get(&self, key: Key) -> Option<&KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| same_key(k, &key))
+            .map(|(_, a)| a)
+    }
+
+    /// Binds a sequence of typed characters to `action`, like vim's `inoremap jk <Esc>`. Each
+    /// character must be typed within `timeout_ms` of the previous one. The characters are
+    /// inserted as they are typed, if the sequence completes they are removed again and `action`
+    /// runs instead. Sequences only apply while the keymap is inserting text.
+    pub fn bind_sequence(&mut self, keys: &str, action: KeyAction, timeout_ms: u32) {
+        let keys: Vec<char> = keys.chars().collect();
+        if keys.is_empty() {
+            return;
+        }
+        match self.sequences.iter_mut().find(|seq| seq.keys == keys) {
+            Some(seq) => {
+                seq.action = action;
+                seq.timeout_ms = timeout_ms;
+            }
+            None => self.sequences.push(KeySequence {
+                keys,
+                action,
+                timeout_ms,
+            }),
+        }
+    }
+
+    /// Removes the binding for the sequence `keys`, returning it if there was one.
+    pub fn unbind_sequence(&mut self, keys: &str) -> Option<KeyAction> {
+        let keys: Vec<char> = keys.chars().collect();
+        let idx = self.sequences.iter().position(|seq| seq.keys == keys)?;
+        self.pending.clear();
+        Some(self.sequences.remove(idx).action)
+    }
+
+    /// Feeds a typed character to the bound sequences. If `c` completes one, returns how many of
+    /// the characters typed before it belong to the sequence along with its action.
+    pub(crate) fn match_sequence(&mut self, c: char, now_ms: u128) -> Option<(usize, KeyAction)> {
+        if self.sequences.is_empty() {
+            return None;
+        }
+        let elapsed = now_ms.saturating_sub(self.pending_ms);
+        self.pending_ms = now_ms;
+        let mut typed = mem::take(&mut self.pending);
+        typed.push(c);
+        // Prefer the longest run of recent characters, dropping the oldest until one fits.
+        for start in 0..typed.len() {
+            let candidate = &typed[start..];
+            let mut is_prefix = false;
+            for seq in &self.sequences {
+                if candidate.len() > 1 && elapsed >= seq.timeout_ms as u128 {
+                    continue;
+                }
+                if seq.keys == candidate {
+                    return Some((candidate.len() - 1, seq.action.clone()));
+                }
+                is_prefix |= seq.keys.starts_with(candidate);
+            }
+            if is_prefix {
+                self.pending = candidate.to_vec();
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Registers an abbreviation like vim's `:iabbrev`. When a word equal to `abbreviation` is
+    /// followed by a typed non-word character it is replaced with `expansion`.
+    pub fn set_abbreviation(&mut self, abbreviation: &str, expansion: &str) {
+        match self
+            .abbreviations
+            .iter_mut()
+            .find(|(a, _)| a == abbreviation)
+        {
+            Some((_, e)) => *e = expansion.to_string(),
+            None => self
+                .abbreviations
+                .push((abbreviation.to_string(), expansion.to_string())),
+        }
+    }
+
+    /// Removes `abbreviation`, returning its expansion if there was one.
+    pub fn remove_abbreviation(&mut self, abbreviation: &str) -> Option<String> {
+        let idx = self
+            .abbreviations
+            .iter()
+            .position(|(a, _)| a == abbreviation)?;
+        Some(self.abbreviations.remove(idx).1)
+    }
+
+    /// Called before `c` is inserted, expands the word before the cursor if `c` ends it and it
+    /// is an abbreviation. Words continuing past the cursor are left alone.
+    pub(crate) fn expand_abbreviation(&self, c: char, editor: &mut Editor) -> io::Result<()> {
+        if self.abbreviations.is_empty() || c.is_alphanumeric() || c == '_' {
+            return Ok(());
+        }
+        let cursor = editor.cursor();
+        let buf = editor.current_buffer();
+        if matches!(buf.grapheme_after(cursor), Some(g) if is_word(g)) {
+            return Ok(());
+        }
+        let mut start = cursor;
+        while start > 0 && matches!(buf.grapheme_before(start), Some(g) if is_word(g)) {
+            start -= 1;
+        }
+        let word = buf.range_graphemes(start, cursor).slice();
+        let expansion = self
+            .abbreviations
+            .iter()
+            .find(|(a, _)| a == word)
+            .map(|(_, e)| e.clone());
+        match expansion {
+            Some(expansion) if start < cursor => editor.replace_before_cursor(start, &expansion),
+            _ => Ok(()),
+        }
+    }
+
+    /// Forget any partially typed sequence.
+    pub(crate) fn reset_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty() && self.sequences.is_empty() && self.abbreviations.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+        self.sequences.clear();
+        self.abbreviations.clear();
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sl_console::event::{KeyCode, KeyMod};
+
+    #[test]
+    fn bind_replaces() {
+        let mut bindings = KeyBindings::new();
+        let key = Key::new_mod(KeyCode::Char('h'), KeyMod::Ctrl);
+        bindings.bind(key, KeyAction::Complete);
+        bindings.bind(key, KeyAction::InsertStr("x".to_string()));
+        assert!(matches!(bindings.get(key), Some(KeyAction::InsertStr(s)) if s == "x"));
+        assert!(bindings.get(Key::new(KeyCode::Char('h'))).is_none());
+        assert!(bindings.unbind(key).is_some());
+        assert!(bindings.is_empty());
+    }
+
+    #[test]
+    fn sequences() {
+        let mut bindings = KeyBindings::new();
+        bindings.bind_sequence("jk", KeyAction::Key(Key::new(KeyCode::Esc)), 100);
+        bindings.bind_sequence("jjj", KeyAction::InsertStr("x".to_string()), 100);
+        assert!(bindings.match_sequence('j', 1000).is_none());
+        assert!(matches!(
+            bindings.match_sequence('k', 1050),
+            Some((1, KeyAction::Key(_)))
+        ));
+        // too slow
+        assert!(bindings.match_sequence('j', 2000).is_none());
+        assert!(bindings.match_sequence('k', 2200).is_none());
+        // an unmatched prefix starts over
+        assert!(bindings.match_sequence('j', 3000).is_none());
+        assert!(bindings.match_sequence('j', 3010).is_none());
+        assert!(bindings.match_sequence('a', 3020).is_none());
+        assert!(bindings.match_sequence('j', 3030).is_none());
+        assert!(bindings.match_sequence('j', 3040).is_none());
+        assert!(matches!(
+            bindings.match_sequence('j', 3050),
+            Some((2, KeyAction::InsertStr(_)))
+        ));
+    }
+}
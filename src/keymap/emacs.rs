@@ -1,14 +1,23 @@
 use sl_console::event::{Key, KeyCode, KeyMod};
 use std::io;
-use unicode_width::UnicodeWidthStr;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::buffer::Buffer;
 use crate::cursor::CursorPosition;
+use crate::keymap::{literal_text, KeyBindings};
 use crate::Editor;
 use crate::KeyMap;
 
 /// Emacs keybindings for `Editor`. This is the default for `Context::read_line()`.
 ///
+/// Besides the usual control and meta keys, ctrl or alt with the left and right arrows move by
+/// word (like meta-b and meta-f) and ctrl or alt with home and end go to the start and end of the
+/// line. Arrows with shift are ignored since there is no region to extend. Ctrl-v inserts the
+/// next key literally, control characters are shown in caret notation (`^I`). Ctrl-g cancels a
+/// history search or completion list, putting the line back as it was before it, and meta-~
+/// flips the case of the character under the cursor like `~` in vi. Meta-. (or meta-_) inserts the
+/// last word of the previous history entry, repeating it replaces that word with the last word
+/// of the entry before. The insert key toggles overwrite mode, see [Editor::set_overwrite].
+///
 /// ```
 /// use sl_liner::*;
 /// use sl_liner::keymap;
@@ -26,7 +35,10 @@ use crate::KeyMap;
 /// ```
 #[derive(Default, Clone)]
 pub struct Emacs {
-    last_arg_fetch_index: Option<usize>,
+    /// History index and inserted length of the last meta-. so a repeat can replace it.
+    last_arg_fetch: Option<(usize, usize)>,
+    bindings: KeyBindings,
+    quoted_insert: bool,
 }
 
 impl Emacs {
@@ -37,16 +49,24 @@ impl Emacs {
     fn handle_ctrl_key<'a>(&mut self, c: char, ed: &mut Editor<'a>) -> io::Result<()> {
         match c {
             'l' => ed.clear(),
-            'a' => ed.move_cursor_to_start_of_line(),
-            'e' => ed.move_cursor_to_end_of_line(),
-            'b' => ed.move_cursor_left(1),
-            'f' => ed.move_cursor_right(1),
+            'a' => ed.move_cursor_to_start_of_current_line(),
+            'e' => ed.move_cursor_to_end_of_current_line(),
+            'b' => move_or_bell(ed, EmacsMoveDir::Left),
+            'f' => move_or_bell(ed, EmacsMoveDir::Right),
             'd' => ed.delete_after_cursor(),
             'p' => ed.move_up(),
             'n' => ed.move_down(),
             'u' => ed.delete_all_before_cursor(),
             'k' => ed.delete_all_after_cursor(),
-            'w' => ed.delete_word_before_cursor(true),
+            'w' => ed.kill_word_before_cursor(),
+            'g' => {
+                ed.cancel()?;
+                Ok(())
+            }
+            'v' => {
+                self.quoted_insert = true;
+                Ok(())
+            }
             'x' => {
                 if ed.undo().is_some() {
                     ed.move_cursor_to_end_of_line()
@@ -60,52 +80,72 @@ impl Emacs {
 
     fn handle_alt_key<'a>(&mut self, c: char, ed: &mut Editor<'a>) -> io::Result<()> {
         match c {
+            // in a multi-line buffer go to its start or end first, then through history
+            '<' if ed.current_buffer().num_lines() > 1 && ed.cursor() > 0 => {
+                ed.move_cursor_to_start_of_line()
+            }
+            '>' if ed.current_buffer().num_lines() > 1
+                && ed.cursor() < ed.current_buffer().num_graphemes() =>
+            {
+                ed.move_cursor_to_end_of_line()
+            }
             '<' => ed.move_to_start_of_history(),
             '>' => ed.move_to_end_of_history(),
             '\x7F' => ed.delete_word_before_cursor(true),
+            'f' if ed.is_currently_showing_autosuggestion() && ed.is_cursor_at_end_of_line() => {
+                ed.accept_autosuggestion_word()
+            }
             'f' => emacs_move_word(ed, EmacsMoveDir::Right),
             'b' => emacs_move_word(ed, EmacsMoveDir::Left),
             'r' => {
                 ed.revert()?;
                 Ok(())
             }
-            '.' => self.handle_last_arg_fetch(ed),
+            '.' | '_' => self.handle_last_arg_fetch(ed),
+            '~' => ed.flip_case(),
             _ => Ok(()),
         }
     }
 
     fn handle_last_arg_fetch<'a>(&mut self, ed: &mut Editor<'a>) -> io::Result<()> {
         // Empty history means no last arg to fetch.
-        if ed.history().is_empty() {
+        if ed.history().is_empty() || !ed.history().is_enabled() {
             return Ok(());
         }
 
-        let history_index = match self.last_arg_fetch_index {
-            Some(0) => return Ok(()),
-            Some(x) => x - 1,
-            None => ed
-                .current_history_location()
-                .unwrap_or(ed.history().len() - 1),
+        let (mut history_index, inserted) = match self.last_arg_fetch {
+            Some((index, inserted)) => (index, inserted),
+            None => (
+                ed.current_history_location()
+                    .unwrap_or_else(|| ed.history().len()),
+                0,
+            ),
+        };
+
+        // Go back to the next older entry that has a word in it, entries that are blank are
+        // skipped. Args are whitespace delimited so `cat src/main.rs` gives `src/main.rs`.
+        let last_arg = loop {
+            if history_index == 0 {
+                return Ok(());
+            }
+            history_index -= 1;
+            if let Some(last_arg) = ed.history()[history_index].split_whitespace().last() {
+                break last_arg.to_owned();
+            }
         };
 
         // If did a last arg fetch just before this, we need to delete it so it can be replaced by
         // this last arg fetch.
-        if self.last_arg_fetch_index.is_some() {
-            let buffer_len = ed.current_buffer().num_graphemes();
-            if let Some(last_arg) = ed.current_buffer().last_arg() {
-                let width = last_arg.width();
-                ed.delete_until(buffer_len - width)?;
-            }
+        if inserted > 0 {
+            let start = ed.cursor().saturating_sub(inserted);
+            ed.delete_until(start)?;
         }
 
         // Actually insert it
-        let buf: Buffer = ed.history()[history_index].into();
-        if let Some(last_arg) = buf.last_arg() {
-            ed.insert_str_after_cursor(last_arg)?;
-        }
+        ed.insert_str_after_cursor(&last_arg)?;
 
         // Edit the index in case the user does a last arg fetch again.
-        self.last_arg_fetch_index = Some(history_index);
+        self.last_arg_fetch = Some((history_index, last_arg.graphemes(true).count()));
 
         Ok(())
     }
@@ -113,13 +153,35 @@ impl Emacs {
 
 impl KeyMap for Emacs {
     fn init<'a>(&mut self, _ed: &mut Editor<'a>) {
-        self.last_arg_fetch_index = None;
+        self.last_arg_fetch = None;
+        self.quoted_insert = false;
+        self.bindings.reset_pending();
+    }
+
+    fn bindings(&self) -> Option<&KeyBindings> {
+        Some(&self.bindings)
+    }
+
+    fn bindings_mut(&mut self) -> Option<&mut KeyBindings> {
+        Some(&mut self.bindings)
+    }
+
+    fn is_capturing_input(&self) -> bool {
+        self.quoted_insert
     }
 
     fn handle_key_core<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        if self.quoted_insert {
+            self.quoted_insert = false;
+            return match literal_text(key) {
+                Some(text) => ed.insert_str_after_cursor(&text),
+                None => Ok(()),
+            };
+        }
+
         match (key.code, key.mods) {
-            (KeyCode::Char('.'), Some(KeyMod::Alt)) => {}
-            _ => self.last_arg_fetch_index = None,
+            (KeyCode::Char('.'), Some(KeyMod::Alt)) | (KeyCode::Char('_'), Some(KeyMod::Alt)) => {}
+            _ => self.last_arg_fetch = None,
         }
 
         match (key.code, key.mods) {
@@ -129,15 +191,31 @@ impl KeyMap for Emacs {
                 Some(KeyMod::Ctrl) => self.handle_ctrl_key(c, ed),
                 _ => Ok(()),
             },
+            (KeyCode::Right, Some(KeyMod::Ctrl)) | (KeyCode::Right, Some(KeyMod::Alt))
+                if ed.is_currently_showing_autosuggestion() && ed.is_cursor_at_end_of_line() =>
+            {
+                ed.accept_autosuggestion_word()
+            }
+            (key_code, Some(KeyMod::Ctrl)) | (key_code, Some(KeyMod::Alt)) => match key_code {
+                KeyCode::Left => emacs_move_word(ed, EmacsMoveDir::Left),
+                KeyCode::Right => emacs_move_word(ed, EmacsMoveDir::Right),
+                KeyCode::Home => ed.move_cursor_to_start_of_line(),
+                KeyCode::End => ed.move_cursor_to_end_of_line(),
+                _ => Ok(()),
+            },
             (key_code, None) => match key_code {
-                KeyCode::Left => ed.move_cursor_left(1),
-                KeyCode::Right => ed.move_cursor_right(1),
+                KeyCode::Left => move_or_bell(ed, EmacsMoveDir::Left),
+                KeyCode::Right => move_or_bell(ed, EmacsMoveDir::Right),
                 KeyCode::Up => ed.move_up(),
                 KeyCode::Down => ed.move_down(),
                 KeyCode::Home => ed.move_cursor_to_start_of_line(),
                 KeyCode::End => ed.move_cursor_to_end_of_line(),
                 KeyCode::Backspace => ed.delete_before_cursor(),
                 KeyCode::Delete => ed.delete_after_cursor(),
+                KeyCode::Insert => {
+                    ed.set_overwrite(!ed.overwrite());
+                    Ok(())
+                }
                 KeyCode::Null => Ok(()),
                 _ => Ok(()),
             },
@@ -152,6 +230,19 @@ enum EmacsMoveDir {
     Right,
 }
 
+/// Moves the cursor one character, ringing the bell at either end of the buffer.
+fn move_or_bell(ed: &mut Editor, direction: EmacsMoveDir) -> io::Result<()> {
+    let cursor = ed.cursor();
+    match direction {
+        EmacsMoveDir::Left => ed.move_cursor_left(1)?,
+        EmacsMoveDir::Right => ed.move_cursor_right(1)?,
+    }
+    if ed.cursor() == cursor {
+        ed.ring_bell()?;
+    }
+    Ok(())
+}
+
 fn emacs_move_word(ed: &mut Editor, direction: EmacsMoveDir) -> io::Result<()> {
     let (words, pos) = ed.get_words_and_cursor_position();
 
@@ -193,7 +284,9 @@ fn emacs_move_word(ed: &mut Editor, direction: EmacsMoveDir) -> io::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Completer, DefaultEditorRules, Editor, History, KeyMap, Prompt};
+    use crate::{
+        BellStyle, Completer, DefaultEditorRules, Editor, History, KeyMap, Prompt, TestEditor,
+    };
     use sl_console::event::Key;
 
     fn simulate_key_codes<'a, 'b, M: KeyMap, I>(
@@ -400,4 +493,399 @@ mod tests {
         assert_eq!(res.is_ok(), true);
         assert_eq!(ed.current_buffer().to_string(), "not empt".to_string());
     }
+
+    #[test]
+    /// meta-f at the end of the line accepts the next word of the autosuggestion
+    fn accept_autosuggestion_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git commit -m").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        ed.insert_str_after_cursor("gi").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Char('f'), KeyMod::Alt)].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "git".to_string());
+        assert!(ed.is_currently_showing_autosuggestion());
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Char('f'), KeyMod::Alt)].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "git commit".to_string());
+        assert_eq!(ed.cursor(), 10);
+        assert!(ed.is_currently_showing_autosuggestion());
+    }
+
+    #[test]
+    /// ctrl-a and ctrl-e stay on the current line of a multi-line buffer, meta-< and meta-> go
+    /// to the start and end of the buffer before moving through history
+    fn multi_line_start_end() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("old").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        ed.insert_str_after_cursor("first line\nsecond line")
+            .unwrap();
+        ed.move_cursor_to(14).unwrap();
+
+        let ctrl = |c| Key::new_mod(KeyCode::Char(c), KeyMod::Ctrl);
+        let alt = |c| Key::new_mod(KeyCode::Char(c), KeyMod::Alt);
+        simulate_keys(&mut map, &mut ed, [ctrl('a')].iter());
+        assert_eq!(ed.cursor(), 11);
+        simulate_keys(&mut map, &mut ed, [ctrl('e')].iter());
+        assert_eq!(ed.cursor(), 22);
+        ed.move_cursor_to(3).unwrap();
+        simulate_keys(&mut map, &mut ed, [ctrl('e')].iter());
+        assert_eq!(ed.cursor(), 10);
+        simulate_keys(&mut map, &mut ed, [ctrl('a')].iter());
+        assert_eq!(ed.cursor(), 0);
+
+        simulate_keys(&mut map, &mut ed, [alt('>')].iter());
+        assert_eq!(ed.cursor(), 22);
+        simulate_keys(&mut map, &mut ed, [alt('<')].iter());
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(ed.current_buffer().to_string(), "first line\nsecond line");
+        simulate_keys(&mut map, &mut ed, [alt('<')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "old");
+    }
+
+    #[test]
+    /// ctrl-v inserts the next key literally instead of running its command
+    fn quoted_insert() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let ctrl = |c| Key::new_mod(KeyCode::Char(c), KeyMod::Ctrl);
+
+        let done = simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Char('a')),
+                ctrl('v'),
+                Key::new(KeyCode::Char('\t')),
+                ctrl('v'),
+                ctrl('a'),
+                ctrl('v'),
+                Key::new(KeyCode::Char('\n')),
+                ctrl('v'),
+                Key::new(KeyCode::Left),
+                Key::new(KeyCode::Char('b')),
+            ]
+            .iter(),
+        );
+        assert!(!done);
+        assert_eq!(ed.current_buffer().to_string(), "a\t\x01\nb");
+    }
+
+    #[test]
+    /// ctrl and alt with the arrow keys move by word
+    fn modified_arrows_move_by_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        ed.insert_str_after_cursor("abc def ghi").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Left, KeyMod::Ctrl)].iter(),
+        );
+        assert_eq!(ed.cursor(), 8);
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Left, KeyMod::Alt)].iter(),
+        );
+        assert_eq!(ed.cursor(), 4);
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Right, KeyMod::Ctrl)].iter(),
+        );
+        assert_eq!(ed.cursor(), 7);
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Home, KeyMod::Ctrl)].iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Right, KeyMod::Shift)].iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+    }
+
+    #[test]
+    /// ctrl-w deletes back to whitespace unless the editor is set to stop at keywords
+    fn ctrl_w() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let ctrl_w = Key::new_mod(KeyCode::Char('w'), KeyMod::Ctrl);
+        ed.insert_str_after_cursor("foo-bar baz").unwrap();
+
+        simulate_keys(&mut map, &mut ed, [ctrl_w, ctrl_w].iter());
+        assert_eq!(ed.current_buffer().to_string(), "".to_string());
+
+        ed.insert_str_after_cursor("foo-bar baz").unwrap();
+        ed.set_word_delete_mode(crate::cursor::ViMoveMode::Keyword);
+        simulate_keys(&mut map, &mut ed, [ctrl_w, ctrl_w].iter());
+        assert_eq!(ed.current_buffer().to_string(), "foo-".to_string());
+    }
+
+    #[test]
+    /// ctrl-d deletes forward on a non-empty line and is only eof on an empty one
+    fn ctrl_d_deletes_forward() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let ctrl_d = Key::new_mod(KeyCode::Char('d'), KeyMod::Ctrl);
+        ed.insert_str_after_cursor("abcd").unwrap();
+        ed.move_cursor_to(1).unwrap();
+
+        simulate_keys(&mut map, &mut ed, [ctrl_d].iter());
+        assert_eq!(ed.current_buffer().to_string(), "acd".to_string());
+        assert_eq!(ed.cursor(), 1);
+
+        // at the end of a non-empty line there is nothing to delete
+        ed.move_cursor_to_end_of_line().unwrap();
+        simulate_keys(&mut map, &mut ed, [ctrl_d].iter());
+        assert_eq!(ed.current_buffer().to_string(), "acd".to_string());
+
+        ed.move_cursor_to(0).unwrap();
+        simulate_keys(&mut map, &mut ed, [ctrl_d, ctrl_d, ctrl_d].iter());
+        assert_eq!(ed.current_buffer().to_string(), "".to_string());
+        let res = map.handle_key(ctrl_d, &mut ed, &mut EmptyCompleter);
+        assert_eq!(res.err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    /// ctrl-g puts back the line from before a history search or a completion list
+    fn ctrl_g_cancels() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("cargo build").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let ctrl = |c| Key::new_mod(KeyCode::Char(c), KeyMod::Ctrl);
+        ed.insert_str_after_cursor("ca").unwrap();
+
+        simulate_keys(&mut map, &mut ed, [ctrl('r')].iter());
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('r')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "car");
+        simulate_keys(&mut map, &mut ed, [ctrl('g')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "ca");
+        assert_eq!(ed.cursor(), 2);
+        // no longer searching, enter takes the line as it is
+        assert!(!ed.cancel().unwrap());
+
+        let mut completer = crate::BasicCompleter::new(vec!["cat", "cargo"]);
+        let tab = Key::new(KeyCode::Char('\t'));
+        for _ in 0..3 {
+            map.handle_key(tab, &mut ed, &mut completer).unwrap();
+        }
+        assert_eq!(ed.current_buffer().to_string(), "cat");
+        map.handle_key(ctrl('g'), &mut ed, &mut completer).unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "ca");
+        assert!(ed.completion_state().is_none());
+    }
+
+    #[test]
+    fn alt_tilde_flips_case() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let alt_tilde = Key::new_mod(KeyCode::Char('~'), KeyMod::Alt);
+        ed.insert_str_after_cursor("aBc").unwrap();
+        ed.move_cursor_to(0).unwrap();
+        simulate_keys(&mut map, &mut ed, [alt_tilde, alt_tilde].iter());
+        assert_eq!(ed.current_buffer().to_string(), "Abc");
+        assert_eq!(ed.cursor(), 2);
+    }
+
+    #[test]
+    /// meta-. inserts the last word of older and older entries, replacing the one before
+    fn alt_dot_cycles_last_args() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("cat src/main.rs").unwrap();
+        history.push("   ").unwrap();
+        history.push("echo hello world").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let alt_dot = Key::new_mod(KeyCode::Char('.'), KeyMod::Alt);
+        ed.insert_str_after_cursor("vim x").unwrap();
+        ed.move_cursor_left(1).unwrap();
+
+        simulate_keys(&mut map, &mut ed, [alt_dot].iter());
+        assert_eq!(ed.current_buffer().to_string(), "vim worldx");
+        // the blank entry is skipped
+        simulate_keys(&mut map, &mut ed, [alt_dot].iter());
+        assert_eq!(ed.current_buffer().to_string(), "vim src/main.rsx");
+        // nothing older, the word stays
+        simulate_keys(&mut map, &mut ed, [alt_dot].iter());
+        assert_eq!(ed.current_buffer().to_string(), "vim src/main.rsx");
+
+        // another key starts the cycle over
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Char(' ')),
+                Key::new_mod(KeyCode::Char('_'), KeyMod::Alt),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "vim src/main.rs worldx");
+    }
+
+    #[test]
+    fn insert_key_toggles_overwrite() {
+        let mut ed = TestEditor::new(Box::new(Emacs::new()));
+        ed.set_line("hello");
+        let line = ed
+            .feed(&[
+                Key::new_mod(KeyCode::Char('a'), KeyMod::Ctrl),
+                Key::new(KeyCode::Insert),
+                Key::new(KeyCode::Char('j')),
+                Key::new(KeyCode::Insert),
+                Key::new(KeyCode::Char('x')),
+            ])
+            .unwrap();
+        assert_eq!(line, "jxello");
+    }
+
+    #[test]
+    /// moving past either end of the line rings the bell if one is configured
+    fn bell_at_line_ends() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            let mut map = Emacs::new();
+            ed.insert_str_after_cursor("ab").unwrap();
+            simulate_key_codes(&mut map, &mut ed, [KeyCode::Right].iter());
+            ed.set_bell_style(BellStyle::Audible);
+            simulate_key_codes(&mut map, &mut ed, [KeyCode::Left, KeyCode::Right].iter());
+            simulate_key_codes(&mut map, &mut ed, [KeyCode::Right].iter());
+            ed.move_cursor_to(0).unwrap();
+            simulate_key_codes(&mut map, &mut ed, [KeyCode::Left].iter());
+        }
+        assert_eq!(out.iter().filter(|b| **b == 7).count(), 2);
+    }
 }
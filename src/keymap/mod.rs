@@ -9,6 +9,88 @@ pub trait KeyMap {
 
     fn init<'a>(&mut self, _editor: &mut Editor<'a>) {}
 
+    /// Sets up a new editor for a line the keymap is already partway through, applying the
+    /// editor settings that go with its current state (vi's normal mode cursor and prompt for
+    /// example) without resetting it the way `init` does.
+    fn resume<'a>(&mut self, _editor: &mut Editor<'a>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// User key bindings consulted before the built in key handling. Keymaps that do not support
+    /// remapping return None.
+    fn bindings(&self) -> Option<&KeyBindings> {
+        None
+    }
+
+    fn bindings_mut(&mut self) -> Option<&mut KeyBindings> {
+        None
+    }
+
+    /// Binds `key` to `action`, overriding the built in handling of `key`. Ignored if this keymap
+    /// does not support remapping.
+    fn bind(&mut self, key: Key, action: KeyAction) {
+        if let Some(bindings) = self.bindings_mut() {
+            bindings.bind(key, action);
+        }
+    }
+
+    /// Binds a sequence of typed characters to `action`, see [KeyBindings::bind_sequence].
+    /// Ignored if this keymap does not support remapping.
+    fn bind_sequence(&mut self, keys: &str, action: KeyAction, timeout_ms: u32) {
+        if let Some(bindings) = self.bindings_mut() {
+            bindings.bind_sequence(keys, action, timeout_ms);
+        }
+    }
+
+    /// Registers an abbreviation, see [KeyBindings::set_abbreviation]. Ignored if this keymap
+    /// does not support remapping.
+    fn set_abbreviation(&mut self, abbreviation: &str, expansion: &str) {
+        if let Some(bindings) = self.bindings_mut() {
+            bindings.set_abbreviation(abbreviation, expansion);
+        }
+    }
+
+    /// Removes an abbreviation, returning its expansion if there was one.
+    fn remove_abbreviation(&mut self, abbreviation: &str) -> Option<String> {
+        self.bindings_mut()
+            .and_then(|b| b.remove_abbreviation(abbreviation))
+    }
+
+    /// True if plain character keys are currently inserted as text, which is when key sequences
+    /// apply.
+    fn is_inserting(&self) -> bool {
+        true
+    }
+
+    /// True while the keymap is collecting raw input, such as a vi search pattern or the key
+    /// after ctrl-v. Every key then goes to `handle_key_core`, including enter and tab, and key
+    /// bindings are not applied.
+    fn is_capturing_input(&self) -> bool {
+        false
+    }
+
+    /// Called by `handle_key` with each key once key sequences are resolved, before bindings
+    /// apply, so a keymap can record macros. A sequence that stands for a key is given as that
+    /// key, the characters typed for it are retracted with `retract_input` first.
+    fn record_key(&mut self, _key: Key) {}
+
+    /// Keys for `handle_key` to feed back through itself once the current key is handled, a
+    /// macro to replay for example. `replay_done` is called after them.
+    fn take_replay(&mut self) -> Option<Vec<Key>> {
+        None
+    }
+
+    fn replay_done<'a>(&mut self, _editor: &mut Editor<'a>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Removes the last `count` typed characters before the cursor when they turn out to be the
+    /// start of a key sequence.
+    fn retract_input<'a>(&mut self, count: usize, editor: &mut Editor<'a>) -> io::Result<()> {
+        let pos = editor.cursor().saturating_sub(count);
+        editor.delete_until_silent(pos)
+    }
+
     fn handle_key<'a>(
         &mut self,
         mut key: Key,
@@ -21,27 +103,86 @@ pub trait KeyMap {
 
         let is_empty = editor.current_buffer().is_empty();
 
-        if key.code == KeyCode::Char('h') && key.mods == Some(KeyMod::Ctrl) {
-            // XXX: Might need to change this when remappable keybindings are added.
-            key = Key::new(KeyCode::Backspace);
+        let sequence = match (key.code, key.mods) {
+            _ if self.is_capturing_input() => None,
+            (KeyCode::Char(c), None) if self.is_inserting() => self
+                .bindings_mut()
+                .and_then(|b| b.match_sequence(c, bindings::now_ms())),
+            _ => {
+                if let Some(b) = self.bindings_mut() {
+                    b.reset_pending();
+                }
+                None
+            }
+        };
+        let binding = match sequence {
+            Some((typed, action)) => {
+                self.retract_input(typed, editor)?;
+                if let KeyAction::Key(to) = &action {
+                    self.record_key(*to);
+                }
+                Some(action)
+            }
+            None => {
+                self.record_key(key);
+                if self.is_capturing_input() {
+                    None
+                } else {
+                    self.bindings().and_then(|b| b.get(key)).cloned()
+                }
+            }
+        };
+
+        match binding {
+            Some(KeyAction::Key(to)) => key = to,
+            Some(action) => {
+                done = action.perform(editor, handler)?;
+                if !matches!(action, KeyAction::Complete) {
+                    editor.skip_completions_hint();
+                }
+                send_search_event(editor, handler);
+                handler.on_event(Event::new(editor, EventKind::AfterKey(key)));
+                editor.flush()?;
+                return Ok(done);
+            }
+            // Unless bound to something else, ctrl-h is backspace.
+            None if key.code == KeyCode::Char('h') && key.mods == Some(KeyMod::Ctrl) => {
+                key = Key::new(KeyCode::Backspace);
+            }
+            None => {
+                if let (KeyCode::Char(c), None) = (key.code, key.mods) {
+                    if self.is_inserting() && !self.is_capturing_input() {
+                        if let Some(b) = self.bindings() {
+                            b.expand_abbreviation(c, editor)?;
+                        }
+                    }
+                }
+            }
         }
 
         match (key.code, key.mods) {
+            _ if self.is_capturing_input() => self.handle_key_core(key, editor)?,
+            (KeyCode::Char('c'), Some(KeyMod::Ctrl)) if editor.ctrl_c_clears_line() => {
+                editor.clear_buffer()?;
+            }
             (KeyCode::Char('c'), Some(KeyMod::Ctrl)) => {
                 editor.handle_newline()?;
+                send_search_event(editor, handler);
                 return Err(io::Error::new(ErrorKind::Interrupted, "ctrl-c"));
             }
             // if the current buffer is empty, treat ctrl-d as eof
             (KeyCode::Char('d'), Some(KeyMod::Ctrl)) if is_empty => {
                 editor.handle_newline()?;
+                send_search_event(editor, handler);
                 return Err(io::Error::new(ErrorKind::UnexpectedEof, "ctrl-d"));
             }
-            (KeyCode::Char('\t'), None) => editor.complete(handler)?,
+            _ if key == editor.complete_key() => editor.complete_or_insert_spaces(handler)?,
             (KeyCode::Char('\n'), None) => {
                 done = editor.handle_newline()?;
             }
-            (KeyCode::Char('f'), Some(KeyMod::Ctrl))
-                if editor.is_currently_showing_autosuggestion() =>
+            _ if editor.accept_autosuggestion_keys().contains(&key)
+                && editor.is_currently_showing_autosuggestion()
+                && editor.is_cursor_at_end_of_line() =>
             {
                 editor.accept_autosuggestion()?;
             }
@@ -51,26 +192,61 @@ pub trait KeyMap {
             (KeyCode::Char('s'), Some(KeyMod::Ctrl)) => {
                 editor.search(true)?;
             }
-            (KeyCode::Right, None)
-                if editor.is_currently_showing_autosuggestion()
-                    && editor.is_cursor_at_end_of_line() =>
-            {
-                editor.accept_autosuggestion()?;
-            }
             _ => {
                 self.handle_key_core(key, editor)?;
                 editor.skip_completions_hint();
             }
         };
 
+        send_search_event(editor, handler);
         handler.on_event(Event::new(editor, EventKind::AfterKey(key)));
 
         editor.flush()?;
 
-        Ok(done)
+        match self.take_replay() {
+            Some(keys) => {
+                let mut res = Ok(done);
+                for key in keys {
+                    res = self.handle_key(key, editor, handler);
+                    if !matches!(res, Ok(false)) {
+                        break;
+                    }
+                }
+                let finished = self.replay_done(editor);
+                res.and_then(|done| finished.map(|_| done))
+            }
+            None => Ok(done),
+        }
+    }
+}
+
+/// Tells the completer a history search started or ended if the last key did that.
+fn send_search_event<'a>(editor: &mut Editor<'a>, handler: &mut dyn Completer) {
+    if let Some(kind) = editor.take_search_event() {
+        handler.on_event(Event::new(editor, kind));
     }
 }
 
+/// The text ctrl-v followed by `key` inserts: the character itself, or the control character
+/// for ctrl and special keys (tab, escape, backspace). None for keys without one, like arrows.
+pub(crate) fn literal_text(key: Key) -> Option<String> {
+    match (key.code, key.mods) {
+        (KeyCode::Char(c), None) => Some(c.to_string()),
+        (KeyCode::Char('?'), Some(KeyMod::Ctrl)) => Some('\x7f'.to_string()),
+        (KeyCode::Char(c), Some(KeyMod::Ctrl)) if ('@'..='_').contains(&c.to_ascii_uppercase()) => {
+            Some((((c.to_ascii_uppercase() as u8) & 0x1f) as char).to_string())
+        }
+        (KeyCode::Char(c), Some(KeyMod::Alt)) => Some(format!("\x1b{}", c)),
+        (KeyCode::Esc, None) => Some('\x1b'.to_string()),
+        (KeyCode::Backspace, None) => Some('\x7f'.to_string()),
+        (KeyCode::BackTab, None) => Some('\t'.to_string()),
+        _ => None,
+    }
+}
+
+pub mod bindings;
+pub use bindings::{KeyAction, KeyBindings};
+
 pub mod vi;
 pub use vi::Vi;
 
@@ -80,7 +256,7 @@ pub use emacs::Emacs;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{DefaultEditorRules, History, Prompt};
+    use crate::{BasicCompleter, DefaultEditorRules, History, Prompt};
     use sl_console::event::Key;
     use std::io::ErrorKind;
 
@@ -101,6 +277,65 @@ mod tests {
         }
     }
 
+    #[derive(Default)]
+    struct SearchEventCompleter {
+        events: Vec<&'static str>,
+    }
+
+    impl Completer for SearchEventCompleter {
+        fn completions(&mut self, _start: &str) -> Vec<String> {
+            Vec::default()
+        }
+
+        fn on_event(&mut self, event: Event<'_, '_>) {
+            match event.kind {
+                EventKind::SearchStarted => self.events.push("started"),
+                EventKind::SearchEnded => self.events.push("ended"),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    /// starting and ending a history search are each sent once
+    fn search_events() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("cargo build").unwrap();
+        history.push("cargo test").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = crate::keymap::Emacs::new();
+        let mut completer = SearchEventCompleter::default();
+        let ctrl_r = Key::new_mod(KeyCode::Char('r'), KeyMod::Ctrl);
+        for key in [ctrl_r, Key::new(KeyCode::Char('c')), ctrl_r] {
+            map.handle_key(key, &mut ed, &mut completer).unwrap();
+        }
+        assert_eq!(completer.events, vec!["started"]);
+
+        let ctrl_g = Key::new_mod(KeyCode::Char('g'), KeyMod::Ctrl);
+        map.handle_key(ctrl_g, &mut ed, &mut completer).unwrap();
+        assert_eq!(completer.events, vec!["started", "ended"]);
+
+        // enter ends the search and the line
+        map.handle_key(ctrl_r, &mut ed, &mut completer).unwrap();
+        let enter = Key::new(KeyCode::Char('\n'));
+        assert!(map.handle_key(enter, &mut ed, &mut completer).unwrap());
+        assert_eq!(
+            completer.events,
+            vec!["started", "ended", "started", "ended"]
+        );
+    }
+
     #[test]
     /// when the current buffer is empty, ctrl-d generates and eof error
     fn ctrl_d_empty() {
@@ -181,4 +416,211 @@ mod tests {
         assert_eq!(res.is_err(), true);
         assert_eq!(res.err().unwrap().kind(), ErrorKind::Interrupted);
     }
+
+    #[test]
+    /// bindings are consulted before the built in handling
+    fn bindings() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        map.bind(
+            Key::new_mod(KeyCode::Char('c'), KeyMod::Ctrl),
+            KeyAction::InsertStr("hello".to_string()),
+        );
+        map.bind(
+            Key::new_mod(KeyCode::Char('h'), KeyMod::Ctrl),
+            KeyAction::Key(Key::new(KeyCode::Left)),
+        );
+
+        let res = map.handle_key(
+            Key::new_mod(KeyCode::Char('c'), KeyMod::Ctrl),
+            &mut ed,
+            &mut EmptyCompleter,
+        );
+        assert!(res.is_ok());
+        let res = map.handle_key(
+            Key::new_mod(KeyCode::Char('h'), KeyMod::Ctrl),
+            &mut ed,
+            &mut EmptyCompleter,
+        );
+        assert!(res.is_ok());
+        assert_eq!(ed.cursor(), 4);
+        assert_eq!(ed.current_buffer().to_string(), "hello".to_string());
+    }
+
+    #[test]
+    /// ctrl-c can be set to clear the line instead
+    fn ctrl_c_clears_line() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = TestKeyMap;
+        ed.set_ctrl_c_clears_line(true);
+        ed.insert_str_after_cursor("not empty").unwrap();
+        ed.search(false).unwrap();
+
+        let res = map.handle_key(
+            Key::new_mod(KeyCode::Char('c'), KeyMod::Ctrl),
+            &mut ed,
+            &mut EmptyCompleter,
+        );
+        assert!(res.is_ok());
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), "");
+    }
+
+    #[test]
+    /// completion can be moved off tab, which is then inserted like other characters
+    fn complete_key() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let mut completer = BasicCompleter::new(vec!["hello"]);
+        ed.set_complete_key(Key::new_mod(KeyCode::Char(' '), KeyMod::Ctrl));
+        ed.insert_str_after_cursor("he").unwrap();
+
+        map.handle_key(Key::new(KeyCode::Char('\t')), &mut ed, &mut completer)
+            .unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "he\t");
+        map.handle_key(Key::new(KeyCode::Backspace), &mut ed, &mut completer)
+            .unwrap();
+        map.handle_key(
+            Key::new_mod(KeyCode::Char(' '), KeyMod::Ctrl),
+            &mut ed,
+            &mut completer,
+        )
+        .unwrap();
+        assert_eq!(String::from(ed), "hello");
+    }
+
+    #[test]
+    /// end accepts the autosuggestion once it is one of the accept keys
+    fn accept_autosuggestion_keys() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("cargo build").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        let mut completer = BasicCompleter::new(Vec::<String>::new());
+        ed.insert_str_after_cursor("car").unwrap();
+        assert!(ed.is_currently_showing_autosuggestion());
+
+        map.handle_key(Key::new(KeyCode::End), &mut ed, &mut completer)
+            .unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "car");
+
+        let mut keys = ed.accept_autosuggestion_keys().to_vec();
+        keys.push(Key::new(KeyCode::End));
+        ed.set_accept_autosuggestion_keys(keys);
+        map.handle_key(Key::new(KeyCode::End), &mut ed, &mut completer)
+            .unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "cargo build");
+    }
+
+    #[test]
+    /// abbreviations expand when the word is ended, not in the middle of a word
+    fn abbreviations() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Emacs::new();
+        map.set_abbreviation("teh", "the");
+        for c in "teh xteh teh".chars() {
+            map.handle_key(Key::new(KeyCode::Char(c)), &mut ed, &mut EmptyCompleter)
+                .unwrap();
+        }
+        ed.move_cursor_to(2).unwrap();
+        map.handle_key(Key::new(KeyCode::Char('.')), &mut ed, &mut EmptyCompleter)
+            .unwrap();
+        ed.move_cursor_to_end_of_line().unwrap();
+        map.handle_key(Key::new(KeyCode::Char('.')), &mut ed, &mut EmptyCompleter)
+            .unwrap();
+        assert_eq!(ed.current_buffer().to_string(), "th.e xteh the.");
+
+        ed.undo();
+        ed.undo();
+        assert_eq!(ed.current_buffer().to_string(), "th.e xteh teh");
+        assert_eq!(map.remove_abbreviation("teh"), Some("the".to_string()));
+        assert_eq!(map.remove_abbreviation("teh"), None);
+    }
+
+    #[test]
+    /// tab can insert spaces when there is nothing to complete
+    fn tab_inserts_spaces() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = TestKeyMap;
+        let tab = Key::new(KeyCode::Char('\t'));
+
+        map.handle_key(tab, &mut ed, &mut EmptyCompleter).unwrap();
+        assert_eq!(ed.cursor(), 0);
+
+        ed.set_tab_inserts_spaces(Some(4));
+        map.handle_key(tab, &mut ed, &mut EmptyCompleter).unwrap();
+        assert_eq!(ed.cursor(), 4);
+        assert_eq!(String::from(ed), "    ");
+    }
 }
@@ -1,49 +1,18 @@
+use std::collections::HashMap;
 use std::io;
-use std::time::{SystemTime, UNIX_EPOCH};
 use std::{cmp, mem};
 
 use sl_console::event::{Key, KeyCode, KeyMod};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::buffer::Buffer;
+use crate::buffer::{Action, Buffer};
+use crate::cursor;
+pub(crate) use crate::cursor::ViMoveDir;
+pub use crate::cursor::{DefaultViKeywordRule, ViKeywordRule, ViMoveMode};
+use crate::keymap::{literal_text, KeyAction, KeyBindings};
 use crate::Editor;
 use crate::KeyMap;
 
-pub trait ViKeywordRule {
-    /// All alphanumeric characters and _ are considered valid for keywords in vi by default.
-    fn is_vi_keyword(&self, str: &str) -> bool {
-        let mut ret = false;
-        if str == "_" {
-            ret = true
-        } else if !str.trim().is_empty() {
-            for c in str.chars() {
-                if c.is_alphanumeric() {
-                    ret = true;
-                } else {
-                    ret = false;
-                    break;
-                }
-            }
-        }
-        ret
-    }
-}
-
-pub struct DefaultViKeywordRule;
-
-impl ViKeywordRule for DefaultViKeywordRule {}
-
-impl Default for DefaultViKeywordRule {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl DefaultViKeywordRule {
-    pub fn new() -> Self {
-        DefaultViKeywordRule {}
-    }
-}
-
 pub struct AlphanumericAndVariableKeywordRule<'a> {
     treat_as_keyword: Vec<&'a str>,
 }
@@ -164,12 +133,22 @@ enum Mode {
     Insert,
     Normal,
     Replace,
+    /// `R`, typed characters replace the ones under the cursor until escape.
+    Overwrite,
     Delete(usize),
     Yank(usize),
+    /// `>` (right) or `<` (left), shifting the lines moved over from the position.
+    Shift(usize, ViMoveDir),
     TextObject(TextObjectMode),
     MoveToChar(CharMovement),
     G,
     Tilde,
+    /// Collecting a `/` (forward) or `?` pattern to search the buffer for.
+    Search(bool),
+    /// Waiting for the register to record a macro into after `q`.
+    Record,
+    /// Waiting for the register of the macro to replay after `@`.
+    Replay,
 }
 
 #[derive(Debug, Clone)]
@@ -233,6 +212,10 @@ fn is_movement_key(key: Key) -> bool {
             | KeyCode::Char('e')
             | KeyCode::Char('E')
             | KeyCode::Char('g')
+            | KeyCode::Char('/')
+            | KeyCode::Char('?')
+            | KeyCode::Char('n')
+            | KeyCode::Char('N')
             | KeyCode::Backspace
             | KeyCode::Char(' ')
             | KeyCode::Home
@@ -248,45 +231,35 @@ fn is_movement_key(key: Key) -> bool {
     )
 }
 
-#[derive(PartialEq, Clone, Copy)]
-enum ViMoveMode {
-    Keyword,
-    Whitespace,
+/// The position of the first non-blank grapheme in the buffer, or of the last grapheme if they are
+/// all blank (vi's `^`).
+fn first_non_blank(buf: &Buffer) -> usize {
+    buf.range_graphemes_all()
+        .position(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| buf.num_graphemes().saturating_sub(1))
 }
 
-#[derive(PartialEq, Clone, Copy)]
-enum ViMoveDir {
-    Left,
-    Right,
+/// The line `pos` is on, the first line is 0.
+fn line_of(buf: &Buffer, pos: usize) -> usize {
+    buf.range(0, pos).matches('\n').count()
 }
 
-impl ViMoveDir {
-    pub fn advance(self, cursor: &mut usize, max: usize) -> bool {
-        self.move_cursor(cursor, max, self)
-    }
-
-    pub fn go_back(self, cursor: &mut usize, max: usize) -> bool {
-        match self {
-            ViMoveDir::Right => self.move_cursor(cursor, max, ViMoveDir::Left),
-            ViMoveDir::Left => self.move_cursor(cursor, max, ViMoveDir::Right),
-        }
-    }
-
-    fn move_cursor(self, cursor: &mut usize, max: usize, dir: ViMoveDir) -> bool {
-        if dir == ViMoveDir::Right && *cursor == max {
-            return false;
-        }
-
-        if dir == ViMoveDir::Left && *cursor == 0 {
-            return false;
-        }
+/// Where each line of the buffer starts.
+fn line_starts(buf: &Buffer) -> Vec<usize> {
+    let newlines = buf
+        .range_graphemes_all()
+        .enumerate()
+        .filter(|(_, g)| *g == "\n")
+        .map(|(i, _)| i + 1);
+    std::iter::once(0).chain(newlines).collect()
+}
 
-        match dir {
-            ViMoveDir::Right => *cursor += 1,
-            ViMoveDir::Left => *cursor -= 1,
-        };
-        true
-    }
+/// How many blanks the line starting at `start` begins with.
+fn leading_blanks(buf: &Buffer, start: usize) -> usize {
+    buf.range(start, buf.num_graphemes())
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .count()
 }
 
 fn find_char(buf: &Buffer, start: usize, ch: char, count: usize) -> Option<usize> {
@@ -307,6 +280,38 @@ fn find_char(buf: &Buffer, start: usize, ch: char, count: usize) -> Option<usize
     offset
 }
 
+/// Find the count'th occurrence of `pattern` after (or before) `start`, wrapping around the end
+/// (or start) of the buffer.
+fn find_str(
+    buf: &Buffer,
+    start: usize,
+    pattern: &str,
+    forward: bool,
+    count: usize,
+) -> Option<usize> {
+    assert!(count > 0);
+    let graphemes: Vec<&str> = buf.range_graphemes_all().collect();
+    let pattern: Vec<&str> = UnicodeSegmentation::graphemes(pattern, true).collect();
+    let len = graphemes.len();
+    if pattern.is_empty() || pattern.len() > len {
+        return None;
+    }
+    let matches_at = |i: usize| graphemes[i..].starts_with(&pattern);
+    let mut pos = start;
+    for _ in 0..count {
+        pos = (1..=len)
+            .map(|step| {
+                if forward {
+                    (pos + step) % len
+                } else {
+                    (pos + len - step % len) % len
+                }
+            })
+            .find(|i| matches_at(*i))?;
+    }
+    Some(pos)
+}
+
 fn find_char_rev(buf: &Buffer, start: usize, ch: char, count: usize) -> Option<usize> {
     assert!(count > 0);
     let rstart = buf.num_graphemes() - start;
@@ -392,6 +397,14 @@ where
 
 /// Vi keybindings for `Editor`.
 ///
+/// Ctrl with the left and right arrows moves by WORD in both insert and normal mode (like `B` and
+/// `W`), and can be used as the motion for `d`, `c` and `y`. In insert mode ctrl-v inserts the
+/// next key literally, control characters are shown in caret notation (`^[`). `R` replaces
+/// characters until escape, backspace in it puts back the characters that were replaced. `>>` and
+/// `<<` indent and dedent lines by the shift width (see `set_shift_width`), with a count or a
+/// motion like the other operators (`3>>`, `>j`). Ctrl-o in insert mode runs one normal mode
+/// command (`ctrl-o 0`, `ctrl-o dw`) and goes back to inserting, as part of the same insert.
+///
 /// ```
 /// use sl_liner::*;
 /// use sl_liner::keymap;
@@ -419,13 +432,29 @@ pub struct Vi {
     last_count: u32,
     movement_reset: bool,
     last_char_movement: Option<(char, CharMovement)>,
-    esc_sequence: Option<(char, char, u32)>,
-    last_insert_ms: u128,
+    search_input: String,
+    last_search: Option<(String, bool)>,
     keyword_rule: Box<dyn ViKeywordRule>,
     normal_prompt_prefix: Option<String>,
     normal_prompt_suffix: Option<String>,
     insert_prompt_prefix: Option<String>,
     insert_prompt_suffix: Option<String>,
+    bindings: KeyBindings,
+    quoted_insert: bool,
+    macros: HashMap<char, Vec<Key>>,
+    // The register being recorded into and the keys so far.
+    recording: Option<(char, Vec<Key>)>,
+    last_macro: Option<char>,
+    // Registers of the macros being replayed, innermost last.
+    replaying: Vec<char>,
+    // Keys of a macro `@` asked for, replayed by `handle_key` once the `@` command is done.
+    replay: Option<Vec<Key>>,
+    // What each character typed in overwrite mode replaced, None past the end of the line, so
+    // backspace can put it back.
+    overwritten: Vec<Option<String>>,
+    shift_width: usize,
+    // Ctrl-o was typed in insert mode, back to it once the normal mode command is done.
+    normal_once: bool,
 }
 
 impl Default for Vi {
@@ -442,13 +471,23 @@ impl Default for Vi {
             last_count: 0,
             movement_reset: false,
             last_char_movement: None,
-            esc_sequence: None,
-            last_insert_ms: 0,
+            search_input: String::new(),
+            last_search: None,
             keyword_rule: Box::new(DefaultViKeywordRule::new()),
             normal_prompt_prefix: None,
             normal_prompt_suffix: None,
             insert_prompt_prefix: None,
             insert_prompt_suffix: None,
+            bindings: KeyBindings::new(),
+            quoted_insert: false,
+            macros: HashMap::new(),
+            recording: None,
+            last_macro: None,
+            replaying: Vec::new(),
+            replay: None,
+            overwritten: Vec::new(),
+            shift_width: 4,
+            normal_once: false,
         }
     }
 }
@@ -458,6 +497,16 @@ impl Vi {
         Self::default()
     }
 
+    /// The register a macro is being recorded into, if `q` started a recording.
+    pub fn recording_register(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    /// The keys of the macro recorded into `register`.
+    pub fn macro_keys(&self, register: char) -> Option<&[Key]> {
+        self.macros.get(&register).map(|keys| &keys[..])
+    }
+
     pub fn set_normal_prompt_prefix(&mut self, prefix: Option<String>) {
         self.normal_prompt_prefix = prefix;
     }
@@ -474,14 +523,28 @@ impl Vi {
         self.insert_prompt_suffix = suffix;
     }
 
+    /// Typing `key1` then `key2` within `timeout_ms` in insert mode acts as Esc. This is a
+    /// shorthand for binding the sequence with [KeyMap::bind_sequence].
     pub fn set_esc_sequence(&mut self, key1: char, key2: char, timeout_ms: u32) {
-        self.esc_sequence = Some((key1, key2, timeout_ms));
+        let keys: String = [key1, key2].iter().collect();
+        self.bindings
+            .bind_sequence(&keys, KeyAction::Key(Key::new(KeyCode::Esc)), timeout_ms);
     }
 
     pub fn set_keyword_rule(&mut self, keyword_rule: Box<dyn ViKeywordRule>) {
         self.keyword_rule = keyword_rule;
     }
 
+    /// Sets how many spaces `>>` indents a line by and `<<` dedents it by, 4 by default. When
+    /// dedenting a tab counts as a whole shift width.
+    pub fn set_shift_width(&mut self, shift_width: usize) {
+        self.shift_width = shift_width.max(1);
+    }
+
+    pub fn shift_width(&self) -> usize {
+        self.shift_width
+    }
+
     /// Get the current mode.
     fn mode(&self) -> Mode {
         self.mode_stack.mode()
@@ -537,7 +600,7 @@ impl Vi {
         self.mode_stack.push(mode);
         self.set_editor_mode(ed)?;
 
-        if mode == Insert || mode == Tilde {
+        if mode == Insert || mode == Tilde || mode == Overwrite {
             ed.current_buffer_mut().start_undo_group();
         }
         Ok(())
@@ -556,7 +619,7 @@ impl Vi {
             // after popping, if mode is delete or change, pop that too. This is used for movements
             // with sub commands like 't' (MoveToChar) and 'g' (G).
             match self.mode() {
-                Delete(_) | Yank(_) => self.mode_stack.pop(),
+                Delete(_) | Yank(_) | Shift(..) => self.mode_stack.pop(),
                 _ => original_mode,
             }
         };
@@ -564,7 +627,7 @@ impl Vi {
         ed.set_no_eol(self.mode() == Normal);
         self.movement_reset = self.mode() != Mode::Insert;
 
-        if let Delete(_) | Yank(_) = last_mode {
+        if let Delete(_) | Yank(_) | Shift(..) = last_mode {
             // perform the delete operation
             match last_mode {
                 Mode::Delete(start_pos) => match move_type {
@@ -575,6 +638,16 @@ impl Vi {
                     Exclusive => ed.yank_until(start_pos)?,
                     Inclusive => ed.yank_until_inclusive(start_pos)?,
                 },
+                Mode::Shift(start_pos, dir) => {
+                    let start_line = line_of(ed.current_buffer(), start_pos);
+                    let end_line = line_of(ed.current_buffer(), ed.cursor());
+                    self.shift_lines(
+                        ed,
+                        cmp::min(start_line, end_line),
+                        cmp::max(start_line, end_line),
+                        dir,
+                    )?
+                }
                 _ => unreachable!(),
             }
 
@@ -603,7 +676,7 @@ impl Vi {
         ed.set_no_eol(self.mode() == Normal);
         self.movement_reset = self.mode() != Insert;
 
-        if last_mode == Insert || last_mode == Tilde {
+        if last_mode == Insert || last_mode == Tilde || last_mode == Overwrite {
             ed.current_buffer_mut().end_undo_group();
         }
 
@@ -614,11 +687,65 @@ impl Vi {
         }
     }
 
-    /// Return to normal mode.
+    /// Return to normal mode, dropping any pending operator along with its counts and the
+    /// command built so far.
     fn normal_mode_abort<'a>(&mut self, ed: &mut Editor<'a>) -> io::Result<()> {
+        // c pushes insert mode under the operator, close the undo group it opened
+        if self.mode_stack.0.contains(&Mode::Insert) {
+            ed.current_buffer_mut().end_undo_group();
+        }
         self.mode_stack.clear();
         ed.set_no_eol(true);
         self.count = 0;
+        self.secondary_count = 0;
+        self.current_command.clear();
+        self.current_insert = None;
+        self.set_editor_mode(ed)
+    }
+
+    fn handle_key_by_mode<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        match self.mode() {
+            Mode::Normal => self.handle_key_normal(key, ed),
+            Mode::Insert => self.handle_key_insert(key, ed),
+            Mode::Replace => self.handle_key_replace(key, ed),
+            Mode::Overwrite => self.handle_key_overwrite(key, ed),
+            Mode::Delete(_) | Mode::Yank(_) => self.handle_key_delete_change_yank(key, ed),
+            Mode::Shift(_, dir) => self.handle_key_shift(key, dir, ed),
+            Mode::MoveToChar(movement) => self.handle_key_move_to_char(key, movement, ed),
+            Mode::G => self.handle_key_g(key, ed),
+            Mode::TextObject(prev) => self.handle_key_text_object(key, prev, ed),
+            Mode::Search(forward) => self.handle_key_search(key, forward, ed),
+            Mode::Record => self.handle_key_record(key, ed),
+            Mode::Replay => self.handle_key_replay(key, ed),
+            Mode::Tilde => unreachable!(),
+        }
+    }
+
+    /// After a key of the command typed after ctrl-o in insert mode, goes back to insert mode
+    /// if the command is done.
+    fn end_normal_once<'a>(&mut self, ed: &mut Editor<'a>) -> io::Result<()> {
+        let stack = &mut self.mode_stack.0;
+        match stack.last() {
+            // escape or an invalid key dropped insert mode too
+            None => self.set_mode_preserve_last(Mode::Insert, ed)?,
+            // anything but a count finishes a plain command
+            Some(Mode::Normal) if self.count == 0 && self.secondary_count == 0 => {
+                stack.pop();
+            }
+            // a movement popped normal mode already, or the command started an insert of its own
+            // (A, cw), carry on with the first one then
+            Some(Mode::Insert) => {
+                if stack.len() > 2 && stack[stack.len() - 2] == Mode::Normal {
+                    stack.truncate(stack.len() - 2);
+                    ed.current_buffer_mut().end_undo_group();
+                }
+            }
+            // the command is not complete yet
+            _ => return Ok(()),
+        }
+        self.normal_once = false;
+        ed.set_no_eol(false);
+        self.movement_reset = false;
         self.set_editor_mode(ed)
     }
 
@@ -693,6 +820,15 @@ impl Vi {
     }
 
     fn handle_key_insert<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        if self.quoted_insert {
+            self.quoted_insert = false;
+            self.last_command.push(key);
+            return match literal_text(key) {
+                Some(text) => ed.insert_str_after_cursor(&text),
+                None => Ok(()),
+            };
+        }
+
         match (key.code, key.mods) {
             (KeyCode::Esc, None) | (KeyCode::Char('['), Some(KeyMod::Ctrl)) => {
                 // perform any repeats
@@ -711,11 +847,6 @@ impl Vi {
                 self.pop_mode(ed)
             }
             (KeyCode::Char(c), None) => {
-                let in_ms = if let Ok(duration) = SystemTime::now().duration_since(UNIX_EPOCH) {
-                    duration.as_millis()
-                } else {
-                    0
-                };
                 if self.movement_reset {
                     ed.current_buffer_mut().end_undo_group();
                     ed.current_buffer_mut().start_undo_group();
@@ -724,35 +855,15 @@ impl Vi {
                     // vim behaves as if this was 'i'
                     self.last_insert = Some(Key::new(KeyCode::Char('i')));
                 }
-                let mut esc = false;
-                if let Some((s1, s2, ms)) = self.esc_sequence {
-                    if let Some(Key {
-                        code: KeyCode::Char(last_c),
-                        mods: None,
-                    }) = self.last_command.last()
-                    {
-                        if *last_c == s1
-                            && c == s2
-                            && (in_ms > 0)
-                            && (in_ms - self.last_insert_ms) < ms as u128
-                        {
-                            esc = true;
-                        }
-                    }
-                }
-                self.last_insert_ms = in_ms;
-                if esc {
-                    ed.move_cursor_left(1)?;
-                    let pos = ed.cursor() + self.move_count_right(ed);
-                    ed.delete_until_silent(pos)?;
-                    self.handle_key_insert(Key::new(KeyCode::Esc), ed)
-                } else {
-                    self.last_command.push(key);
-                    ed.insert_after_cursor(c)
-                }
+                self.last_command.push(key);
+                ed.insert_after_cursor(c)
             }
-            // delete and backspace need to be included in the command buffer
-            (KeyCode::Backspace, None) | (KeyCode::Delete, None) => {
+            // deletions and quoted inserts need to be included in the command buffer
+            (KeyCode::Backspace, None)
+            | (KeyCode::Delete, None)
+            | (KeyCode::Char('w'), Some(KeyMod::Ctrl))
+            | (KeyCode::Char('u'), Some(KeyMod::Ctrl))
+            | (KeyCode::Char('v'), Some(KeyMod::Ctrl)) => {
                 if self.movement_reset {
                     ed.current_buffer_mut().end_undo_group();
                     ed.current_buffer_mut().start_undo_group();
@@ -762,7 +873,24 @@ impl Vi {
                     self.last_insert = Some(Key::new(KeyCode::Char('i')));
                 }
                 self.last_command.push(key);
-                self.handle_key_common(key, ed)
+                match key.code {
+                    // delete the word before the cursor
+                    KeyCode::Char('w') => ed.kill_word_before_cursor(),
+                    // delete back to the start of the line
+                    KeyCode::Char('u') => ed.delete_line_before_cursor(),
+                    // insert the next key as is
+                    KeyCode::Char('v') => {
+                        self.quoted_insert = true;
+                        Ok(())
+                    }
+                    _ => self.handle_key_common(key, ed),
+                }
+            }
+            // run one normal mode command, the insert carries on after it
+            (KeyCode::Char('o'), Some(KeyMod::Ctrl)) => {
+                self.normal_once = true;
+                self.mode_stack.push(Mode::Normal);
+                Ok(())
             }
             // if this is a movement while in insert mode, reset the repeat count
             (KeyCode::Left, None)
@@ -773,6 +901,23 @@ impl Vi {
                 self.movement_reset = true;
                 self.handle_key_common(key, ed)
             }
+            // accept the next word of the autosuggestion
+            (KeyCode::Right, Some(KeyMod::Ctrl))
+                if ed.is_currently_showing_autosuggestion() && ed.is_cursor_at_end_of_line() =>
+            {
+                self.count = 0;
+                self.movement_reset = true;
+                ed.accept_autosuggestion_word()
+            }
+            (KeyCode::Left, Some(KeyMod::Ctrl)) | (KeyCode::Right, Some(KeyMod::Ctrl)) => {
+                self.count = 0;
+                self.movement_reset = true;
+                if key.code == KeyCode::Left {
+                    self.move_word_ws_back(ed, 1)
+                } else {
+                    self.move_word_ws(ed, 1)
+                }
+            }
             // up and down require even more special handling
             (KeyCode::Up, None) => {
                 self.count = 0;
@@ -815,6 +960,16 @@ impl Vi {
         match key.mods {
             Some(KeyMod::Ctrl) => match key.code {
                 KeyCode::Char('r') => self.handle_redo(ed),
+                KeyCode::Left => {
+                    let count = self.move_count();
+                    self.move_word_ws_back(ed, count)?;
+                    self.pop_mode_after_movement(Exclusive, ed)
+                }
+                KeyCode::Right => {
+                    let count = self.move_count();
+                    self.move_word_ws(ed, count)?;
+                    self.pop_mode_after_movement(Exclusive, ed)
+                }
                 _ => Ok(()),
             },
             None => {
@@ -852,6 +1007,14 @@ impl Vi {
                         Ok(())
                     }
                     KeyCode::Char('r') => self.set_mode(Mode::Replace, ed),
+                    KeyCode::Char('R') => {
+                        self.last_insert = Some(key);
+                        self.last_command.clear();
+                        self.last_count = 0;
+                        self.count = 0;
+                        self.overwritten.clear();
+                        self.set_mode(Mode::Overwrite, ed)
+                    }
                     KeyCode::Char('d') | KeyCode::Char('c') | KeyCode::Char('y') => {
                         self.current_command.clear();
 
@@ -876,6 +1039,20 @@ impl Vi {
                         self.count = 0;
                         Ok(())
                     }
+                    KeyCode::Char('>') | KeyCode::Char('<') => {
+                        self.current_command.clear();
+                        self.current_command.push(key);
+                        self.current_insert = None;
+                        let dir = if key.code == KeyCode::Char('>') {
+                            ViMoveDir::Right
+                        } else {
+                            ViMoveDir::Left
+                        };
+                        self.set_mode(Mode::Shift(ed.cursor(), dir), ed)?;
+                        self.secondary_count = self.count;
+                        self.count = 0;
+                        Ok(())
+                    }
                     KeyCode::Char('D') => {
                         // update the last command state
                         self.last_insert = None;
@@ -886,6 +1063,15 @@ impl Vi {
 
                         ed.delete_all_after_cursor()
                     }
+                    KeyCode::Char('Y') => {
+                        // yank the whole line, the cursor stays put
+                        self.count = 0;
+                        let buf = ed.current_buffer_mut();
+                        let len = buf.num_graphemes();
+                        buf.yank(0, len);
+                        buf.set_register_linewise(true);
+                        Ok(())
+                    }
                     KeyCode::Char('C') => {
                         // update the last command state
                         self.last_insert = None;
@@ -911,12 +1097,20 @@ impl Vi {
                     }
                     KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => {
                         let count = self.move_count_left(ed);
+                        let cursor = ed.cursor();
                         ed.move_cursor_left(count)?;
+                        if ed.cursor() == cursor {
+                            ed.ring_bell()?;
+                        }
                         self.pop_mode_after_movement(Exclusive, ed)
                     }
                     KeyCode::Char('l') | KeyCode::Right | KeyCode::Char(' ') => {
                         let count = self.move_count_right(ed);
+                        let cursor = ed.cursor();
                         ed.move_cursor_right(count)?;
+                        if ed.cursor() == cursor {
+                            ed.ring_bell()?;
+                        }
                         self.pop_mode_after_movement(Exclusive, ed)
                     }
                     KeyCode::Char('k') | KeyCode::Up => {
@@ -964,6 +1158,61 @@ impl Vi {
                         self.pop_mode_after_movement(Exclusive, ed)
                     }
                     KeyCode::Char('g') => self.set_mode(Mode::G, ed),
+                    KeyCode::Char('q') if self.mode() == Normal => {
+                        self.count = 0;
+                        match self.recording.take() {
+                            Some((register, mut keys)) => {
+                                // the q that stops the recording is not part of the macro
+                                keys.pop();
+                                self.macros.insert(register, keys);
+                                Ok(())
+                            }
+                            None => self.set_mode(Record, ed),
+                        }
+                    }
+                    KeyCode::Char('@') if self.mode() == Normal => self.set_mode(Replay, ed),
+                    // search history for the word under the cursor, * for newer entries
+                    KeyCode::Char('*') | KeyCode::Char('#') if self.mode() == Normal => {
+                        self.count = 0;
+                        let word = ed.word_under_cursor().map(|(_, _, word)| word.to_string());
+                        match word {
+                            Some(word) => {
+                                let forward = key.code == KeyCode::Char('*');
+                                if ed.search_history_for(&word, forward)? {
+                                    Ok(())
+                                } else {
+                                    ed.ring_bell()
+                                }
+                            }
+                            None => Ok(()),
+                        }
+                    }
+                    KeyCode::Char('/') | KeyCode::Char('?') => {
+                        self.search_input.clear();
+                        self.set_mode(Search(key.code == KeyCode::Char('/')), ed)
+                    }
+                    // repeat whichever search was done last, of history or of the buffer
+                    KeyCode::Char('n') | KeyCode::Char('N') => {
+                        let same_way = key.code == KeyCode::Char('n');
+                        let history_search = match ed.last_history_search() {
+                            Some((_, forward)) if self.mode() == Mode::Normal => Some(forward),
+                            _ => None,
+                        };
+                        if let Some(forward) = history_search {
+                            self.count = 0;
+                            return if ed.search_history_again(forward == same_way)? {
+                                Ok(())
+                            } else {
+                                self.normal_mode_abort(ed)
+                            };
+                        }
+                        match self.last_search.clone() {
+                            Some((pattern, forward)) => {
+                                self.search_buffer(&pattern, forward == same_way, ed)
+                            }
+                            None => self.normal_mode_abort(ed),
+                        }
+                    }
                     // if count is 0, 0 should move to start of line
                     KeyCode::Char('0') if self.count == 0 => {
                         ed.move_cursor_to_start_of_line()?;
@@ -977,7 +1226,8 @@ impl Vi {
                         Ok(())
                     }
                     KeyCode::Char('^') => {
-                        ed.move_cursor_to_start_of_line()?;
+                        let pos = first_non_blank(ed.current_buffer());
+                        ed.move_cursor_to(pos)?;
                         self.pop_mode_after_movement(Exclusive, ed)
                     }
                     KeyCode::Char('$') => {
@@ -1007,6 +1257,8 @@ impl Vi {
                         for _ in 0..self.move_count_right(ed) {
                             ed.flip_case()?;
                         }
+                        // the count is used up, `.` gets it from last_count
+                        self.count = 0;
                         self.pop_mode(ed)?;
                         Ok(())
                     }
@@ -1074,6 +1326,50 @@ impl Vi {
         Ok(())
     }
 
+    fn handle_key_overwrite<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        match (key.code, key.mods) {
+            (KeyCode::Esc, None) | (KeyCode::Char('['), Some(KeyMod::Ctrl)) => {
+                self.overwritten.clear();
+                ed.move_cursor_left(1)?;
+                self.pop_mode(ed)
+            }
+            (KeyCode::Char(c), None) => {
+                self.last_command.push(key);
+                let original = ed
+                    .current_buffer()
+                    .grapheme_after(ed.cursor())
+                    .map(String::from);
+                if original.is_some() {
+                    ed.delete_after_cursor()?;
+                }
+                self.overwritten.push(original);
+                ed.insert_after_cursor(c)
+            }
+            // put back what the last typed character replaced, before that just move left
+            (KeyCode::Backspace, None) => {
+                self.last_command.push(key);
+                ed.move_cursor_left(1)?;
+                match self.overwritten.pop() {
+                    Some(original) => {
+                        ed.delete_after_cursor()?;
+                        if let Some(original) = original {
+                            ed.insert_str_after_cursor(&original)?;
+                            ed.move_cursor_left(1)?;
+                        }
+                        Ok(())
+                    }
+                    None => Ok(()),
+                }
+            }
+            // anything else may move the cursor or change the line, what was typed so far can
+            // no longer be taken back
+            _ => {
+                self.overwritten.clear();
+                self.handle_key_common(key, ed)
+            }
+        }
+    }
+
     fn set_count(&mut self) {
         // set count
         self.count = match (self.count, self.secondary_count) {
@@ -1099,16 +1395,46 @@ impl Vi {
         ) {
             // check if this is a movement key
             (key, Some(text_object), _) if key.mods == None => {
+                // current_insert is kept so a change (c) with a text object ends in insert mode
+                // and repeats as a change
                 self.current_command.push(key);
-                self.current_insert = None;
                 self.set_mode(Mode::TextObject(text_object), ed)?;
                 Ok(())
             }
+            // in vim, cw and cW on a non-blank act like ce and cE and leave trailing whitespace
+            (
+                Key {
+                    code: KeyCode::Char(c @ 'w') | KeyCode::Char(c @ 'W'),
+                    mods: None,
+                },
+                _,
+                Some(Key {
+                    code: KeyCode::Char('c'),
+                    mods: None,
+                }),
+            ) if matches!(ed.current_buffer().grapheme_after(ed.cursor()), Some(g) if !g.trim().is_empty()) =>
+            {
+                self.set_count();
+                self.current_command.push(key);
+                let move_mode = if c == 'w' {
+                    ViMoveMode::Keyword
+                } else {
+                    ViMoveMode::Whitespace
+                };
+                self.change_word(ed, move_mode)
+            }
             (key, _, _)
                 if is_movement_key(key)
                     | (key.code == KeyCode::Char('0') && key.mods == None && self.count == 0) =>
             {
-                self.set_count();
+                match key.code {
+                    // 0 and ^ are absolute, any count given before the operator is dropped (a
+                    // count carried over to handle_key_normal would also turn 0 into a digit)
+                    KeyCode::Char('0') | KeyCode::Char('^') | KeyCode::Home => {
+                        self.secondary_count = 0
+                    }
+                    _ => self.set_count(),
+                }
 
                 // update the last command state
                 self.current_command.push(key);
@@ -1171,11 +1497,16 @@ impl Vi {
                 self.count = 0;
                 self.secondary_count = 0;
                 ed.move_cursor_to_start_of_line()?;
+                let had_text = !ed.current_buffer().is_empty();
                 if key.code == KeyCode::Char('y') {
                     ed.yank_all_after_cursor()?;
                 } else {
                     ed.delete_all_after_cursor()?;
                 }
+                if had_text || key.code == KeyCode::Char('y') {
+                    // paste the line back as a line of its own, like vim
+                    ed.current_buffer_mut().set_register_linewise(true);
+                }
 
                 // return to the previous mode
                 self.pop_mode(ed)
@@ -1185,6 +1516,121 @@ impl Vi {
         }
     }
 
+    fn handle_key_shift<'a>(
+        &mut self,
+        key: Key,
+        dir: ViMoveDir,
+        ed: &mut Editor<'a>,
+    ) -> io::Result<()> {
+        let operator = match dir {
+            ViMoveDir::Right => '>',
+            ViMoveDir::Left => '<',
+        };
+        match (key.code, key.mods) {
+            // >> and << shift count lines, >j and <k the lines count up or down as well
+            (KeyCode::Char(c), None) if c == operator => self.shift_count_lines(key, 0, dir, ed),
+            (KeyCode::Char('j'), None) | (KeyCode::Down, None) => {
+                self.shift_count_lines(key, 1, dir, ed)
+            }
+            (KeyCode::Char('k'), None) | (KeyCode::Up, None) => {
+                self.shift_count_lines(key, -1, dir, ed)
+            }
+            _ if is_movement_key(key)
+                | (key.code == KeyCode::Char('0') && key.mods.is_none() && self.count == 0) =>
+            {
+                match key.code {
+                    KeyCode::Char('0') | KeyCode::Char('^') | KeyCode::Home => {
+                        self.secondary_count = 0
+                    }
+                    _ => self.set_count(),
+                }
+                self.current_command.push(key);
+                // the shift is done once the movement pops the mode
+                self.handle_key_normal(key, ed)
+            }
+            (KeyCode::Char('0'..='9'), None) => self.handle_key_normal(key, ed),
+            _ => self.normal_mode_abort(ed),
+        }
+    }
+
+    /// Shifts the cursor's line and the count lines after it (`dir_lines` 1), before it (-1),
+    /// or for `>>` the count lines starting with it (0).
+    fn shift_count_lines<'a>(
+        &mut self,
+        key: Key,
+        dir_lines: isize,
+        dir: ViMoveDir,
+        ed: &mut Editor<'a>,
+    ) -> io::Result<()> {
+        self.set_count();
+        self.current_command.push(key);
+        let count = self.move_count();
+        let line = line_of(ed.current_buffer(), ed.cursor());
+        let (first, last) = match dir_lines {
+            1 => (line, line + count),
+            -1 => (line.saturating_sub(count), line),
+            _ => (line, line + count - 1),
+        };
+        self.pop_mode(ed)?;
+        self.shift_lines(ed, first, last, dir)?;
+
+        // update the last state
+        mem::swap(&mut self.last_command, &mut self.current_command);
+        self.last_insert = None;
+        self.last_count = self.count;
+        self.count = 0;
+        self.secondary_count = 0;
+        Ok(())
+    }
+
+    /// Indents (right) or dedents (left) lines `first` through `last` by the shift width in one
+    /// undo group, leaving the cursor on the first non-blank of the first line. Empty lines are
+    /// not indented and a line with less indentation than the shift width loses all of it.
+    fn shift_lines<'a>(
+        &self,
+        ed: &mut Editor<'a>,
+        first: usize,
+        last: usize,
+        dir: ViMoveDir,
+    ) -> io::Result<()> {
+        let buf = ed.current_buffer_mut();
+        let starts = line_starts(buf);
+        let last = cmp::min(last, starts.len() - 1);
+        buf.start_undo_group();
+        // from the last line up, so the starts of the lines before stay where they are
+        for &start in starts[first..=last].iter().rev() {
+            match dir {
+                ViMoveDir::Right => match buf.grapheme_after(start) {
+                    None | Some("\n") => {}
+                    Some(_) => buf.insert_action(Action::Insert {
+                        start,
+                        text: " ".repeat(self.shift_width),
+                    }),
+                },
+                ViMoveDir::Left => {
+                    let mut width = 0;
+                    let blanks = buf
+                        .range(start, buf.num_graphemes())
+                        .chars()
+                        .take_while(|c| {
+                            let fits = width < self.shift_width && (*c == ' ' || *c == '\t');
+                            width += if *c == '\t' { self.shift_width } else { 1 };
+                            fits
+                        })
+                        .count();
+                    if blanks > 0 {
+                        let text = buf.range(start, start + blanks).to_owned();
+                        buf.insert_action(Action::Remove { start, text });
+                    }
+                }
+            }
+        }
+        buf.end_undo_group();
+        let start = starts[first];
+        let pos = start + leading_blanks(ed.current_buffer(), start);
+        ed.move_cursor_to(pos)
+    }
+
     fn handle_key_move_to_char<'a>(
         &mut self,
         key: Key,
@@ -1197,6 +1643,10 @@ impl Vi {
         let count = self.move_count();
         self.count = 0;
 
+        // a repeated t or T skips over a match right next to the cursor, otherwise it would never
+        // move past it (and d; or d, would have nothing to work with)
+        let skip = matches!(movement, Repeat | ReverseRepeat) as usize;
+
         let (key_code, movement) = match (key, movement, self.last_char_movement) {
             // repeat the last movement
             (_, Repeat, Some((c, last_movement))) => (KeyCode::Char(c), last_movement),
@@ -1236,7 +1686,7 @@ impl Vi {
                 match movement {
                     RightUntil => {
                         move_type = Inclusive;
-                        match find_char(ed.current_buffer(), ed.cursor() + 1, c, count) {
+                        match find_char(ed.current_buffer(), ed.cursor() + 1 + skip, c, count) {
                             Some(i) => {
                                 let prev_mode = self.mode_stack.pop();
                                 return_to_pos = match self.mode() {
@@ -1272,7 +1722,8 @@ impl Vi {
                     }
                     LeftUntil => {
                         move_type = Exclusive;
-                        match find_char_rev(ed.current_buffer(), ed.cursor(), c, count) {
+                        let start = ed.cursor().saturating_sub(skip);
+                        match find_char_rev(ed.current_buffer(), start, c, count) {
                             Some(i) => ed.move_cursor_to(i + 1),
                             None => Ok(()),
                         }
@@ -1300,17 +1751,71 @@ impl Vi {
         }
     }
 
-    fn handle_key_g<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
-        use self::MoveType::*;
-
-        let count = self.move_count();
+    fn handle_key_search<'a>(
+        &mut self,
+        key: Key,
+        forward: bool,
+        ed: &mut Editor<'a>,
+    ) -> io::Result<()> {
         self.current_command.push(key);
-
-        let res = match key.code {
-            KeyCode::Char('e') => {
-                self.move_to_end_of_word_back(ed, count)?;
-                self.pop_mode_after_movement(Inclusive, ed)
-            }
+        match (key.code, key.mods) {
+            (KeyCode::Char('\n'), None) => {
+                // an empty pattern repeats the last search
+                let pattern = mem::take(&mut self.search_input);
+                if !pattern.is_empty() {
+                    self.last_search = Some((pattern, forward));
+                }
+                ed.forget_history_search();
+                match self.last_search.clone() {
+                    Some((pattern, _)) => self.search_buffer(&pattern, forward, ed),
+                    None => self.normal_mode_abort(ed),
+                }
+            }
+            (KeyCode::Backspace, None) => match self.search_input.pop() {
+                Some(_) => Ok(()),
+                None => self.normal_mode_abort(ed),
+            },
+            (KeyCode::Char(c), None) => {
+                self.search_input.push(c);
+                Ok(())
+            }
+            _ => {
+                self.search_input.clear();
+                self.normal_mode_abort(ed)
+            }
+        }
+    }
+
+    /// Move to the next occurrence of `pattern` in the buffer, completing any pending delete or
+    /// yank up to it.
+    fn search_buffer<'a>(
+        &mut self,
+        pattern: &str,
+        forward: bool,
+        ed: &mut Editor<'a>,
+    ) -> io::Result<()> {
+        let count = self.move_count();
+        self.count = 0;
+        match find_str(ed.current_buffer(), ed.cursor(), pattern, forward, count) {
+            Some(i) => {
+                ed.move_cursor_to(i)?;
+                self.pop_mode_after_movement(MoveType::Exclusive, ed)
+            }
+            None => self.normal_mode_abort(ed),
+        }
+    }
+
+    fn handle_key_g<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        use self::MoveType::*;
+
+        let count = self.move_count();
+        self.current_command.push(key);
+
+        let res = match key.code {
+            KeyCode::Char('e') => {
+                self.move_to_end_of_word_back(ed, count)?;
+                self.pop_mode_after_movement(Inclusive, ed)
+            }
             KeyCode::Char('E') => {
                 self.move_to_end_of_word_ws_back(ed, count)?;
                 self.pop_mode_after_movement(Inclusive, ed)
@@ -1324,6 +1829,43 @@ impl Vi {
         res
     }
 
+    fn handle_key_record<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        match (key.code, key.mods) {
+            (KeyCode::Char(register), None) if register.is_ascii_alphanumeric() => {
+                self.recording = Some((register, Vec::new()));
+                self.pop_mode(ed)
+            }
+            _ => self.normal_mode_abort(ed),
+        }
+    }
+
+    /// Replays a macro `count` times by handing its keys to `handle_key` to go through like typed
+    /// ones. `@@` replays the last macro again. A macro that (indirectly) replays itself stops
+    /// there.
+    fn handle_key_replay<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        let count = self.move_count();
+        self.count = 0;
+        let register = match (key.code, key.mods) {
+            (KeyCode::Char('@'), None) => self.last_macro,
+            (KeyCode::Char(register), None) => Some(register),
+            _ => None,
+        };
+        let register = match register {
+            Some(register) if !self.replaying.contains(&register) => register,
+            _ => return self.normal_mode_abort(ed),
+        };
+        let keys = match self.macros.get(&register) {
+            Some(keys) => keys.clone(),
+            None => return self.normal_mode_abort(ed),
+        };
+        self.last_macro = Some(register);
+        self.pop_mode(ed)?;
+
+        self.replaying.push(register);
+        self.replay = Some(keys.repeat(count));
+        Ok(())
+    }
+
     fn reset_curor_pos_for_command_mode(&mut self, pos: usize) -> Option<Mode> {
         match self.mode_stack.pop() {
             Mode::Delete(_) => {
@@ -1348,6 +1890,7 @@ impl Vi {
         self.pop_mode(ed)?;
 
         self.set_count();
+        self.current_command.push(key);
 
         match (TextObjectMovement::from_key_code(key.code), key.mods) {
             (Some(movement), None) => match movement {
@@ -1514,84 +2057,15 @@ impl Vi {
         count: usize,
         ws_included_in_count: bool,
     ) -> io::Result<()> {
-        #[derive(Clone, Copy)]
-        enum State {
-            Whitespace,
-            Keyword,
-            NonKeyword,
-        }
-
-        let mut cursor = ed.cursor();
-        'repeat: for _ in 0..count {
-            let buf = ed.current_buffer();
-            let mut state = match buf.grapheme_after(cursor) {
-                None => break,
-                Some(str) => match str {
-                    str if str.trim().is_empty() => State::Whitespace,
-                    str if self.keyword_rule.is_vi_keyword(str) => State::Keyword,
-                    _ => State::NonKeyword,
-                },
-            };
-
-            while direction.advance(&mut cursor, buf.num_graphemes()) {
-                let str = match buf.grapheme_after(cursor) {
-                    Some(str) => str,
-                    _ => break 'repeat,
-                };
-
-                // if ws_included_in_count is true we want to make sure we treat
-                // any contiguous string of whitespace appropriately towards
-                // the overall count, this means that at (NonKeyWord and Keyword)
-                // to Whitespace boundaries we need to break so the count loop
-                // increments one more time. The default behavior just cycles
-                // through Whitespace.
-                match state {
-                    State::Whitespace => match str {
-                        str if str.trim().is_empty() => {}
-                        _ => {
-                            break;
-                        }
-                    },
-                    State::Keyword => match str {
-                        str if str.trim().is_empty() => {
-                            if ws_included_in_count {
-                                break;
-                            } else {
-                                state = State::Whitespace
-                            }
-                        }
-                        str if move_mode == ViMoveMode::Keyword
-                            && !self.keyword_rule.is_vi_keyword(str) =>
-                        {
-                            break
-                        }
-                        _ => {}
-                    },
-                    State::NonKeyword => match str {
-                        str if str.trim().is_empty() => {
-                            if ws_included_in_count {
-                                break;
-                            } else {
-                                state = State::Whitespace
-                            }
-                        }
-                        str if move_mode == ViMoveMode::Keyword
-                            && self.keyword_rule.is_vi_keyword(str) =>
-                        {
-                            break
-                        }
-                        _ => {}
-                    },
-                }
-            }
-        }
-
-        // default positioning of cursor when moving in this manner ends one
-        // position to the left of the desired positioning in vi when moving
-        // and treating whitespace as words for the purposed of text objects.
-        if ws_included_in_count && count > 0 {
-            cursor -= 1;
-        }
+        let cursor = cursor::skip_words(
+            ed.current_buffer(),
+            ed.cursor(),
+            count,
+            move_mode,
+            &*self.keyword_rule,
+            direction,
+            ws_included_in_count,
+        );
         ed.move_cursor_to(cursor)
     }
 
@@ -1603,6 +2077,31 @@ impl Vi {
         self.vi_move_word_end(ed, ViMoveMode::Whitespace, ViMoveDir::Right, count)
     }
 
+    /// Handle the word motion of `cw`/`cW` when the cursor is on a non-blank. The change runs to
+    /// the end of the word, which is just the cursor if it is already on the last character.
+    fn change_word(&mut self, ed: &mut Editor, move_mode: ViMoveMode) -> io::Result<()> {
+        let mut count = self.move_count();
+        let buf = ed.current_buffer();
+        let at_end = match (
+            buf.grapheme_after(ed.cursor()),
+            buf.grapheme_after(ed.cursor() + 1),
+        ) {
+            (_, None) => true,
+            (_, Some(next)) if next.trim().is_empty() => true,
+            (Some(curr), Some(next)) => {
+                move_mode == ViMoveMode::Keyword
+                    && self.keyword_rule.is_vi_keyword(curr)
+                        != self.keyword_rule.is_vi_keyword(next)
+            }
+            (None, Some(_)) => unreachable!(),
+        };
+        if at_end {
+            count -= 1;
+        }
+        self.vi_move_word_end(ed, move_mode, ViMoveDir::Right, count)?;
+        self.pop_mode_after_movement(MoveType::Inclusive, ed)
+    }
+
     fn move_word_back(&self, ed: &mut Editor, count: usize) -> io::Result<()> {
         self.vi_move_word_end(ed, ViMoveMode::Keyword, ViMoveDir::Left, count)
     }
@@ -1618,77 +2117,54 @@ impl Vi {
         direction: ViMoveDir,
         count: usize,
     ) -> io::Result<()> {
-        enum State {
-            Whitespace,
-            EndOnWord,
-            EndOnOther,
-            EndOnWhitespace,
-        }
+        let cursor = cursor::to_word_edge(
+            ed.current_buffer(),
+            ed.cursor(),
+            count,
+            move_mode,
+            &*self.keyword_rule,
+            direction,
+        );
+        ed.move_cursor_to(cursor)
+    }
+}
 
-        let mut cursor = ed.cursor();
-        'repeat: for _ in 0..count {
-            let buf = ed.current_buffer();
-            let mut state = State::Whitespace;
+impl KeyMap for Vi {
+    fn resume<'a>(&mut self, ed: &mut Editor<'a>) -> io::Result<()> {
+        ed.set_no_eol(self.mode() == Mode::Normal);
+        self.set_editor_mode(ed)
+    }
 
-            while direction.advance(&mut cursor, buf.num_graphemes()) {
-                let str = match buf.grapheme_after(cursor) {
-                    Some(c) => c,
-                    _ => break 'repeat,
-                };
+    fn handle_key_core<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
+        if self.normal_once && self.replaying.is_empty() {
+            self.handle_key_by_mode(key, ed)?;
+            if !self.replaying.is_empty() {
+                // the command is a macro, back to insert mode after it
+                return Ok(());
+            }
+            return self.end_normal_once(ed);
+        }
+        self.handle_key_by_mode(key, ed)
+    }
 
-                match state {
-                    State::Whitespace => match str {
-                        // skip initial whitespace
-                        str if str.trim().is_empty() => {}
-                        // if we are in keyword mode and found a keyword, stop on word
-                        str if move_mode == ViMoveMode::Keyword
-                            && self.keyword_rule.is_vi_keyword(str) =>
-                        {
-                            state = State::EndOnWord;
-                        }
-                        // not in keyword mode, stop on whitespace
-                        _ if move_mode == ViMoveMode::Whitespace => {
-                            state = State::EndOnWhitespace;
-                        }
-                        // in keyword mode, found non-whitespace non-keyword, stop on anything
-                        _ => {
-                            state = State::EndOnOther;
-                        }
-                    },
-                    State::EndOnWord if !self.keyword_rule.is_vi_keyword(str) => {
-                        direction.go_back(&mut cursor, buf.num_graphemes());
-                        break;
-                    }
-                    State::EndOnWhitespace if str.trim().is_empty() => {
-                        direction.go_back(&mut cursor, buf.num_graphemes());
-                        break;
-                    }
-                    State::EndOnOther
-                        if str.trim().is_empty() || self.keyword_rule.is_vi_keyword(str) =>
-                    {
-                        direction.go_back(&mut cursor, buf.num_graphemes());
-                        break;
-                    }
-                    _ => {}
-                }
+    fn record_key(&mut self, key: Key) {
+        if let Some((_, keys)) = &mut self.recording {
+            if self.replaying.is_empty() {
+                keys.push(key);
             }
         }
+    }
 
-        ed.move_cursor_to(cursor)
+    fn take_replay(&mut self) -> Option<Vec<Key>> {
+        self.replay.take()
     }
-}
 
-impl KeyMap for Vi {
-    fn handle_key_core<'a>(&mut self, key: Key, ed: &mut Editor<'a>) -> io::Result<()> {
-        match self.mode() {
-            Mode::Normal => self.handle_key_normal(key, ed),
-            Mode::Insert => self.handle_key_insert(key, ed),
-            Mode::Replace => self.handle_key_replace(key, ed),
-            Mode::Delete(_) | Mode::Yank(_) => self.handle_key_delete_change_yank(key, ed),
-            Mode::MoveToChar(movement) => self.handle_key_move_to_char(key, movement, ed),
-            Mode::G => self.handle_key_g(key, ed),
-            Mode::TextObject(prev) => self.handle_key_text_object(key, prev, ed),
-            Mode::Tilde => unreachable!(),
+    fn replay_done<'a>(&mut self, ed: &mut Editor<'a>) -> io::Result<()> {
+        self.replaying.pop();
+        if self.normal_once && self.replaying.is_empty() {
+            self.end_normal_once(ed)
+        } else {
+            Ok(())
         }
     }
 
@@ -1705,16 +2181,56 @@ impl KeyMap for Vi {
         self.last_count = 0;
         self.movement_reset = false;
         self.last_char_movement = None;
+        self.quoted_insert = false;
+        // recorded macros are kept for the next line, a recording in progress is dropped
+        self.recording = None;
+        self.replaying.clear();
+        self.replay = None;
+        self.overwritten.clear();
+        self.normal_once = false;
+        self.bindings.reset_pending();
         // since we start in insert mode, we need to start an undo group
         ed.current_buffer_mut().start_undo_group();
         let _ = self.set_editor_mode(ed);
     }
+
+    fn bindings(&self) -> Option<&KeyBindings> {
+        Some(&self.bindings)
+    }
+
+    fn bindings_mut(&mut self) -> Option<&mut KeyBindings> {
+        Some(&mut self.bindings)
+    }
+
+    fn is_inserting(&self) -> bool {
+        self.mode() == Mode::Insert
+    }
+
+    fn is_capturing_input(&self) -> bool {
+        self.quoted_insert || matches!(self.mode(), Mode::Search(_))
+    }
+
+    fn retract_input<'a>(&mut self, count: usize, ed: &mut Editor<'a>) -> io::Result<()> {
+        // the retracted keys are not part of the command to repeat or the macro being recorded
+        let len = self.last_command.len();
+        self.last_command.truncate(len.saturating_sub(count));
+        if let Some((_, keys)) = &mut self.recording {
+            if self.replaying.is_empty() {
+                keys.truncate(keys.len().saturating_sub(count));
+            }
+        }
+        let pos = ed.cursor().saturating_sub(count);
+        ed.delete_until_silent(pos)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Buffer, Completer, DefaultEditorRules, Editor, History, KeyMap, Prompt};
+    use crate::{
+        BasicCompleter, BellStyle, Buffer, Completer, DefaultEditorRules, Editor, History, KeyMap,
+        Prompt, TestEditor,
+    };
 
     fn simulate_key_codes<'a, 'b, M: KeyMap, I>(
         keymap: &mut M,
@@ -4958,8 +5474,8 @@ mod tests {
     }
 
     #[test]
-    /// test a compound count with delete
-    fn delete_with_count() {
+    /// a count before d0 is ignored, 0 is absolute
+    fn delete_until_start_with_count() {
         let mut out = Vec::new();
 
         let mut history = History::new();
@@ -4983,21 +5499,20 @@ mod tests {
             &mut ed,
             [
                 KeyCode::Esc,
-                KeyCode::Char('0'),
-                KeyCode::Char('2'),
+                KeyCode::Char('$'),
+                KeyCode::Char('3'),
                 KeyCode::Char('d'),
-                KeyCode::Char('2'),
-                KeyCode::Char('l'),
+                KeyCode::Char('0'),
             ]
             .iter(),
         );
         assert_eq!(ed.cursor(), 0);
-        assert_eq!(String::from(ed), "te");
+        assert_eq!(String::from(ed), "e");
     }
 
     #[test]
-    /// test a compound count with delete and repeat
-    fn delete_with_count_and_repeat() {
+    /// a count before c0 is ignored, 0 is absolute
+    fn change_until_start_with_count() {
         let mut out = Vec::new();
 
         let mut history = History::new();
@@ -5014,29 +5529,30 @@ mod tests {
         .unwrap();
         let mut map = Vi::new();
         map.init(&mut ed);
-        ed.insert_str_after_cursor("delete delete").unwrap();
+        ed.insert_str_after_cursor("change").unwrap();
 
         simulate_key_codes(
             &mut map,
             &mut ed,
             [
                 KeyCode::Esc,
-                KeyCode::Char('0'),
-                KeyCode::Char('2'),
-                KeyCode::Char('d'),
+                KeyCode::Char('$'),
                 KeyCode::Char('2'),
-                KeyCode::Char('l'),
-                KeyCode::Char('.'),
+                KeyCode::Char('c'),
+                KeyCode::Char('0'),
+                KeyCode::Char('x'),
             ]
             .iter(),
         );
-        assert_eq!(ed.cursor(), 0);
-        assert_eq!(String::from(ed), "elete");
+        assert_eq!(ed.cursor(), 1);
+        assert_eq!(String::from(ed), "xe");
     }
 
     #[test]
-    fn move_to_end_of_word_simple() {
+    /// d^ stops at the first non-blank
+    fn delete_until_first_non_blank() {
         let mut out = Vec::new();
+
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
         let rules = DefaultEditorRules::default();
@@ -5049,21 +5565,30 @@ mod tests {
             &rules,
         )
         .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("  delete").unwrap();
 
-        ed.insert_str_after_cursor("here are").unwrap();
-        let start_pos = ed.cursor();
-        ed.insert_str_after_cursor(" som").unwrap();
-        let end_pos = ed.cursor();
-        ed.insert_str_after_cursor("e words").unwrap();
-        ed.move_cursor_to(start_pos).unwrap();
-        let vi = Vi::new();
-        vi.move_to_end_of_word(&mut ed, 1).unwrap();
-        assert_eq!(ed.cursor(), end_pos);
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('$'),
+                KeyCode::Char('d'),
+                KeyCode::Char('^'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 2);
+        assert_eq!(String::from(ed), "  e");
     }
 
     #[test]
-    fn move_to_end_of_word_comma() {
+    /// d^ before the first non-blank deletes up to it
+    fn delete_forward_to_first_non_blank() {
         let mut out = Vec::new();
+
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
         let rules = DefaultEditorRules::default();
@@ -5076,26 +5601,32 @@ mod tests {
             &rules,
         )
         .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("   delete").unwrap();
 
-        ed.insert_str_after_cursor("here ar").unwrap();
-        let start_pos = ed.cursor();
-        ed.insert_after_cursor('e').unwrap();
-        let end_pos1 = ed.cursor();
-        ed.insert_str_after_cursor(", som").unwrap();
-        let end_pos2 = ed.cursor();
-        ed.insert_str_after_cursor("e words").unwrap();
-        ed.move_cursor_to(start_pos).unwrap();
-
-        let vi = Vi::new();
-        vi.move_to_end_of_word(&mut ed, 1).unwrap();
-        assert_eq!(ed.cursor(), end_pos1);
-        vi.move_to_end_of_word(&mut ed, 1).unwrap();
-        assert_eq!(ed.cursor(), end_pos2);
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('l'),
+                KeyCode::Char('3'),
+                KeyCode::Char('d'),
+                KeyCode::Char('^'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 1);
+        assert_eq!(String::from(ed), " delete");
     }
 
     #[test]
-    fn move_to_end_of_word_nonkeywords() {
+    /// c^ stops at the first non-blank
+    fn change_until_first_non_blank() {
         let mut out = Vec::new();
+
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
         let rules = DefaultEditorRules::default();
@@ -5108,26 +5639,31 @@ mod tests {
             &rules,
         )
         .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("  change").unwrap();
 
-        ed.insert_str_after_cursor("here ar").unwrap();
-        let start_pos = ed.cursor();
-        ed.insert_str_after_cursor("e,,,").unwrap();
-        let end_pos1 = ed.cursor();
-        ed.insert_str_after_cursor(",som").unwrap();
-        let end_pos2 = ed.cursor();
-        ed.insert_str_after_cursor("e words").unwrap();
-        ed.move_cursor_to(start_pos).unwrap();
-
-        let vi = Vi::new();
-        vi.move_to_end_of_word(&mut ed, 1).unwrap();
-        assert_eq!(ed.cursor(), end_pos1);
-        vi.move_to_end_of_word(&mut ed, 1).unwrap();
-        assert_eq!(ed.cursor(), end_pos2);
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('$'),
+                KeyCode::Char('c'),
+                KeyCode::Char('^'),
+                KeyCode::Char('x'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(String::from(ed), "  xe");
     }
 
     #[test]
-    fn move_to_end_of_word_whitespace() {
+    /// test a compound count with delete
+    fn delete_with_count() {
         let mut out = Vec::new();
+
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
         let rules = DefaultEditorRules::default();
@@ -5140,8 +5676,174 @@ mod tests {
             &rules,
         )
         .unwrap();
-
-        assert_eq!(ed.cursor(), 0);
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("delete").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('2'),
+                KeyCode::Char('d'),
+                KeyCode::Char('2'),
+                KeyCode::Char('l'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), "te");
+    }
+
+    #[test]
+    /// test a compound count with delete and repeat
+    fn delete_with_count_and_repeat() {
+        let mut out = Vec::new();
+
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("delete delete").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('2'),
+                KeyCode::Char('d'),
+                KeyCode::Char('2'),
+                KeyCode::Char('l'),
+                KeyCode::Char('.'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), "elete");
+    }
+
+    #[test]
+    fn move_to_end_of_word_simple() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+
+        ed.insert_str_after_cursor("here are").unwrap();
+        let start_pos = ed.cursor();
+        ed.insert_str_after_cursor(" som").unwrap();
+        let end_pos = ed.cursor();
+        ed.insert_str_after_cursor("e words").unwrap();
+        ed.move_cursor_to(start_pos).unwrap();
+        let vi = Vi::new();
+        vi.move_to_end_of_word(&mut ed, 1).unwrap();
+        assert_eq!(ed.cursor(), end_pos);
+    }
+
+    #[test]
+    fn move_to_end_of_word_comma() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+
+        ed.insert_str_after_cursor("here ar").unwrap();
+        let start_pos = ed.cursor();
+        ed.insert_after_cursor('e').unwrap();
+        let end_pos1 = ed.cursor();
+        ed.insert_str_after_cursor(", som").unwrap();
+        let end_pos2 = ed.cursor();
+        ed.insert_str_after_cursor("e words").unwrap();
+        ed.move_cursor_to(start_pos).unwrap();
+
+        let vi = Vi::new();
+        vi.move_to_end_of_word(&mut ed, 1).unwrap();
+        assert_eq!(ed.cursor(), end_pos1);
+        vi.move_to_end_of_word(&mut ed, 1).unwrap();
+        assert_eq!(ed.cursor(), end_pos2);
+    }
+
+    #[test]
+    fn move_to_end_of_word_nonkeywords() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+
+        ed.insert_str_after_cursor("here ar").unwrap();
+        let start_pos = ed.cursor();
+        ed.insert_str_after_cursor("e,,,").unwrap();
+        let end_pos1 = ed.cursor();
+        ed.insert_str_after_cursor(",som").unwrap();
+        let end_pos2 = ed.cursor();
+        ed.insert_str_after_cursor("e words").unwrap();
+        ed.move_cursor_to(start_pos).unwrap();
+
+        let vi = Vi::new();
+        vi.move_to_end_of_word(&mut ed, 1).unwrap();
+        assert_eq!(ed.cursor(), end_pos1);
+        vi.move_to_end_of_word(&mut ed, 1).unwrap();
+        assert_eq!(ed.cursor(), end_pos2);
+    }
+
+    #[test]
+    fn move_to_end_of_word_whitespace() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+
+        assert_eq!(ed.cursor(), 0);
         ed.insert_str_after_cursor("here are").unwrap();
         let start_pos = ed.cursor();
         assert_eq!(ed.cursor(), 8);
@@ -6171,7 +6873,6 @@ mod tests {
                 KeyCode::Char('e'),
                 KeyCode::Char('a'),
                 KeyCode::Char('k'),
-                KeyCode::Char(' '),
             ]
             .iter(),
         );
@@ -6225,10 +6926,9 @@ mod tests {
     }
 
     #[test]
-    /// make sure t command does nothing if nothing was found
-    fn test_t_not_found() {
+    /// ctrl-w in insert mode deletes the word before the cursor
+    fn insert_mode_ctrl_w() {
         let mut out = Vec::new();
-
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
         let rules = DefaultEditorRules::default();
@@ -6243,25 +6943,44 @@ mod tests {
         .unwrap();
         let mut map = Vi::new();
         map.init(&mut ed);
-        ed.insert_str_after_cursor("abc defg").unwrap();
+        let ctrl_w = Key::new_mod(KeyCode::Char('w'), KeyMod::Ctrl);
+
+        // nothing to delete at the start of the line
+        simulate_keys(&mut map, &mut ed, [ctrl_w].iter());
+        assert_eq!(ed.current_buffer().to_string(), "");
 
         simulate_key_codes(
             &mut map,
             &mut ed,
             [
                 KeyCode::Esc,
-                KeyCode::Char('0'),
-                KeyCode::Char('t'),
-                KeyCode::Char('z'),
+                KeyCode::Char('i'),
+                KeyCode::Char('a'),
+                KeyCode::Char(' '),
+                KeyCode::Char('b'),
+                KeyCode::Char('c'),
+                KeyCode::Char(' '),
             ]
             .iter(),
         );
-        assert_eq!(ed.cursor(), 0);
+        simulate_keys(&mut map, &mut ed, [ctrl_w].iter());
+        assert_eq!(ed.current_buffer().to_string(), "a ");
+        assert_eq!(ed.cursor(), 2);
+
+        // repeating the insert (before the space) repeats the delete
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc, KeyCode::Char('.')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "aa  ");
+
+        // the delete is part of the insert's undo group
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('u')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "a ");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('u')].iter());
+        assert_eq!(String::from(ed), "");
     }
 
     #[test]
-    /// make sure t command moves the cursor
-    fn test_t_movement() {
+    /// ctrl-u in insert mode deletes back to the start of the current line
+    fn insert_mode_ctrl_u() {
         let mut out = Vec::new();
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
@@ -6277,25 +6996,64 @@ mod tests {
         .unwrap();
         let mut map = Vi::new();
         map.init(&mut ed);
-        ed.insert_str_after_cursor("abc defg").unwrap();
+        let ctrl_u = Key::new_mod(KeyCode::Char('u'), KeyMod::Ctrl);
+        ed.insert_str_after_cursor("first line\nsecond line")
+            .unwrap();
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc].iter());
+        ed.move_cursor_to(18).unwrap();
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('i')].iter());
 
-        simulate_key_codes(
-            &mut map,
-            &mut ed,
-            [
-                KeyCode::Esc,
-                KeyCode::Char('0'),
-                KeyCode::Char('t'),
-                KeyCode::Char('d'),
-            ]
-            .iter(),
-        );
-        assert_eq!(ed.cursor(), 3);
+        simulate_keys(&mut map, &mut ed, [ctrl_u].iter());
+        assert_eq!(ed.current_buffer().to_string(), "first line\nline");
+        assert_eq!(ed.cursor(), 11);
+
+        // already at the start of the line
+        simulate_keys(&mut map, &mut ed, [ctrl_u].iter());
+        assert_eq!(ed.current_buffer().to_string(), "first line\nline");
+
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc, KeyCode::Char('u')].iter());
+        assert_eq!(String::from(ed), "first line\nsecond line");
     }
 
     #[test]
-    /// make sure t command moves the cursor
-    fn test_t_movement_with_count() {
+    /// h and l ring the bell if one is configured when there is nowhere to move
+    fn bell_at_line_ends() {
+        let mut out = Vec::new();
+        {
+            let mut history = History::new();
+            let mut buf = String::with_capacity(512);
+            let rules = DefaultEditorRules::default();
+            let mut ed = Editor::new(
+                &mut out,
+                Prompt::from("prompt"),
+                None,
+                &mut history,
+                &mut buf,
+                &rules,
+            )
+            .unwrap();
+            let mut map = Vi::new();
+            map.init(&mut ed);
+            ed.set_bell_style(BellStyle::Audible);
+            ed.insert_str_after_cursor("ab").unwrap();
+            simulate_key_codes(
+                &mut map,
+                &mut ed,
+                [
+                    KeyCode::Esc,
+                    KeyCode::Char('l'),
+                    KeyCode::Char('h'),
+                    KeyCode::Char('h'),
+                ]
+                .iter(),
+            );
+        }
+        assert_eq!(out.iter().filter(|b| **b == 7).count(), 2);
+    }
+
+    #[test]
+    /// make sure t command does nothing if nothing was found
+    fn test_t_not_found() {
         let mut out = Vec::new();
 
         let mut history = History::new();
@@ -6312,7 +7070,7 @@ mod tests {
         .unwrap();
         let mut map = Vi::new();
         map.init(&mut ed);
-        ed.insert_str_after_cursor("abc defg d").unwrap();
+        ed.insert_str_after_cursor("abc defg").unwrap();
 
         simulate_key_codes(
             &mut map,
@@ -6320,18 +7078,17 @@ mod tests {
             [
                 KeyCode::Esc,
                 KeyCode::Char('0'),
-                KeyCode::Char('2'),
                 KeyCode::Char('t'),
-                KeyCode::Char('d'),
+                KeyCode::Char('z'),
             ]
             .iter(),
         );
-        assert_eq!(ed.cursor(), 8);
+        assert_eq!(ed.cursor(), 0);
     }
 
     #[test]
-    /// test normal mode after char movement
-    fn test_t_movement_then_normal() {
+    /// make sure t command moves the cursor
+    fn test_t_movement() {
         let mut out = Vec::new();
         let mut history = History::new();
         let mut buf = String::with_capacity(512);
@@ -6357,11 +7114,81 @@ mod tests {
                 KeyCode::Char('0'),
                 KeyCode::Char('t'),
                 KeyCode::Char('d'),
-                KeyCode::Char('l'),
             ]
             .iter(),
         );
-        assert_eq!(ed.cursor(), 4);
+        assert_eq!(ed.cursor(), 3);
+    }
+
+    #[test]
+    /// make sure t command moves the cursor
+    fn test_t_movement_with_count() {
+        let mut out = Vec::new();
+
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc defg d").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('2'),
+                KeyCode::Char('t'),
+                KeyCode::Char('d'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 8);
+    }
+
+    #[test]
+    /// test normal mode after char movement
+    fn test_t_movement_then_normal() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc defg").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('t'),
+                KeyCode::Char('d'),
+                KeyCode::Char('l'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 4);
     }
 
     #[test]
@@ -6578,6 +7405,81 @@ mod tests {
         assert_eq!(ed.cursor(), 6);
     }
 
+    #[test]
+    /// a count on ; or , repeats the last f/t that many times
+    fn test_semi_movement_count() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abcabcabc").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('f'),
+                KeyCode::Char('c'),
+                KeyCode::Char('2'),
+                KeyCode::Char(';'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 8);
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('2'), KeyCode::Char(',')].iter(),
+        );
+        assert_eq!(ed.cursor(), 2);
+
+        // the count of the f is not carried over to a plain ;
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Char('0'),
+                KeyCode::Char('2'),
+                KeyCode::Char('t'),
+                KeyCode::Char('c'),
+                KeyCode::Char(';'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 7);
+
+        // and with an operator
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Char('0'),
+                KeyCode::Char('f'),
+                KeyCode::Char('c'),
+                KeyCode::Char('0'),
+                KeyCode::Char('d'),
+                KeyCode::Char('2'),
+                KeyCode::Char(';'),
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "abc");
+    }
+
     #[test]
     /// make sure , command moves the cursor
     fn test_comma_movement() {
@@ -6690,6 +7592,1312 @@ mod tests {
         assert_eq!(String::from(ed), "ab");
     }
 
+    #[test]
+    /// ; repeats the f used by a previous delete
+    fn test_semi_after_delete_find() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc def ghi").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('d'),
+                KeyCode::Char('f'),
+                KeyCode::Char(' '),
+                KeyCode::Char(';'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(String::from(ed), "def ghi");
+    }
+
+    #[test]
+    /// d; after t deletes up to the next match, not the adjacent one
+    fn test_semi_delete_until() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("a,b,c,d").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('t'),
+                KeyCode::Char(','),
+                KeyCode::Char('d'),
+                KeyCode::Char(';'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), ",c,d");
+    }
+
+    #[test]
+    /// d; after T deletes back to just after the next match, not the adjacent one
+    fn test_semi_delete_until_rev() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("a,b,c,d").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('T'),
+                KeyCode::Char(','),
+                KeyCode::Char('d'),
+                KeyCode::Char(';'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 4);
+        assert_eq!(String::from(ed), "a,b,d");
+    }
+
+    #[test]
+    /// y; yanks through the match without moving the cursor
+    fn test_semi_yank() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc abc").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('f'),
+                KeyCode::Char('c'),
+                KeyCode::Char('y'),
+                KeyCode::Char(';'),
+                KeyCode::Char('$'),
+                KeyCode::Char('p'),
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "abc abcc abc");
+    }
+
+    #[test]
+    /// cw on a word leaves the following whitespace alone, like ce
+    fn change_word_like_end_of_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("foo bar").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('c'),
+                KeyCode::Char('w'),
+                KeyCode::Char('X'),
+                KeyCode::Esc,
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "X bar");
+    }
+
+    #[test]
+    /// cw on the last character of a word only changes that character, cW spans punctuation
+    fn change_word_at_end_of_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("foo.bar baz qux").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('2'),
+                KeyCode::Char('l'),
+                KeyCode::Char('c'),
+                KeyCode::Char('w'),
+                KeyCode::Char('X'),
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('c'),
+                KeyCode::Char('W'),
+                KeyCode::Char('Y'),
+                KeyCode::Esc,
+                KeyCode::Char('w'),
+                KeyCode::Char('w'),
+                KeyCode::Char('.'),
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "Y baz Y");
+    }
+
+    #[test]
+    /// cw on whitespace still only changes the whitespace
+    fn change_word_on_whitespace() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("foo  bar").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('3'),
+                KeyCode::Char('l'),
+                KeyCode::Char('c'),
+                KeyCode::Char('w'),
+                KeyCode::Char('X'),
+                KeyCode::Esc,
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "fooXbar");
+    }
+
+    #[test]
+    /// ctrl-left and ctrl-right move by WORD in insert and normal mode and work after d
+    fn ctrl_arrows_move_by_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("a.b cd ef").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Left, KeyMod::Ctrl)].iter(),
+        );
+        assert_eq!(ed.cursor(), 7);
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Esc),
+                Key::new(KeyCode::Char('0')),
+                Key::new_mod(KeyCode::Right, KeyMod::Ctrl),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 4);
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Char('d')),
+                Key::new_mod(KeyCode::Right, KeyMod::Ctrl),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 4);
+        assert_eq!(String::from(ed), "a.b ef");
+    }
+
+    #[test]
+    /// c2aw changes two words and their whitespace and stays in insert mode
+    fn change_count_around_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("a b c d").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('c'),
+                KeyCode::Char('2'),
+                KeyCode::Char('a'),
+                KeyCode::Char('w'),
+                KeyCode::Char('X'),
+            ]
+            .iter(),
+        );
+        assert_eq!(map.mode(), Mode::Insert);
+        assert_eq!(ed.cursor(), 1);
+        assert_eq!(String::from(ed), "Xc d");
+    }
+
+    #[test]
+    /// caw changes the word and the whitespace after it
+    fn change_around_word() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("foo bar baz").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('w'),
+                KeyCode::Char('l'),
+                KeyCode::Char('c'),
+                KeyCode::Char('a'),
+                KeyCode::Char('w'),
+                KeyCode::Char('X'),
+            ]
+            .iter(),
+        );
+        assert_eq!(map.mode(), Mode::Insert);
+        assert_eq!(ed.cursor(), 5);
+        assert_eq!(String::from(ed), "foo Xbaz");
+    }
+
+    #[test]
+    /// ci( changes the text between the parentheses
+    fn change_inside_parens() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("f(ab) x").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('f'),
+                KeyCode::Char('a'),
+                KeyCode::Char('c'),
+                KeyCode::Char('i'),
+                KeyCode::Char('('),
+                KeyCode::Char('X'),
+            ]
+            .iter(),
+        );
+        assert_eq!(map.mode(), Mode::Insert);
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(String::from(ed), "f(X) x");
+    }
+
+    #[test]
+    /// ca( changes the parentheses too
+    fn change_around_parens() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("f(ab) x").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('f'),
+                KeyCode::Char('b'),
+                KeyCode::Char('c'),
+                KeyCode::Char('a'),
+                KeyCode::Char('('),
+                KeyCode::Char('X'),
+            ]
+            .iter(),
+        );
+        assert_eq!(map.mode(), Mode::Insert);
+        assert_eq!(ed.cursor(), 2);
+        assert_eq!(String::from(ed), "fX x");
+    }
+
+    #[test]
+    /// a change with a text object is one undo step and can be repeated with .
+    fn change_around_word_undo_repeat() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("aa bb cc dd").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('c'),
+                KeyCode::Char('a'),
+                KeyCode::Char('w'),
+                KeyCode::Char('X'),
+                KeyCode::Char(' '),
+                KeyCode::Esc,
+                KeyCode::Char('w'),
+                KeyCode::Char('.'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "X X cc dd");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('u')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "X bb cc dd");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('u')].iter());
+        assert_eq!(String::from(ed), "aa bb cc dd");
+    }
+
+    #[test]
+    /// a delete with a text object repeats with .
+    fn delete_around_word_repeat() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("aa bb cc").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('d'),
+                KeyCode::Char('a'),
+                KeyCode::Char('w'),
+                KeyCode::Char('.'),
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "cc");
+    }
+
+    #[test]
+    /// ctrl-v in insert mode inserts the next key, even escape, and repeats with .
+    fn insert_mode_quoted_insert() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Char('a')),
+                Key::new_mod(KeyCode::Char('v'), KeyMod::Ctrl),
+                Key::new(KeyCode::Esc),
+                Key::new_mod(KeyCode::Char('v'), KeyMod::Ctrl),
+                Key::new(KeyCode::Char('\t')),
+            ]
+            .iter(),
+        );
+        assert_eq!(map.mode(), Mode::Insert);
+        assert_eq!(ed.current_buffer().to_string(), "a\x1b\t");
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new(KeyCode::Esc), Key::new(KeyCode::Char('.'))].iter(),
+        );
+        assert_eq!(String::from(ed), "a\x1ba\x1b\t\t");
+    }
+
+    #[test]
+    fn yank_line_paste_below() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("hello").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('y'),
+                KeyCode::Char('y'),
+                KeyCode::Char('p'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "hello\nhello");
+        assert_eq!(ed.cursor(), 6);
+
+        // a plain yank pastes inline again
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('y'), KeyCode::Char('l'), KeyCode::Char('p')].iter(),
+        );
+        assert_eq!(String::from(ed), "hello\nhhello");
+    }
+
+    #[test]
+    fn yank_line_paste_above_with_count() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("hello").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('Y'),
+                KeyCode::Char('2'),
+                KeyCode::Char('P'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "hello\nhello\nhello");
+        assert_eq!(ed.cursor(), 0);
+    }
+
+    #[test]
+    fn delete_line_paste() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("hello").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('d'),
+                KeyCode::Char('d'),
+                KeyCode::Char('i'),
+                KeyCode::Char('a'),
+                KeyCode::Esc,
+                KeyCode::Char('p'),
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "a\nhello");
+    }
+
+    #[test]
+    fn vi_repeat_history_search() {
+        let mut history = History::new();
+        history.push("data pat second").unwrap();
+        history.push("skip1").unwrap();
+        history.push("data pat one").unwrap();
+        history.push("skip2").unwrap();
+        let mut out = Vec::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new_mod(KeyCode::Char('r'), KeyMod::Ctrl),
+                Key::new(KeyCode::Char('p')),
+                Key::new(KeyCode::Char('a')),
+                Key::new(KeyCode::Char('t')),
+                Key::new(KeyCode::Right),
+                Key::new(KeyCode::Esc),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "data pat one");
+
+        // n keeps going back like ctrl-r, N comes forward again
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('n')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "data pat second");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('n')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "data pat second");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('N')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "data pat one");
+
+        // after a buffer search n repeats that instead
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Char('0'),
+                KeyCode::Char('/'),
+                KeyCode::Char('a'),
+                KeyCode::Char('\n'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 1);
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('n')].iter());
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(String::from(ed), "data pat one");
+    }
+
+    #[test]
+    fn record_and_replay_macro() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("a b c d e f g").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('q'),
+                KeyCode::Char('a'),
+                KeyCode::Char('d'),
+                KeyCode::Char('w'),
+            ]
+            .iter(),
+        );
+        assert_eq!(map.recording_register(), Some('a'));
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('q')].iter());
+        assert_eq!(map.recording_register(), None);
+        assert_eq!(
+            map.macro_keys('a'),
+            Some(&[Key::new(KeyCode::Char('d')), Key::new(KeyCode::Char('w'))][..])
+        );
+        assert_eq!(ed.current_buffer().to_string(), "b c d e f g");
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('@'), KeyCode::Char('a')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "c d e f g");
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('@'), KeyCode::Char('@')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "d e f g");
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('2'), KeyCode::Char('@'), KeyCode::Char('a')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "f g");
+    }
+
+    #[test]
+    /// a macro holds the keys as handle_key saw them, with key sequences resolved and tab
+    /// completing again on replay
+    fn macro_records_sequences_and_completion() {
+        let mut map = Vi::new();
+        map.set_esc_sequence('j', 'k', 1000);
+        let mut ed = TestEditor::new(Box::new(map));
+        ed.set_completer(Box::new(BasicCompleter::new(vec!["hello"])));
+        let line = ed
+            .feed_codes(&[
+                KeyCode::Esc,
+                KeyCode::Char('q'),
+                KeyCode::Char('a'),
+                KeyCode::Char('a'),
+                KeyCode::Char(' '),
+                KeyCode::Char('h'),
+                KeyCode::Char('e'),
+                KeyCode::Char('\t'),
+                KeyCode::Char('j'),
+                KeyCode::Char('k'),
+                KeyCode::Char('q'),
+            ])
+            .unwrap();
+        assert_eq!(line, " hello");
+        let line = ed
+            .feed_codes(&[KeyCode::Char('@'), KeyCode::Char('a')])
+            .unwrap();
+        assert_eq!(line, " hello hello");
+        // back in normal mode after the replayed jk
+        assert_eq!(ed.feed_codes(&[KeyCode::Char('x')]).unwrap(), " hello hell");
+    }
+
+    #[test]
+    fn macro_replaying_itself_stops() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abcdef").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('q'),
+                KeyCode::Char('b'),
+                KeyCode::Char('x'),
+                KeyCode::Char('@'),
+                KeyCode::Char('b'),
+                KeyCode::Char('q'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "bcdef");
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('@'), KeyCode::Char('b')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "cdef");
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('3'), KeyCode::Char('@'), KeyCode::Char('b')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "f");
+    }
+
+    #[test]
+    fn vi_search_history_for_word_under_cursor() {
+        let mut history = History::new();
+        history.push("make test").unwrap();
+        history.push("ls").unwrap();
+        history.push("cargo test --lib").unwrap();
+        history.push("cargo build").unwrap();
+        let mut out = Vec::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("run test").unwrap();
+
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc, KeyCode::Char('#')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "cargo test --lib");
+        assert_eq!(ed.last_history_search(), Some(("test", false)));
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('n')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "make test");
+
+        // * looks at newer entries, going around to the oldest
+        let star = [KeyCode::Char('0'), KeyCode::Char('w'), KeyCode::Char('*')];
+        simulate_key_codes(&mut map, &mut ed, star.iter());
+        assert_eq!(ed.current_buffer().to_string(), "cargo test --lib");
+        assert_eq!(ed.last_history_search(), Some(("test", true)));
+        simulate_key_codes(&mut map, &mut ed, star.iter());
+        assert_eq!(ed.current_buffer().to_string(), "make test");
+    }
+
+    #[test]
+    fn esc_aborts_operator_pending() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abcdefgh").unwrap();
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc, KeyCode::Char('0')].iter());
+
+        // d3<Esc>, the next x deletes one char
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('d'), KeyCode::Char('3'), KeyCode::Esc].iter(),
+        );
+        assert_eq!(map.mode(), Mode::Normal);
+        assert_eq!(map.count, 0);
+        assert_eq!(map.secondary_count, 0);
+        assert!(map.current_command.is_empty());
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('x')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "bcdefgh");
+
+        // 2d<Esc>, the count given before the operator is gone too
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('2'), KeyCode::Char('d'), KeyCode::Esc].iter(),
+        );
+        assert_eq!(map.mode(), Mode::Normal);
+        assert_eq!(map.count, 0);
+        assert_eq!(map.secondary_count, 0);
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('d'), KeyCode::Char('l')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "cdefgh");
+
+        // c<Esc> leaves no insert mode behind, u undoes the dl above
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('c'), KeyCode::Esc].iter());
+        assert_eq!(map.mode(), Mode::Normal);
+        assert!(map.current_insert.is_none());
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('u')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "bcdefgh");
+    }
+
+    #[test]
+    fn overwrite_backspace_restores() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abcd").unwrap();
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('h'),
+                KeyCode::Char('R'),
+                KeyCode::Char('x'),
+                KeyCode::Char('y'),
+                KeyCode::Char('z'),
+            ]
+            .iter(),
+        );
+        // typing past the end of the line appends
+        assert_eq!(ed.current_buffer().to_string(), "abxyz");
+        assert_eq!(ed.cursor(), 5);
+
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Backspace].iter());
+        assert_eq!(ed.current_buffer().to_string(), "abxy");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Backspace].iter());
+        assert_eq!(ed.current_buffer().to_string(), "abxd");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Backspace].iter());
+        assert_eq!(ed.current_buffer().to_string(), "abcd");
+        assert_eq!(ed.cursor(), 2);
+        // past where the replacing started backspace only moves left
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Backspace].iter());
+        assert_eq!(ed.current_buffer().to_string(), "abcd");
+        assert_eq!(ed.cursor(), 1);
+
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('Q'), KeyCode::Esc].iter());
+        assert_eq!(ed.current_buffer().to_string(), "aQcd");
+        assert_eq!(ed.cursor(), 1);
+        // the whole replace is one undo
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('u')].iter());
+        assert_eq!(ed.current_buffer().to_string(), "abcd");
+    }
+
+    #[test]
+    /// ge and gE with counts, as motions and after d
+    fn ge_with_counts() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc].iter());
+        let run = |map: &mut Vi, ed: &mut Editor, line: &str, keys: &str| {
+            ed.clear_buffer().unwrap();
+            ed.insert_str_after_cursor(line).unwrap();
+            let codes: Vec<KeyCode> = keys.chars().map(KeyCode::Char).collect();
+            simulate_key_codes(map, ed, codes.iter());
+            (ed.current_buffer().to_string(), ed.cursor())
+        };
+
+        assert_eq!(
+            run(&mut map, &mut ed, "one two three", "$2ge"),
+            ("one two three".to_string(), 2)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one-two three", "$2gE"),
+            ("one-two three".to_string(), 0)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one-two three", "$2ge"),
+            ("one-two three".to_string(), 3)
+        );
+        // the delete takes in both the character under the cursor and the one moved to
+        assert_eq!(
+            run(&mut map, &mut ed, "one two three", "$dge"),
+            ("one tw".to_string(), 5)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one two three", "$2dge"),
+            ("on".to_string(), 1)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one two three", "$d2ge"),
+            ("on".to_string(), 1)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one-two three", "$2dgE"),
+            ("".to_string(), 0)
+        );
+        // going back from the middle of a word
+        assert_eq!(
+            run(&mut map, &mut ed, "one two three", "$hh2dge"),
+            ("onee".to_string(), 2)
+        );
+    }
+
+    #[test]
+    /// >> and << with counts and motions, each one undo step
+    fn shift_lines() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Esc].iter());
+        let run = |map: &mut Vi, ed: &mut Editor, line: &str, cursor: usize, keys: &str| {
+            ed.clear_buffer().unwrap();
+            ed.insert_str_after_cursor(line).unwrap();
+            ed.move_cursor_to(cursor).unwrap();
+            let codes: Vec<KeyCode> = keys.chars().map(KeyCode::Char).collect();
+            simulate_key_codes(map, ed, codes.iter());
+            (ed.current_buffer().to_string(), ed.cursor())
+        };
+
+        assert_eq!(
+            run(&mut map, &mut ed, "one\ntwo\nthree", 5, ">>"),
+            ("one\n    two\nthree".to_string(), 8)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one\ntwo\nthree", 4, "2>>"),
+            ("one\n    two\n    three".to_string(), 8)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one\ntwo\nthree", 1, ">j"),
+            ("    one\n    two\nthree".to_string(), 4)
+        );
+        assert_eq!(
+            run(&mut map, &mut ed, "one\ntwo\nthree", 9, "2<k"),
+            ("one\ntwo\nthree".to_string(), 0)
+        );
+        // empty lines stay empty
+        assert_eq!(
+            run(&mut map, &mut ed, "a\n\nb", 0, "3>>"),
+            ("    a\n\n    b".to_string(), 4)
+        );
+        // a motion within the line shifts just that line
+        assert_eq!(
+            run(&mut map, &mut ed, "one two", 0, ">w"),
+            ("    one two".to_string(), 4)
+        );
+        // less indentation than the shift width is all removed, a tab counts as a whole one
+        assert_eq!(
+            run(&mut map, &mut ed, "  one\n      two\n\t three", 0, "3<<"),
+            ("one\n  two\n three".to_string(), 0)
+        );
+        assert_eq!(run(&mut map, &mut ed, "one\ntwo", 0, ">ju").0, "one\ntwo");
+        assert_eq!(
+            run(&mut map, &mut ed, "one", 0, ">>."),
+            ("        one".to_string(), 8)
+        );
+
+        map.set_shift_width(2);
+        assert_eq!(
+            run(&mut map, &mut ed, "one", 0, ">>"),
+            ("  one".to_string(), 2)
+        );
+        // anything that is not a motion cancels the shift
+        assert_eq!(
+            run(&mut map, &mut ed, "one", 0, ">x"),
+            ("one".to_string(), 0)
+        );
+    }
+
+    #[test]
+    /// D, C and x put what they delete in the register for p
+    fn delete_commands_fill_register() {
+        let mut history = History::new();
+        let mut out = Vec::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("one two").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('w'),
+                KeyCode::Char('D'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().as_str(), "one ");
+        simulate_key_codes(&mut map, &mut ed, [KeyCode::Char('p')].iter());
+        assert_eq!(ed.current_buffer().as_str(), "one two");
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('0'), KeyCode::Char('x'), KeyCode::Char('p')].iter(),
+        );
+        assert_eq!(ed.current_buffer().as_str(), "noe two");
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Char('$'),
+                KeyCode::Char('b'),
+                KeyCode::Char('C'),
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('P'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().as_str(), "twonoe ");
+    }
+
+    #[test]
+    /// ctrl-o runs one normal mode command from insert mode
+    fn ctrl_o_runs_one_normal_command() {
+        let mut ed = TestEditor::new(Box::new(Vi::new()));
+        let ctrl_o = Key::new_mod(KeyCode::Char('o'), KeyMod::Ctrl);
+        ed.feed_str("world").unwrap();
+        ed.feed(&[ctrl_o, Key::new(KeyCode::Char('0'))]).unwrap();
+        assert_eq!(ed.feed_str("hello ").unwrap(), "hello world");
+
+        ed.feed(&[ctrl_o, Key::new(KeyCode::Char('$'))]).unwrap();
+        assert_eq!(ed.cursor(), 11);
+        assert_eq!(ed.feed_str("!").unwrap(), "hello world!");
+
+        // an operator with its motion is one command
+        ed.feed(&[ctrl_o, Key::new(KeyCode::Char('0'))]).unwrap();
+        ed.feed(&[ctrl_o]).unwrap();
+        ed.feed_str("dw").unwrap();
+        assert_eq!(ed.feed_str("bye ").unwrap(), "bye world!");
+
+        // a count is part of the command
+        ed.feed(&[ctrl_o]).unwrap();
+        ed.feed_str("2x").unwrap();
+        assert_eq!(ed.feed_str("o").unwrap(), "bye orld!");
+
+        // still in the same insert, escape and undo take it all back
+        ed.feed_codes(&[KeyCode::Esc, KeyCode::Char('u')]).unwrap();
+        assert_eq!(ed.feed_codes(&[]).unwrap(), "");
+    }
+
+    #[test]
+    /// one undo takes back a whole completion
+    fn undo_completion() {
+        let mut ed = TestEditor::new(Box::new(Vi::new()));
+        ed.set_completer(Box::new(BasicCompleter::new(vec!["hello"])));
+        ed.feed_str("echo he").unwrap();
+        assert_eq!(ed.feed_codes(&[KeyCode::Char('\t')]).unwrap(), "echo hello");
+        let line = ed.feed_codes(&[KeyCode::Esc, KeyCode::Char('u')]).unwrap();
+        assert_eq!(line, "echo he");
+    }
+
     #[test]
     /// test find_char
     fn test_find_char() {
@@ -7620,6 +9828,50 @@ mod tests {
         assert_eq!(String::from(ed), "tilde");
     }
 
+    #[test]
+    /// repeating a tilde with a count flips as many characters again
+    fn tilde_count_repeat() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("tilde count").unwrap();
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Esc,
+                KeyCode::Char('0'),
+                KeyCode::Char('3'),
+                KeyCode::Char('~'),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "TILde count");
+        assert_eq!(ed.cursor(), 3);
+        assert_eq!(map.count, 0);
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [KeyCode::Char('w'), KeyCode::Char('.')].iter(),
+        );
+        assert_eq!(ed.current_buffer().to_string(), "TILde COUnt");
+        assert_eq!(ed.cursor(), 9);
+    }
+
     #[test]
     /// test tilde
     fn tilde_count() {
@@ -8342,4 +10594,264 @@ mod tests {
         assert_eq!(ed.cursor(), 1);
         assert_eq!(String::from(ed), "()");
     }
+
+    #[test]
+    fn accept_autosuggestion_word_insert() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        history.push("git commit -m").unwrap();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("git").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [Key::new_mod(KeyCode::Right, KeyMod::Ctrl)].iter(),
+        );
+        assert_eq!(ed.cursor(), 10);
+        assert_eq!(String::from(ed), "git commit");
+    }
+
+    #[test]
+    fn key_sequences() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        map.bind_sequence("jj", KeyAction::Key(Key::new(KeyCode::Esc)), 1000);
+        map.bind_sequence("kk", KeyAction::InsertStr("()".to_string()), 1000);
+
+        simulate_key_codes(
+            &mut map,
+            &mut ed,
+            [
+                KeyCode::Char('j'),
+                KeyCode::Char('a'),
+                KeyCode::Char('k'),
+                KeyCode::Char('k'),
+                KeyCode::Char('j'),
+                KeyCode::Char('j'),
+                // normal mode, j is a movement here
+                KeyCode::Char('j'),
+                KeyCode::Char('j'),
+                KeyCode::Char('x'),
+            ]
+            .iter(),
+        );
+        assert_eq!(String::from(ed), "ja(");
+    }
+
+    #[test]
+    fn search_buffer_forward() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc foo def foo").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Esc),
+                Key::new(KeyCode::Char('0')),
+                Key::new(KeyCode::Char('/')),
+                Key::new(KeyCode::Char('f')),
+                Key::new(KeyCode::Char('o')),
+                Key::new(KeyCode::Char('o')),
+                Key::new(KeyCode::Char('\n')),
+                Key::new(KeyCode::Char('n')),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 12);
+        assert_eq!(String::from(ed), "abc foo def foo");
+    }
+
+    #[test]
+    fn search_buffer_wraps() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc foo def foo").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Esc),
+                Key::new(KeyCode::Char('0')),
+                Key::new(KeyCode::Char('/')),
+                Key::new(KeyCode::Char('f')),
+                Key::new(KeyCode::Char('o')),
+                Key::new(KeyCode::Char('o')),
+                Key::new(KeyCode::Char('\n')),
+                Key::new(KeyCode::Char('n')),
+                Key::new(KeyCode::Char('n')),
+                Key::new(KeyCode::Char('N')),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 12);
+        assert_eq!(String::from(ed), "abc foo def foo");
+    }
+
+    #[test]
+    fn search_buffer_backward() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("foo abc foo def").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Esc),
+                Key::new(KeyCode::Char('?')),
+                Key::new(KeyCode::Char('f')),
+                Key::new(KeyCode::Char('o')),
+                Key::new(KeyCode::Char('o')),
+                Key::new(KeyCode::Char('\n')),
+                Key::new(KeyCode::Char('n')),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), "foo abc foo def");
+    }
+
+    #[test]
+    fn search_buffer_delete() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc foo def foo").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Esc),
+                Key::new(KeyCode::Char('0')),
+                Key::new(KeyCode::Char('d')),
+                Key::new(KeyCode::Char('/')),
+                Key::new(KeyCode::Char('d')),
+                Key::new(KeyCode::Char('e')),
+                Key::new(KeyCode::Char('f')),
+                Key::new(KeyCode::Char('\n')),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 0);
+        assert_eq!(String::from(ed), "def foo");
+    }
+
+    #[test]
+    fn search_buffer_no_match() {
+        let mut out = Vec::new();
+        let mut history = History::new();
+        let mut buf = String::with_capacity(512);
+        let rules = DefaultEditorRules::default();
+        let mut ed = Editor::new(
+            &mut out,
+            Prompt::from("prompt"),
+            None,
+            &mut history,
+            &mut buf,
+            &rules,
+        )
+        .unwrap();
+        let mut map = Vi::new();
+        map.init(&mut ed);
+        ed.insert_str_after_cursor("abc foo").unwrap();
+
+        simulate_keys(
+            &mut map,
+            &mut ed,
+            [
+                Key::new(KeyCode::Esc),
+                Key::new(KeyCode::Char('0')),
+                Key::new(KeyCode::Char('/')),
+                Key::new(KeyCode::Char('x')),
+                Key::new(KeyCode::Char('y')),
+                Key::new(KeyCode::Char('z')),
+                Key::new(KeyCode::Char('\n')),
+                Key::new(KeyCode::Char('l')),
+            ]
+            .iter(),
+        );
+        assert_eq!(ed.cursor(), 1);
+        assert_eq!(String::from(ed), "abc foo");
+    }
 }
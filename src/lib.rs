@@ -40,5 +40,10 @@ pub mod editor_rules;
 pub use editor_rules::*;
 
 mod grapheme_iter;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+#[cfg(any(test, feature = "test-util"))]
+pub use test_util::TestEditor;
 #[cfg(test)]
 mod test;
@@ -13,6 +13,7 @@ use std::fmt;
 /// let prompt = Prompt::from("prompt$ ");
 /// assert_eq!(&prompt.to_string(), "prompt$ ");
 /// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Prompt {
     pub prefix: Option<String>,
     pub prompt: String,
@@ -1,10 +1,16 @@
-use crate::context::ColorClosure;
+use crate::context::{ColorClosure, ColorInfo, CursorColorClosure};
 use crate::prompt::Prompt;
-use crate::{util, Buffer, Cursor};
-use sl_console::{clear, color, cursor};
-use std::cmp::Ordering;
+use crate::{util, BellStyle, Buffer, Cursor};
+use sl_console::{clear, color, cursor, style};
+use std::cmp::{self, Ordering};
 use std::fmt::Write;
 use std::io;
+use std::thread;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+// How long the screen stays reversed for a visual bell.
+const VISUAL_BELL_MS: u64 = 100;
 
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct Metrics {
@@ -64,11 +70,11 @@ impl Metrics {
             // Cursor might overrun autosuggestion with history search.
             Some(suggestion) if cursor.curr_grapheme() < suggestion.num_graphemes() => (
                 suggestion.line_width_until(cursor.curr_grapheme()),
-                suggestion.is_last_arg_newline(),
+                suggestion.range(0, cursor.curr_grapheme()).ends_with('\n'),
             ),
             _ => (
                 buf.line_width_until(cursor.curr_grapheme()),
-                buf.is_last_arg_newline(),
+                buf.range(0, cursor.curr_grapheme()).ends_with('\n'),
             ),
         };
 
@@ -93,6 +99,14 @@ impl Metrics {
         })
     }
 
+    /// Column and row of the cursor, 0-indexed, relative to the start of the prompt line.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (
+            self.new_total_width_to_cursor % self.width,
+            self.new_total_width_to_cursor / self.width,
+        )
+    }
+
     pub fn term_cursor_line(&self) -> usize {
         (self.new_total_width_to_cursor + self.width) / self.width
     }
@@ -112,6 +126,36 @@ impl Metrics {
     }
 }
 
+/// Display width of `line` up to grapheme `cursor`.
+pub(crate) fn line_width_to_cursor(line: &str, cursor: usize) -> usize {
+    line.graphemes(true)
+        .take(cursor)
+        .map(util::grapheme_width)
+        .sum()
+}
+
+/// First visible column of a horizontally scrolled line that is `view_width` columns wide. The
+/// view only moves when the cursor would land on a marker cell, and never further right than
+/// needed to show the end of the line.
+pub(crate) fn scroll_offset(
+    offset: usize,
+    cursor_col: usize,
+    line_width: usize,
+    view_width: usize,
+) -> usize {
+    if line_width < view_width {
+        return 0;
+    }
+    let mut offset = offset;
+    if cursor_col < offset + 1 {
+        offset = cursor_col.saturating_sub(1);
+    }
+    if cursor_col + 2 > offset + view_width {
+        offset = cursor_col + 2 - view_width;
+    }
+    cmp::min(offset, line_width + 2 - view_width)
+}
+
 /// An interface to simplify writing to and clearing the terminal. This is where most
 /// of the display logic for interfacing with the console library exists.
 pub struct Terminal<'a> {
@@ -125,11 +169,34 @@ pub struct Terminal<'a> {
     // A closure that is evaluated just before we write to out.
     // This allows us to do custom syntax highlighting and other fun stuff.
     closure: Option<ColorClosure>,
+    // Like `closure` but also told where the cursor is, used instead of it when set.
+    cursor_closure: Option<&'a mut CursorColorClosure>,
     // Use the closure if it is set.
     use_closure: bool,
     buf: &'a mut String,
+    // First visible column when a long line is scrolled instead of wrapped.
+    scroll_offset: usize,
+    // Byte range of the shown line to draw in reverse video, the match of a history search.
+    highlight: Option<(usize, usize)>,
+    // Keep SGR mouse reporting on instead of turning mouse reporting off on every redraw.
+    mouse_reporting: bool,
 }
 
+/// Turns off the mouse reporting turned on while the editor handles mouse clicks.
+pub(crate) const MOUSE_REPORTING_OFF: &str = "\x1B[?1000l\x1B[?1006l";
+
+/// Saves the cursor and switches to a cleared alternate screen with the cursor in the top left.
+pub(crate) const ALTERNATE_SCREEN_ON: &str = "\x1B7\x1B[?1049h\x1B[H";
+
+/// Switches back to the main screen as it was and restores the cursor.
+pub(crate) const ALTERNATE_SCREEN_OFF: &str = "\x1B[?1049l\x1B8";
+
+/// Has the terminal mark pasted text with `ESC [ 200 ~` and `ESC [ 201 ~`.
+pub(crate) const BRACKETED_PASTE_ON: &str = "\x1B[?2004h";
+
+/// Turns bracketed paste off again.
+pub(crate) const BRACKETED_PASTE_OFF: &str = "\x1B[?2004l";
+
 fn fmt_io_err(err: std::fmt::Error) -> io::Error {
     let msg = format!("{}", err);
     io::Error::new(io::ErrorKind::Other, msg)
@@ -146,8 +213,12 @@ impl<'a> Terminal<'a> {
             term_cursor_line: 1,
             color_lines: None,
             closure,
+            cursor_closure: None,
             buf,
             use_closure: true,
+            scroll_offset: 0,
+            highlight: None,
+            mouse_reporting: false,
         }
     }
 
@@ -187,14 +258,24 @@ impl<'a> Terminal<'a> {
         self.use_closure = use_closure;
     }
 
-    fn colorize(&mut self, line: &str) -> String {
-        match self.closure {
-            Some(ref mut f) if self.use_closure => {
+    pub(crate) fn set_cursor_closure(&mut self, closure: Option<&'a mut CursorColorClosure>) {
+        self.cursor_closure = closure;
+    }
+
+    // `cursor` is the byte offset of the cursor in `line`, if it is on it.
+    fn colorize(&mut self, line: &str, cursor: Option<usize>) -> String {
+        match (&mut self.cursor_closure, &mut self.closure) {
+            (Some(f), _) if self.use_closure => {
+                let color = f(line, ColorInfo { cursor });
+                self.color_lines = Some((line.to_string(), color.clone()));
+                color
+            }
+            (None, Some(f)) if self.use_closure => {
                 let color = f(line);
                 self.color_lines = Some((line.to_string(), color.clone()));
                 color
             }
-            Some(_) => {
+            (Some(_), _) | (_, Some(_)) => {
                 if let Some((old_line, colorized)) = &self.color_lines {
                     if line.starts_with(old_line) {
                         let mut new_line = colorized.clone();
@@ -216,8 +297,9 @@ impl<'a> Terminal<'a> {
         line: &str,
         is_search: bool,
         buf_num_remaining_bytes: usize,
+        cursor: Option<usize>,
     ) -> io::Result<()> {
-        let start = self.colorize(&line[..buf_num_remaining_bytes]);
+        let start = self.colorize(&line[..buf_num_remaining_bytes], cursor);
         if is_search {
             write!(self.buf, "{}", color::Yellow.fg_str()).map_err(fmt_io_err)?;
         }
@@ -229,12 +311,46 @@ impl<'a> Terminal<'a> {
         Ok(())
     }
 
+    // Writes `text`, which starts `offset` bytes into the shown line, in reverse video where it
+    // overlaps the `highlight` byte range of that line.
+    fn push_highlighted(
+        &mut self,
+        text: &str,
+        offset: usize,
+        highlight: (usize, usize),
+    ) -> io::Result<()> {
+        let end = offset + text.len();
+        let start = highlight.0.clamp(offset, end) - offset;
+        let stop = highlight.1.clamp(offset, end) - offset;
+        if start == stop {
+            self.buf.push_str(text);
+            return Ok(());
+        }
+        self.buf.push_str(&text[..start]);
+        write!(
+            self.buf,
+            "{}{}{}",
+            style::Invert,
+            &text[start..stop],
+            style::NoInvert
+        )
+        .map_err(fmt_io_err)?;
+        self.buf.push_str(&text[stop..]);
+        Ok(())
+    }
+
     pub fn clear(&mut self) -> io::Result<()> {
         write!(self.buf, "{}{}", clear::All, cursor::Goto(1, 1)).map_err(fmt_io_err)?;
         self.term_cursor_line = 1;
         Ok(())
     }
 
+    /// Forgets which line of the prompt and buffer the terminal cursor is on, so the next
+    /// redraw starts on the terminal cursor's line instead of moving up to the prompt first.
+    pub(crate) fn forget_cursor_line(&mut self) {
+        self.term_cursor_line = 1;
+    }
+
     pub fn write_prompt(&mut self, prompt: &str) -> io::Result<()> {
         write!(self.buf, "{}", prompt).map_err(fmt_io_err)
     }
@@ -248,8 +364,9 @@ impl<'a> Terminal<'a> {
 
         let (w, _) = sl_console::terminal_size()?;
 
-        // XXX wide character support
-        let max_word_size = completions.iter().fold(1, |m, x| max(m, x.chars().count()));
+        let max_word_size = completions
+            .iter()
+            .fold(1, |m, x| max(m, util::str_width(x)));
         let cols = max(1, w as usize / (max_word_size));
         let col_width = 2 + w as usize / cols;
         let cols = max(1, w as usize / col_width);
@@ -276,7 +393,9 @@ impl<'a> Terminal<'a> {
                 )
                 .map_err(fmt_io_err)?;
             }
-            write!(output_buf, "{:<1$}", com, col_width).map_err(fmt_io_err)?;
+            // pad by display width, format's padding counts chars
+            let padding = col_width.saturating_sub(util::str_width(com));
+            write!(output_buf, "{}{:<2$}", com, "", padding).map_err(fmt_io_err)?;
             if Some(index) == highlighted {
                 write!(
                     output_buf,
@@ -294,7 +413,11 @@ impl<'a> Terminal<'a> {
     }
 
     pub fn clear_after_cursor(&mut self) -> io::Result<()> {
-        self.buf.push_str("\x1B[?1000l\x1B[?1l");
+        if self.mouse_reporting {
+            self.buf.push_str("\x1B[?1000h\x1B[?1006h\x1B[?1l");
+        } else {
+            self.buf.push_str("\x1B[?1000l\x1B[?1l");
+        }
 
         if self.term_cursor_line > 1 {
             write!(self.buf, "{}", cursor::Up(self.term_cursor_line as u16 - 1))
@@ -304,14 +427,30 @@ impl<'a> Terminal<'a> {
         write!(self.buf, "\r{}", clear::AfterCursor).map_err(fmt_io_err)
     }
 
+    /// Writes the completions being cycled through, at most `max_display` of them. If there are
+    /// more, the page holding the highlighted one is shown followed by how many are left out.
     pub fn maybe_write_completions(
         &mut self,
         show_completions_hint: Option<&(Vec<String>, Option<usize>)>,
+        max_display: Option<usize>,
     ) -> io::Result<usize> {
         // If we're cycling through completions, show those
         let mut completion_lines = 0;
         if let Some((completions, i)) = show_completions_hint {
-            completion_lines = 1 + Self::print_completion_list(completions, *i, self.buf)?;
+            let page = match max_display {
+                Some(max) if completions.len() > max => max.max(1),
+                _ => completions.len(),
+            };
+            let start = i.map_or(0, |i| i - i % page);
+            let end = completions.len().min(start + page);
+            let highlighted = i.map(|i| i - start);
+            completion_lines =
+                1 + Self::print_completion_list(&completions[start..end], highlighted, self.buf)?;
+            let hidden = completions.len() - (end - start);
+            if hidden > 0 {
+                write!(self.buf, "\r\n… ({} more)", hidden).map_err(fmt_io_err)?;
+                completion_lines += 1;
+            }
             self.buf.push_str("\r\n");
         }
         Ok(completion_lines)
@@ -322,6 +461,7 @@ impl<'a> Terminal<'a> {
         buf: &Buffer,
         autosuggestion: Option<&Buffer>,
         show_autosuggest: bool,
+        cursor: usize,
         metrics: Metrics,
         is_search: bool,
     ) -> io::Result<()> {
@@ -334,6 +474,7 @@ impl<'a> Terminal<'a> {
             _ => (buf.lines(), buf.num_lines()),
         };
         let mut buf_num_remaining_bytes = buf.num_bytes();
+        let mut line_offset = 0;
 
         for (i, line) in lines.enumerate() {
             if i > 0 {
@@ -341,14 +482,19 @@ impl<'a> Terminal<'a> {
                     .map_err(fmt_io_err)?;
             }
 
-            if buf_num_remaining_bytes == 0 {
+            // the cursor byte offset is within the buffer, before any autosuggestion
+            let line_cursor = cursor.checked_sub(line_offset).filter(|&c| c <= line.len());
+            if let Some(highlight) = self.highlight {
+                write!(self.buf, "{}", color::Yellow.fg_str()).map_err(fmt_io_err)?;
+                self.push_highlighted(line, line_offset, highlight)?;
+            } else if buf_num_remaining_bytes == 0 {
                 self.buf.push_str(line);
             } else if line.as_bytes().len() > buf_num_remaining_bytes {
-                self.display_with_suggest(line, is_search, buf_num_remaining_bytes)?;
+                self.display_with_suggest(line, is_search, buf_num_remaining_bytes, line_cursor)?;
                 buf_num_remaining_bytes = 0;
             } else {
                 buf_num_remaining_bytes -= line.as_bytes().len();
-                let written_line = self.colorize(line);
+                let written_line = self.colorize(line, line_cursor);
                 if is_search {
                     write!(self.buf, "{}", color::Yellow.fg_str()).map_err(fmt_io_err)?;
                 }
@@ -357,10 +503,138 @@ impl<'a> Terminal<'a> {
             if i + 1 < lines_len {
                 self.buf.push_str("\r\n");
             }
+            line_offset += line.len() + 1;
         }
         Ok(())
     }
 
+    pub(crate) fn set_mouse_reporting(&mut self, mouse_reporting: bool) {
+        self.mouse_reporting = mouse_reporting;
+    }
+
+    /// Asks the terminal where the cursor is, the answer comes back as input.
+    pub(crate) fn request_cursor_position(&mut self) -> io::Result<()> {
+        self.out.write_all(b"\x1B[6n")?;
+        self.out.flush()
+    }
+
+    /// Sets the byte range of the shown line to draw in reverse video, or None for no highlight.
+    pub(crate) fn set_highlight(&mut self, highlight: Option<(usize, usize)>) {
+        self.highlight = highlight;
+    }
+
+    pub(crate) fn scroll_offset(&self) -> usize {
+        self.scroll_offset
+    }
+
+    pub(crate) fn reset_scroll(&mut self) {
+        self.scroll_offset = 0;
+    }
+
+    /// Like `show_lines` but for a single line that is scrolled horizontally to fit in the
+    /// `view_width` columns after the prompt. Hidden text is marked with `<` and `>`. Returns
+    /// the column of the cursor within the view.
+    pub(crate) fn show_line_scrolled(
+        &mut self,
+        buf: &Buffer,
+        autosuggestion: Option<&Buffer>,
+        show_autosuggest: bool,
+        cursor: usize,
+        view_width: usize,
+        is_search: bool,
+    ) -> io::Result<usize> {
+        let line = match autosuggestion {
+            Some(suggestion) if show_autosuggest => suggestion.to_string(),
+            _ => buf.to_string(),
+        };
+        let line_width = util::str_width(&line);
+        let cursor_col = line_width_to_cursor(&line, cursor);
+        self.scroll_offset = scroll_offset(self.scroll_offset, cursor_col, line_width, view_width);
+        let offset = self.scroll_offset;
+
+        let left_marker = offset > 0;
+        let right_marker = line_width > offset + view_width;
+        let first_col = offset + left_marker as usize;
+        let last_col = offset + view_width - right_marker as usize;
+
+        // Collect the graphemes that fit entirely in the view, remembering how much of that
+        // is the buffer rather than the autosuggestion.
+        let mut visible = String::new();
+        let mut visible_offset = None;
+        let mut buf_bytes = 0;
+        let mut col = 0;
+        let mut pad_start = 0;
+        for (i, grapheme) in line.grapheme_indices(true) {
+            let start = col;
+            col += util::grapheme_width(grapheme);
+            if start < first_col {
+                // A wide grapheme cut by the left edge leaves blank cells.
+                pad_start = col.saturating_sub(first_col);
+                continue;
+            }
+            if col > last_col {
+                break;
+            }
+            if i < buf.num_bytes() {
+                buf_bytes += grapheme.len();
+            }
+            visible_offset.get_or_insert(i);
+            visible.push_str(grapheme);
+        }
+
+        // the cursor as a byte offset in what is visible, if it is in view
+        let cursor_byte = line
+            .grapheme_indices(true)
+            .nth(cursor)
+            .map_or(line.len(), |(i, _)| i);
+        let visible_cursor = cursor_byte
+            .checked_sub(visible_offset.unwrap_or(0))
+            .filter(|&c| c <= visible.len());
+
+        if left_marker {
+            self.buf.push('<');
+        }
+        for _ in 0..pad_start {
+            self.buf.push(' ');
+        }
+        if let Some(highlight) = self.highlight {
+            write!(self.buf, "{}", color::Yellow.fg_str()).map_err(fmt_io_err)?;
+            self.push_highlighted(&visible, visible_offset.unwrap_or(0), highlight)?;
+        } else if buf_bytes < visible.len() {
+            self.display_with_suggest(&visible, is_search, buf_bytes, visible_cursor)?;
+        } else {
+            let written_line = self.colorize(&visible, visible_cursor);
+            if is_search {
+                write!(self.buf, "{}", color::Yellow.fg_str()).map_err(fmt_io_err)?;
+            }
+            self.buf.push_str(&written_line);
+        }
+        if right_marker {
+            let drawn = first_col + pad_start + util::str_width(&visible);
+            write!(self.buf, "{}", color::Reset.fg_str()).map_err(fmt_io_err)?;
+            for _ in drawn..last_col {
+                self.buf.push(' ');
+            }
+            self.buf.push('>');
+        }
+        Ok(cursor_col - offset)
+    }
+
+    /// Moves the term cursor to `col` on the prompt line and writes everything out, the
+    /// counterpart of `display` for a scrolled line.
+    pub(crate) fn display_at_column(
+        &mut self,
+        col: usize,
+        completion_lines: usize,
+    ) -> io::Result<()> {
+        self.buf.push('\r');
+        if col > 0 {
+            write!(self.buf, "{}", cursor::Right(col as u16)).map_err(fmt_io_err)?;
+        }
+        self.term_cursor_line = completion_lines + 1;
+        self.finish_display()
+    }
+
     pub(crate) fn display(&mut self, metrics: Metrics, completion_lines: usize) -> io::Result<()> {
         // at the end of the line, move the cursor down a line
         if metrics.at_end_of_line() {
@@ -394,6 +668,10 @@ impl<'a> Terminal<'a> {
 
         self.term_cursor_line = completion_lines + metrics.term_cursor_line();
 
+        self.finish_display()
+    }
+
+    fn finish_display(&mut self) -> io::Result<()> {
         write!(
             self.buf,
             "{}{}",
@@ -413,6 +691,22 @@ impl<'a> Terminal<'a> {
         self.out.flush()
     }
 
+    /// Rings the terminal bell in the given style.
+    pub fn bell(&mut self, style: BellStyle) -> io::Result<()> {
+        match style {
+            BellStyle::Audible => self.out.write_all(b"\x07"),
+            BellStyle::Visual => {
+                // Flash the screen by briefly switching it to reverse video.
+                self.out.write_all(b"\x1B[?5h")?;
+                self.out.flush()?;
+                thread::sleep(Duration::from_millis(VISUAL_BELL_MS));
+                self.out.write_all(b"\x1B[?5l")?;
+                self.out.flush()
+            }
+            BellStyle::None => Ok(()),
+        }
+    }
+
     pub fn write_newline(&mut self) -> io::Result<()> {
         self.out.write_all(b"\r\n")
     }
@@ -507,6 +801,24 @@ mod tests {
         assert_eq!(m.new_num_lines, 1);
     }
 
+    #[test]
+    fn test_scroll_offset() {
+        // fits, cursor at the end included
+        assert_eq!(scroll_offset(0, 9, 9, 10), 0);
+        assert_eq!(scroll_offset(5, 9, 9, 10), 0);
+        // cursor past the right edge scrolls so it sits before the '>' marker
+        assert_eq!(scroll_offset(0, 8, 20, 10), 0);
+        assert_eq!(scroll_offset(0, 9, 20, 10), 1);
+        assert_eq!(scroll_offset(0, 20, 20, 10), 12);
+        // moving back within the view does not scroll
+        assert_eq!(scroll_offset(12, 15, 20, 10), 12);
+        // cursor onto the '<' marker scrolls left
+        assert_eq!(scroll_offset(12, 12, 20, 10), 11);
+        assert_eq!(scroll_offset(12, 0, 20, 10), 0);
+        // the line got shorter
+        assert_eq!(scroll_offset(12, 5, 12, 10), 4);
+    }
+
     #[test]
     fn test_last_arg_is_newline() {
         let newline = Buffer::from("\n".to_owned());
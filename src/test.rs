@@ -82,6 +82,86 @@ fn test_history_indexing() {
     assert_eq!(&h[2], "c");
 }
 
+#[test]
+fn test_history_iter() {
+    let mut h = History::new();
+    h.push("a").unwrap();
+    h.push("b").unwrap();
+    h.push("a").unwrap();
+    h.push("c").unwrap();
+    assert_eq!(h.iter().collect::<Vec<_>>(), vec!["b", "a", "c"]);
+    assert_eq!(h.iter().next_back(), Some("c"));
+    assert_eq!(h.get(0), Some("b"));
+    assert_eq!(h.get(3), None);
+}
+
+#[test]
+fn test_history_reader_writer() {
+    let mut h = History::new();
+    h.push("a").unwrap();
+    h.set_search_context(Some("ctx".to_string()));
+    h.push("b").unwrap();
+    let mut saved = Vec::new();
+    h.save_to_writer(&mut saved).unwrap();
+    assert_eq!(
+        String::from_utf8(saved.clone()).unwrap(),
+        "a\n#<ctx>ctx\nb\n"
+    );
+
+    let mut h2 = History::new();
+    h2.push("old").unwrap();
+    h2.load_from_reader(&saved[..], false).unwrap();
+    assert_eq!(h2.iter().collect::<Vec<_>>(), vec!["a", "b"]);
+    assert_eq!(h2.get_context(1), &Some(vec!["ctx".to_string()]));
+    h2.load_from_reader(&b"c\nd\nc\n"[..], true).unwrap();
+    assert_eq!(h2.iter().collect::<Vec<_>>(), vec!["a", "b", "d", "c"]);
+}
+
+#[test]
+fn test_history_disabled() {
+    let mut h = History::new();
+    h.push("a").unwrap();
+    h.set_enabled(false);
+    h.push("b").unwrap();
+    h.push_throwaway("c").unwrap();
+    h.load_from_reader(&b"d\n"[..], true).unwrap();
+    assert_eq!(h.iter().collect::<Vec<_>>(), vec!["a"]);
+
+    h.set_enabled(true);
+    h.push("b").unwrap();
+    assert_eq!(h.iter().collect::<Vec<_>>(), vec!["a", "b"]);
+}
+
+#[test]
+fn test_history_filter() {
+    let mut h = History::new();
+    h.set_filter(Some(Box::new(|line: &str| {
+        if line.starts_with(' ') {
+            None
+        } else if let Some(i) = line.find("TOKEN=") {
+            Some(format!("{}TOKEN=***", &line[..i]))
+        } else {
+            Some(line.to_string())
+        }
+    })));
+    h.push("ls").unwrap();
+    h.push("export TOKEN=hunter2").unwrap();
+    h.push(" echo secret").unwrap();
+    h.push_throwaway(" oops").unwrap();
+    // the redacted entry is what the next one is compared with
+    h.push("export TOKEN=other").unwrap();
+    assert_eq!(h.iter().collect::<Vec<_>>(), vec!["ls", "export TOKEN=***"]);
+    let mut saved = Vec::new();
+    h.save_to_writer(&mut saved).unwrap();
+    let saved = String::from_utf8(saved).unwrap();
+    assert!(saved.contains("TOKEN=***"));
+    assert!(!saved.contains("hunter2"));
+
+    h.set_filter(None);
+    h.push(" echo secret").unwrap();
+    assert_eq!(h.len(), 3);
+}
+
 #[test]
 fn test_in_memory_history_truncating() {
     let mut h = History::new();
@@ -117,6 +197,42 @@ fn test_in_file_history_truncating() {
     fs::remove_file(tmp_file).unwrap();
 }
 
+#[test]
+fn test_context_clear_and_set_history() {
+    let mut tmp_file = env::temp_dir();
+    tmp_file.push("liner_test_file_clear.txt");
+    let _ = fs::remove_file(&tmp_file);
+
+    let mut context = Context::new();
+    context
+        .history
+        .set_file_name_and_load_history(&tmp_file)
+        .unwrap();
+    context.history.push("ls").unwrap();
+    context.history.push("pwd").unwrap();
+    context.clear_history(false).unwrap();
+    assert!(context.history.is_empty());
+    // the file keeps its entries
+    assert_eq!(fs::read_to_string(&tmp_file).unwrap(), "ls\npwd\n");
+
+    context.clear_history(true).unwrap();
+    assert!(context.history.is_empty());
+    assert_eq!(fs::read_to_string(&tmp_file).unwrap(), "");
+    context.history.push("echo").unwrap();
+    assert_eq!(context.history.iter().collect::<Vec<_>>(), vec!["echo"]);
+
+    let mut prepared = History::new();
+    prepared.push("cargo test").unwrap();
+    context.set_history(prepared);
+    assert_eq!(
+        context.history.iter().collect::<Vec<_>>(),
+        vec!["cargo test"]
+    );
+    assert_eq!(context.history.file_name(), None);
+
+    fs::remove_file(tmp_file).unwrap();
+}
+
 static TEXT: &'static str = "a
 b
 c
@@ -285,3 +401,105 @@ fn test_shared_history() {
     assert_eq!(h2.get_context(1).as_ref().unwrap().len(), 1);
     assert_eq!(h2.get_context(1).as_ref().unwrap().get(0).unwrap(), "*");
 }
+
+#[test]
+fn test_autosuggestion_follows_reloaded_history() {
+    let mut tmp_file = env::temp_dir();
+    tmp_file.push("liner_reload_suggestion789.txt");
+    {
+        let mut _f = fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(tmp_file.clone())
+            .unwrap();
+    }
+    let mut history = History::new();
+    history
+        .set_file_name_and_load_history(tmp_file.clone())
+        .unwrap();
+    history.push("cargo build").unwrap();
+    // another process sharing the file
+    let mut other = History::new();
+    other.set_file_name_and_load_history(tmp_file).unwrap();
+
+    let mut out = Vec::new();
+    let mut buf = String::with_capacity(512);
+    let rules = DefaultEditorRules::default();
+    let mut ed = Editor::new(
+        &mut out,
+        Prompt::from("prompt"),
+        None,
+        &mut history,
+        &mut buf,
+        &rules,
+    )
+    .unwrap();
+    ed.insert_str_after_cursor("cargo").unwrap();
+    ed.accept_autosuggestion().unwrap();
+    assert_eq!(ed.current_buffer().to_string(), "cargo build");
+
+    ed.clear_buffer().unwrap();
+    ed.insert_str_after_cursor("git").unwrap();
+    assert!(!ed.is_currently_showing_autosuggestion());
+    other.push("git status").unwrap();
+    ed.reload_history().unwrap();
+    assert!(ed.is_currently_showing_autosuggestion());
+    ed.accept_autosuggestion().unwrap();
+    assert_eq!(ed.current_buffer().to_string(), "git status");
+}
+
+#[test]
+fn test_read_result_from_io_result() {
+    use std::io;
+
+    assert!(matches!(ReadResult::from(Ok("line".to_string())), ReadResult::Line(l) if l == "line"));
+    let eof = Err(io::Error::new(io::ErrorKind::UnexpectedEof, "ctrl-d"));
+    assert!(matches!(ReadResult::from(eof), ReadResult::Eof));
+    let int = Err(io::Error::new(io::ErrorKind::Interrupted, "ctrl-c"));
+    assert!(matches!(ReadResult::from(int), ReadResult::Interrupted));
+    let cancelled = Err(io::Error::new(io::ErrorKind::Interrupted, Cancelled));
+    assert!(matches!(ReadResult::from(cancelled), ReadResult::Cancelled));
+    let other = Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"));
+    assert!(matches!(ReadResult::from(other), ReadResult::Io(_)));
+}
+
+#[test]
+fn test_read_piped_line() {
+    use crate::context::read_piped_line;
+    use std::io::{self, Cursor};
+
+    let mut input = Cursor::new("first\r\nsecond\n\nlast");
+    assert_eq!(read_piped_line(&mut input).unwrap(), "first");
+    assert_eq!(read_piped_line(&mut input).unwrap(), "second");
+    assert_eq!(read_piped_line(&mut input).unwrap(), "");
+    assert_eq!(read_piped_line(&mut input).unwrap(), "last");
+    let eof = read_piped_line(&mut input).unwrap_err();
+    assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(matches!(ReadResult::from(Err(eof)), ReadResult::Eof));
+}
+
+#[test]
+fn test_context_builder_history_file() {
+    let mut tmp_file = env::temp_dir();
+    tmp_file.push("liner_builder_file.txt");
+    let _ = fs::remove_file(&tmp_file);
+    {
+        let mut h = History::new();
+        h.set_file_name_and_load_history(&tmp_file).unwrap();
+        h.push("first").unwrap();
+        h.push("second").unwrap();
+    }
+    let context = Context::builder()
+        .keymap(Box::new(keymap::Vi::new()))
+        .history_file(&tmp_file)
+        .tab_inserts_spaces(Some(4))
+        .build()
+        .unwrap();
+    assert_eq!(
+        context.history.iter().collect::<Vec<_>>(),
+        vec!["first", "second"]
+    );
+    assert_eq!(context.history.file_name(), tmp_file.to_str());
+    fs::remove_file(&tmp_file).unwrap();
+}
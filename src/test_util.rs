@@ -0,0 +1,251 @@
+//! Helpers for testing keymaps, completers and editor rules without a terminal.
+//!
+//! Enabled for this crate's own tests and, for downstream crates, with the `test-util` feature.
+use std::io;
+
+use sl_console::event::{Key, KeyCode};
+
+use crate::{
+    Buffer, Completer, DefaultEditorRules, Editor, EditorRules, EmptyCompleter, History,
+    KeyInterceptor, KeyMap, Prompt,
+};
+
+type EditorSetup = Box<dyn for<'a> FnMut(&mut Editor<'a>)>;
+
+/// Drives a keymap against an `Editor` that draws into memory.
+///
+/// The line and cursor carry over between calls to `feed`, as does the state of the keymap, so a
+/// test can feed keys in several steps and check the buffer in between. Each `feed` draws with a
+/// new `Editor` that the keymap sets up again with [KeyMap::resume]; other editor state, like a
+/// search or completion list in progress, only lasts for one `feed`.
+pub struct TestEditor {
+    out: Vec<u8>,
+    buf: String,
+    prompt: String,
+    history: History,
+    rules: Box<dyn EditorRules>,
+    keymap: Box<dyn KeyMap>,
+    completer: Box<dyn Completer>,
+    setup: Option<EditorSetup>,
+    interceptor: Option<KeyInterceptor>,
+    line: Buffer,
+    cursor: Option<usize>,
+    initialized: bool,
+    done: bool,
+}
+
+impl TestEditor {
+    pub fn new(keymap: Box<dyn KeyMap>) -> Self {
+        TestEditor {
+            out: Vec::new(),
+            buf: String::with_capacity(512),
+            prompt: "prompt".to_string(),
+            history: History::new(),
+            rules: Box::new(DefaultEditorRules::default()),
+            keymap,
+            completer: Box::new(EmptyCompleter::new()),
+            setup: None,
+            interceptor: None,
+            line: Buffer::new(),
+            cursor: None,
+            initialized: false,
+            done: false,
+        }
+    }
+
+    pub fn set_prompt(&mut self, prompt: &str) -> &mut Self {
+        self.prompt = prompt.to_string();
+        self
+    }
+
+    pub fn set_completer(&mut self, completer: Box<dyn Completer>) -> &mut Self {
+        self.completer = completer;
+        self
+    }
+
+    pub fn set_editor_rules(&mut self, rules: Box<dyn EditorRules>) -> &mut Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Sets a function run on the `Editor` before every `feed`, for the settings a `Context`
+    /// would normally pass on (max length, bell style, ...).
+    pub fn set_editor_setup<F>(&mut self, setup: F) -> &mut Self
+    where
+        F: for<'a> FnMut(&mut Editor<'a>) + 'static,
+    {
+        self.setup = Some(Box::new(setup));
+        self
+    }
+
+    /// Sets a function that sees each key before the keymap, like
+    /// [Context::set_key_interceptor](crate::Context::set_key_interceptor).
+    pub fn set_key_interceptor(&mut self, interceptor: Option<KeyInterceptor>) -> &mut Self {
+        self.interceptor = interceptor;
+        self
+    }
+
+    /// Replaces the line being edited, the cursor goes to the end of it.
+    pub fn set_line(&mut self, line: &str) -> &mut Self {
+        self.line = Buffer::from(line.to_owned());
+        self.cursor = None;
+        self
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    pub fn keymap_mut(&mut self) -> &mut dyn KeyMap {
+        &mut *self.keymap
+    }
+
+    /// The cursor position after the last `feed`.
+    pub fn cursor(&self) -> usize {
+        self.cursor.unwrap_or_else(|| self.line.num_graphemes())
+    }
+
+    /// True if a key fed so far finished the line (enter for example).
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Everything drawn so far.
+    pub fn output(&self) -> &[u8] {
+        &self.out
+    }
+
+    /// Feeds `keys` to the keymap and returns the line. Keys after one that finishes the line
+    /// are ignored.
+    pub fn feed<'k, I>(&mut self, keys: I) -> io::Result<String>
+    where
+        I: IntoIterator<Item = &'k Key>,
+    {
+        let mut ed = Editor::new_with_init_buffer(
+            &mut self.out,
+            Prompt::from(self.prompt.clone()),
+            None,
+            &mut self.history,
+            &mut self.buf,
+            self.line.clone(),
+            &*self.rules,
+        )?;
+        if let Some(setup) = &mut self.setup {
+            setup(&mut ed);
+        }
+        if self.initialized {
+            self.keymap.resume(&mut ed)?;
+        } else {
+            self.keymap.init(&mut ed);
+            self.initialized = true;
+        }
+        if let Some(cursor) = self.cursor {
+            ed.move_cursor_to(cursor)?;
+        }
+
+        for key in keys {
+            if self.done {
+                break;
+            }
+            if let Some(interceptor) = &mut self.interceptor {
+                if interceptor(*key, &mut ed)? {
+                    continue;
+                }
+            }
+            self.done = self
+                .keymap
+                .handle_key(*key, &mut ed, &mut *self.completer)?;
+        }
+
+        self.line = ed.current_buffer().clone();
+        self.cursor = Some(ed.cursor());
+        Ok(self.line.to_string())
+    }
+
+    /// Like `feed` for keys without modifiers.
+    pub fn feed_codes<'k, I>(&mut self, codes: I) -> io::Result<String>
+    where
+        I: IntoIterator<Item = &'k KeyCode>,
+    {
+        let keys: Vec<Key> = codes.into_iter().map(|code| Key::new(*code)).collect();
+        self.feed(&keys)
+    }
+
+    /// Types `text` one character at a time.
+    pub fn feed_str(&mut self, text: &str) -> io::Result<String> {
+        let keys: Vec<Key> = text.chars().map(|c| Key::new(KeyCode::Char(c))).collect();
+        self.feed(&keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{Emacs, Vi};
+    use crate::BasicCompleter;
+    use sl_console::event::KeyMod;
+
+    #[test]
+    fn state_carries_over() {
+        let mut ed = TestEditor::new(Box::new(Vi::new()));
+        assert_eq!(ed.feed_str("foo bar").unwrap(), "foo bar");
+        ed.feed_codes(&[KeyCode::Esc, KeyCode::Char('b')]).unwrap();
+        assert_eq!(ed.cursor(), 4);
+        assert_eq!(ed.feed_codes(&[KeyCode::Char('D')]).unwrap(), "foo ");
+        assert_eq!(ed.feed_codes(&[KeyCode::Char('u')]).unwrap(), "foo bar");
+        assert!(!ed.is_done());
+        ed.feed_codes(&[KeyCode::Char('\n')]).unwrap();
+        assert!(ed.is_done());
+    }
+
+    #[test]
+    fn vi_normal_mode_carries_over() {
+        let mut ed = TestEditor::new(Box::new(Vi::new()));
+        ed.feed_str("abc").unwrap();
+        ed.feed_codes(&[KeyCode::Esc]).unwrap();
+        let line = ed
+            .feed_codes(&[KeyCode::Char('$'), KeyCode::Char('x')])
+            .unwrap();
+        assert_eq!(line, "ab");
+        assert_eq!(ed.cursor(), 1);
+    }
+
+    #[test]
+    fn completer_and_setup() {
+        let mut ed = TestEditor::new(Box::new(Emacs::new()));
+        ed.set_completer(Box::new(BasicCompleter::new(vec!["hello"])))
+            .set_editor_setup(|ed| ed.set_max_length(Some(8)))
+            .set_line("he");
+        assert_eq!(ed.feed_codes(&[KeyCode::Char('\t')]).unwrap(), "hello");
+        assert_eq!(ed.feed_str(" world").unwrap(), "hello wo");
+        assert_eq!(
+            ed.feed(&[Key::new_mod(KeyCode::Char('u'), KeyMod::Ctrl)])
+                .unwrap(),
+            ""
+        );
+        assert!(!ed.output().is_empty());
+    }
+
+    #[test]
+    fn key_interceptor_goes_first() {
+        let mut ed = TestEditor::new(Box::new(Emacs::new()));
+        ed.set_key_interceptor(Some(Box::new(|key, ed| match key.code {
+            KeyCode::F(5) => {
+                ed.insert_str_after_cursor(" --run")?;
+                Ok(true)
+            }
+            // swallowed
+            KeyCode::Char('x') => Ok(true),
+            _ => Ok(false),
+        })));
+        assert_eq!(ed.feed_str("cargo xbuild").unwrap(), "cargo build");
+        assert_eq!(
+            ed.feed_codes(&[KeyCode::F(5)]).unwrap(),
+            "cargo build --run"
+        );
+    }
+}
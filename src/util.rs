@@ -1,9 +1,115 @@
 use std::{borrow::Cow, io};
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
 pub fn last_prompt_line_width<S: AsRef<str>>(s: S) -> usize {
     let last_prompt_line_width = handle_prompt(s.as_ref());
-    remove_codes(last_prompt_line_width).graphemes(true).count()
+    str_width(&remove_codes(last_prompt_line_width))
+}
+
+/// Display width of a grapheme cluster. This is the width of its widest char, so zero width
+/// joiners and combining marks add nothing and a ZWJ emoji sequence is as wide as one emoji.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme
+        .chars()
+        .map(|c| c.width().unwrap_or(0))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Display width of a string, summed over its grapheme clusters.
+pub fn str_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+/// `s` as it is drawn: tabs expanded with spaces to the next multiple of `tab_stop` columns,
+/// counted from the start of the line, other ASCII control characters except newline shown in
+/// caret notation (`^A`, `^[`, `^?`) and the non-ASCII ones (C1 controls like U+0085) as
+/// `<U+0085>`. None if it has no control characters.
+pub fn shown_text(s: &str, tab_stop: usize) -> Option<String> {
+    if !s.chars().any(|c| c.is_control() && c != '\n') {
+        return None;
+    }
+    let tab_stop = tab_stop.max(1);
+    let mut shown = String::with_capacity(s.len() + tab_stop);
+    let mut col = 0;
+    for grapheme in s.graphemes(true) {
+        col = push_shown(&mut shown, grapheme, col, tab_stop);
+    }
+    Some(shown)
+}
+
+/// Columns `grapheme` takes when drawn at column `col` of its line, see `shown_text`.
+pub(crate) fn shown_width(grapheme: &str, col: usize, tab_stop: usize) -> usize {
+    if !grapheme.chars().any(|c| c.is_control()) {
+        return grapheme_width(grapheme);
+    }
+    let mut shown = String::new();
+    push_shown(&mut shown, grapheme, col, tab_stop.max(1)) - col
+}
+
+// Appends `grapheme` to `shown` the way `shown_text` draws it at column `col` and returns the
+// column after it.
+fn push_shown(shown: &mut String, grapheme: &str, mut col: usize, tab_stop: usize) -> usize {
+    if !grapheme.chars().any(|c| c.is_control()) {
+        shown.push_str(grapheme);
+        return col + grapheme_width(grapheme);
+    }
+    for c in grapheme.chars() {
+        match c {
+            '\n' => {
+                shown.push(c);
+                col = 0;
+            }
+            '\t' => {
+                let spaces = tab_stop - col % tab_stop;
+                shown.push_str(&" ".repeat(spaces));
+                col += spaces;
+            }
+            '\x7f' => {
+                shown.push_str("^?");
+                col += 2;
+            }
+            c if c.is_ascii_control() => {
+                shown.push('^');
+                shown.push((c as u8 + b'@') as char);
+                col += 2;
+            }
+            c if c.is_control() => {
+                let code = format!("<U+{:04X}>", c as u32);
+                col += code.len();
+                shown.push_str(&code);
+            }
+            c => {
+                shown.push(c);
+                col += c.width().unwrap_or(0);
+            }
+        }
+    }
+    col
+}
+
+/// The longest start of `s` at most `max_cols` columns wide. It is cut between grapheme clusters
+/// so no character is split, a wide character that would go past `max_cols` is left out whole.
+pub fn truncate_graphemes(s: &str, max_cols: usize) -> &str {
+    let mut width = 0;
+    for (i, grapheme) in s.grapheme_indices(true) {
+        width += grapheme_width(grapheme);
+        if width > max_cols {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// `s` if it fits in `max_cols` columns, else as much of it as fits followed by an ellipsis.
+pub fn truncate_with_ellipsis(s: &str, max_cols: usize) -> Cow<'_, str> {
+    if str_width(s) <= max_cols {
+        Cow::Borrowed(s)
+    } else {
+        let cut = truncate_graphemes(s, max_cols.saturating_sub(1));
+        Cow::Owned(format!("{}…", cut))
+    }
 }
 
 pub fn find_longest_common_prefix<T: Clone + Eq>(among: &[Vec<T>]) -> Option<Vec<T>> {
@@ -88,13 +194,66 @@ pub fn handle_prompt(full_prompt: &str) -> &str {
 }
 
 pub fn terminal_width() -> io::Result<usize> {
+    let (size_col, _) = terminal_size()?;
+    Ok(size_col as usize)
+}
+
+/// Columns and rows of the terminal, asked for each time so a resize is picked up. A size the
+/// terminal reports as 0 is taken as 80 columns or 24 rows.
+pub fn terminal_size() -> io::Result<(u16, u16)> {
     if cfg!(test) {
-        Ok(80_usize)
+        Ok((80, 24))
     } else {
-        let (mut size_col, _) = ::sl_console::terminal_size()?;
+        let (mut size_col, mut size_row) = ::sl_console::terminal_size()?;
         if size_col == 0 {
             size_col = 80;
         }
-        Ok(size_col as usize)
+        if size_row == 0 {
+            size_row = 24;
+        }
+        Ok((size_col, size_row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_chars_shown() {
+        let shown = shown_text("a\x01b", 8).unwrap();
+        assert_eq!(shown, "a^Ab");
+        assert_eq!(str_width(&shown), 4);
+        let shown = shown_text("a\u{85}b\x1b", 8).unwrap();
+        assert_eq!(shown, "a<U+0085>b^[");
+        assert_eq!(str_width(&shown), 12);
+        // the tab stop is counted from the shown width
+        assert_eq!(shown_text("\x01\tb", 4).unwrap(), "^A  b");
+        assert_eq!(shown_text("line\nnext", 8), None);
+    }
+
+    #[test]
+    fn truncate_wide_graphemes() {
+        assert_eq!(truncate_graphemes("日本語テキスト", 6), "日本語");
+        // a wide character that does not fit is left out whole
+        assert_eq!(truncate_graphemes("日本語テキスト", 5), "日本");
+        assert_eq!(truncate_graphemes("ab日本", 3), "ab");
+        assert_eq!(
+            truncate_graphemes("e\u{301}e\u{301}x", 2),
+            "e\u{301}e\u{301}"
+        );
+        assert_eq!(truncate_graphemes("short", 10), "short");
+        assert_eq!(truncate_graphemes("abc", 0), "");
+
+        assert_eq!(
+            truncate_with_ellipsis("日本語テキスト", 14),
+            "日本語テキスト"
+        );
+        assert_eq!(truncate_with_ellipsis("日本語テキスト", 7), "日本語…");
+        assert_eq!(truncate_with_ellipsis("日本語テキスト", 6), "日本…");
+        assert_eq!(
+            str_width(&truncate_with_ellipsis("cargo build --release", 10)),
+            10
+        );
     }
 }